@@ -0,0 +1,38 @@
+use image_forensics::{analysis::blur_estimation::BlurAnalyzer, error::Result};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/portrait.jpg")?;
+
+    let analyzer = BlurAnalyzer::new();
+    let result = analyzer.analyze(&image)?;
+
+    println!("Median blur: {:.2}px", result.median_blur);
+    let measured = result.block_blur.iter().flatten().count();
+    println!(
+        "Blocks with a blur estimate: {} of {}",
+        measured,
+        result.block_blur.len()
+    );
+
+    for region in &result.inconsistent_regions {
+        println!(
+            "{:?} region at ({}, {}) {}x{}: {:.2}px against {:.2}px around it (confidence {:.2})",
+            region.kind,
+            region.region.x,
+            region.region.y,
+            region.region.width,
+            region.region.height,
+            region.blur,
+            region.surrounding_blur,
+            region.confidence
+        );
+    }
+    println!(
+        "Manipulation probability: {:.2}",
+        result.manipulation_probability
+    );
+
+    result.visualization.save("output/blur_map.png")?;
+
+    Ok(())
+}
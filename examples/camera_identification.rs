@@ -0,0 +1,29 @@
+use image_forensics::{
+    analysis::{camera_identification::CameraIdentifier, prnu_fingerprint::CameraFingerprint},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let fingerprint = CameraFingerprint::load("output/camera.prnu")?;
+    let query = image::open("evidences/query.jpg")?;
+
+    let identifier = CameraIdentifier::new();
+    let result = identifier.identify(&fingerprint, &query)?;
+
+    println!("PCE: {:.1}", result.pce);
+    println!("Correlation: {:.4}", result.peak_correlation);
+    println!(
+        "Best offset: ({}, {}) at scale {:.3}",
+        result.offset_x, result.offset_y, result.scale
+    );
+    println!(
+        "Decision: {}",
+        if result.is_match {
+            "taken with this camera"
+        } else {
+            "no match"
+        }
+    );
+
+    Ok(())
+}
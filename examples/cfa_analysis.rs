@@ -1,6 +1,7 @@
 use image_forensics::{
     analysis::cfa_analysis::{CfaAnalyzer, CfaConfig, CfaPattern},
     error::Result,
+    metadata::exif::ExifExtractor,
 };
 
 fn main() -> Result<()> {
@@ -41,5 +42,49 @@ fn main() -> Result<()> {
         .consistency_map
         .save("output/cfa_consistency.png")?;
 
+    let em_result = cfa_analyzer.analyze_em(&image)?;
+
+    println!(
+        "Blocks with demosaicing peak: {:.1}% (threshold {:.2})",
+        em_result.cfa_detected_fraction * 100.0,
+        em_result.peak_threshold
+    );
+    for (i, region) in em_result.missing_regions.iter().enumerate() {
+        println!(
+            "  {}. ({}, {}) {}x{}",
+            i + 1,
+            region.x,
+            region.y,
+            region.width,
+            region.height
+        );
+    }
+
+    em_result
+        .localization_map
+        .save("output/cfa_em_localization.png")?;
+
+    let layout = cfa_analyzer.analyze_layout(&image)?;
+
+    println!(
+        "Layout: {:?} (phase {:?}, confidence {:.2})",
+        layout.pattern, layout.phase, layout.confidence
+    );
+    println!("Demosaicing: {:?}", layout.demosaicing);
+    if let Some(fit) = &layout.kernel_fit {
+        println!(
+            "  Kernel fit: intra {:.3}, cross {:.3}, directional {:.3}",
+            fit.intra_channel, fit.cross_channel, fit.directional
+        );
+    }
+
+    let metadata = ExifExtractor::extract("evidences/copy_move.png")?;
+    let check = cfa_analyzer.check_metadata(&layout, &metadata);
+
+    println!("Expected layout: {:?}", check.expected_layout);
+    for finding in &check.findings {
+        println!("  {}", finding);
+    }
+
     Ok(())
 }
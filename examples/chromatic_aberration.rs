@@ -1,4 +1,7 @@
-use image_forensics::{analysis::chromatic_aberration::ChromaticAberrationAnalyzer, error::Result};
+use image_forensics::{
+    analysis::chromatic_aberration::{ChromaticAberrationAnalyzer, LensProfile},
+    error::Result,
+};
 
 fn main() -> Result<()> {
     let image = image::open("evidences/copy_move.png")?;
@@ -22,8 +25,34 @@ fn main() -> Result<()> {
 
     if let Some(ref model) = ca_result.radial_model {
         println!("Radial model fit: {:.2}", model.fit_quality);
-        println!("Red coefficient: {:.4}", model.k_red);
-        println!("Blue coefficient: {:.4}", model.k_blue);
+        println!("Center estimated: {}", model.center_estimated);
+        println!("Red coefficients: {:?}", model.red_coefficients);
+        println!("Blue coefficients: {:?}", model.blue_coefficients);
+        println!("Residual scale: {:.3}px", model.residual_scale);
+    }
+
+    let outliers = ca_result
+        .residuals
+        .iter()
+        .filter(|r| r.weight < 0.5)
+        .count();
+    println!("Outlier blocks: {}/{}", outliers, ca_result.residuals.len());
+
+    let profile = LensProfile::from_lensfun_poly3(
+        "Example 18-55mm",
+        [1.0003, 0.0, 0.0002],
+        [0.9997, 0.0, -0.0001],
+    );
+    let comparison = ca_analyzer.compare_lens_profile(&ca_result, &profile);
+    println!(
+        "Lens profile {}: {:?} (expected {:.2}px, measured {:.2}px)",
+        comparison.lens,
+        comparison.verdict,
+        comparison.expected_peak_shift,
+        comparison.measured_peak_shift
+    );
+    for finding in &comparison.findings {
+        println!("  - {}", finding);
     }
 
     ca_result
@@ -0,0 +1,58 @@
+use image_forensics::{
+    analysis::contrast_enhancement::{ContrastAnalyzer, ContrastMapping},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/retouched.jpg")?;
+
+    let analyzer = ContrastAnalyzer::new();
+    let result = analyzer.analyze(&image)?;
+
+    for (name, channel) in ["Red", "Green", "Blue"].iter().zip(&result.channels) {
+        println!(
+            "{} channel: energy ratio {:.1}, probability {:.2}",
+            name, channel.energy_ratio, channel.probability
+        );
+        if let Some(estimate) = &channel.mapping {
+            match estimate.mapping {
+                ContrastMapping::Gamma(gamma) => {
+                    println!("  Gamma {:.2} (fit {:.2})", gamma, estimate.fit)
+                }
+                ContrastMapping::Linear { gain, offset } => println!(
+                    "  Linear stretch: gain {:.2}, offset {:.2} (fit {:.2})",
+                    gain, offset, estimate.fit
+                ),
+            }
+        }
+    }
+
+    for region in &result.enhanced_regions {
+        println!(
+            "Region at ({}, {}) {}x{}: energy {:.1}x against {:.1}x for the image (confidence {:.2})",
+            region.region.x,
+            region.region.y,
+            region.region.width,
+            region.region.height,
+            region.log_energy.exp(),
+            region.image_log_energy.exp(),
+            region.confidence
+        );
+        if let Some(estimate) = &region.mapping {
+            println!(
+                "  Mapping: {:?} (fit {:.2})",
+                estimate.mapping, estimate.fit
+            );
+        }
+    }
+    println!(
+        "Manipulation probability: {:.2}",
+        result.manipulation_probability
+    );
+
+    result
+        .visualization
+        .save("output/contrast_enhancement.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use image_forensics::{
+    analysis::corneal_reflection::{CornealReflectionAnalyzer, EyeRegion},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/group_photo.jpg")?;
+
+    let analyzer = CornealReflectionAnalyzer::new();
+
+    // Irises can be marked by hand; `analyze` finds them automatically instead.
+    let eyes = [
+        EyeRegion {
+            person: "left".to_string(),
+            center: (212.0, 180.0),
+            radius: 9.0,
+        },
+        EyeRegion {
+            person: "left".to_string(),
+            center: (262.0, 181.0),
+            radius: 9.0,
+        },
+        EyeRegion {
+            person: "right".to_string(),
+            center: (540.0, 196.0),
+            radius: 8.0,
+        },
+        EyeRegion {
+            person: "right".to_string(),
+            center: (586.0, 195.0),
+            radius: 8.0,
+        },
+    ];
+    let result = analyzer.analyze_eyes(&image, &eyes)?;
+
+    for person in &result.people {
+        println!("{}:", person.person);
+        if let Some(light) = person.light_direction {
+            println!(
+                "  Light direction: ({:.2}, {:.2}, {:.2})",
+                light[0], light[1], light[2]
+            );
+        }
+        if let Some(angle) = person.eye_disagreement {
+            println!("  Between eyes: {:.1} deg", angle.to_degrees());
+        }
+        if let Some(angle) = person.scene_disagreement {
+            println!("  Against other people: {:.1} deg", angle.to_degrees());
+        }
+    }
+
+    for manipulation in &result.manipulations {
+        println!(
+            "{} ({:?}, confidence {:.2})",
+            manipulation.description, manipulation.confidence_level, manipulation.confidence
+        );
+        for evidence in &manipulation.evidence {
+            println!("  - {}", evidence);
+        }
+    }
+
+    result.visualization.save("output/corneal_reflection.png")?;
+
+    Ok(())
+}
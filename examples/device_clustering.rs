@@ -0,0 +1,40 @@
+use std::fs;
+
+use image_forensics::{analysis::device_clustering::DeviceClusterer, error::Result};
+
+fn main() -> Result<()> {
+    let clusterer = DeviceClusterer::new();
+    let result = clusterer.cluster_directory("evidences/phone_dump")?;
+
+    println!(
+        "{} images, {} probable devices (crop {}x{}, threshold {:.4})",
+        result.names.len(),
+        result.clusters.len(),
+        result.crop_size,
+        result.crop_size,
+        result.correlation_threshold
+    );
+    println!("Skipped images: {}", result.skipped.len());
+
+    for (i, cluster) in result.clusters.iter().enumerate() {
+        match &cluster.statistics {
+            Some(stats) => println!(
+                "Device {}: {} images, correlation {:.4} (min {:.4}, max {:.4})",
+                i + 1,
+                cluster.members.len(),
+                stats.mean_correlation,
+                stats.min_correlation,
+                stats.max_correlation
+            ),
+            None => println!("Device {}: single image", i + 1),
+        }
+
+        for &member in &cluster.members {
+            println!("  {}", result.names[member]);
+        }
+    }
+
+    fs::write("output/device_dendrogram.json", result.dendrogram_json()?)?;
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use image_forensics::{analysis::donor_search::DonorSearcher, error::Result};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/splicing.png")?;
+
+    let donor_searcher = DonorSearcher::new();
+    let donor_result = donor_searcher.search(&image, "evidences/corpus")?;
+
+    println!("Searched images: {}", donor_result.searched_images);
+    println!("Skipped images: {}", donor_result.skipped_images.len());
+    println!("Confidence: {:.1}%", donor_result.confidence * 100.0);
+
+    for (i, donor_match) in donor_result.matches.iter().take(5).enumerate() {
+        println!(
+            "  {}, {} ({}, {}) -> Query ({}, {}) | Scale: {:.2} | Rotation: {:.1} deg | Support: {}",
+            i + 1,
+            donor_match.donor_path.display(),
+            donor_match.pair.source.x,
+            donor_match.pair.source.y,
+            donor_match.pair.target.x,
+            donor_match.pair.target.y,
+            donor_match.transform.scale,
+            donor_match.transform.rotation,
+            donor_match.supporting_matches
+        );
+    }
+
+    donor_result
+        .visualization
+        .save("output/donor_search_result.png")?;
+
+    Ok(())
+}
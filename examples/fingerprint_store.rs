@@ -0,0 +1,39 @@
+use image_forensics::{
+    analysis::{
+        camera_identification::CameraIdentifier, fingerprint_store::FingerprintStore,
+        prnu_fingerprint::PrnuFingerprintEstimator,
+    },
+    error::Result,
+    image_utils::list_images,
+};
+
+fn main() -> Result<()> {
+    let mut store = FingerprintStore::open("output/fingerprints")?;
+
+    let estimator = PrnuFingerprintEstimator::new();
+    let fingerprint = estimator.estimate_paths(&list_images("evidences/camera")?)?;
+    store.add("Canon", "EOS 80D", "032021001234", &fingerprint)?;
+
+    println!("{} devices in store", store.len());
+
+    let query = image::open("evidences/query.jpg")?;
+    let matching = store.match_image(&query, &CameraIdentifier::new())?;
+
+    for (i, m) in matching.matches.iter().enumerate() {
+        println!(
+            "  {}. {} {} (serial {}): PCE {:.1}{}",
+            i + 1,
+            m.device.make,
+            m.device.model,
+            m.device.serial,
+            m.result.pce,
+            if m.result.is_match { " [match]" } else { "" }
+        );
+    }
+
+    for (id, error) in &matching.skipped {
+        println!("  Skipped {}: {}", id, error);
+    }
+
+    Ok(())
+}
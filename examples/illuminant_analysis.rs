@@ -0,0 +1,40 @@
+use image_forensics::{analysis::illuminant_analysis::IlluminantAnalyzer, error::Result};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/portrait.jpg")?;
+
+    let analyzer = IlluminantAnalyzer::new();
+    let result = analyzer.analyze(&image)?;
+
+    let [r, g, b] = result.scene_illuminant;
+    println!(
+        "Scene illuminant chromaticity: ({:.3}, {:.3}, {:.3})",
+        r, g, b
+    );
+    println!("Superpixels: {}", result.superpixels.len());
+    println!("Flagging threshold: {:.1} deg", result.threshold);
+
+    for region in &result.inconsistent_regions {
+        let [r, g, b] = region.illuminant;
+        println!(
+            "Region at ({}, {}) {}x{}: illuminant ({:.3}, {:.3}, {:.3}), {:.1} deg off the scene",
+            region.region.x,
+            region.region.y,
+            region.region.width,
+            region.region.height,
+            r,
+            g,
+            b,
+            region.mean_deviation
+        );
+    }
+    println!(
+        "Manipulation probability: {:.2}",
+        result.manipulation_probability
+    );
+
+    result.illuminant_map.save("output/illuminant_map.png")?;
+    result.visualization.save("output/illuminant.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use image_forensics::{analysis::image_comparison::ImageComparator, error::Result};
+
+fn main() -> Result<()> {
+    let original = image::open("evidences/claimed_original.jpg")?;
+    let published = image::open("evidences/published.jpg")?;
+
+    let comparator = ImageComparator::new();
+    let result = comparator.compare(&original, &published)?;
+
+    let summary = &result.summary;
+    println!("Resize factor: {:.3}", summary.resize_factor);
+    println!("Rotation: {:.2} deg", summary.rotation);
+    println!(
+        "Crop box: ({}, {}) {}x{}",
+        summary.crop_box.x, summary.crop_box.y, summary.crop_box.width, summary.crop_box.height
+    );
+    println!(
+        "JPEG quality: {} -> {} ({:+})",
+        summary.original_quality, summary.published_quality, summary.quality_change
+    );
+    println!(
+        "Registration confidence: {:.3}",
+        result.registration.confidence
+    );
+    println!("Changed area: {:.1}%", result.changed_fraction * 100.0);
+
+    for (i, region) in result.changed_regions.iter().enumerate() {
+        println!(
+            "  {}. ({}, {}) {}x{}",
+            i + 1,
+            region.x,
+            region.y,
+            region.width,
+            region.height
+        );
+    }
+
+    result.heatmap.save("output/comparison_heatmap.png")?;
+    result.visualization.save("output/comparison_result.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use image_forensics::{
+    analysis::lighting_estimation::{LightingAnalyzer, OccludingContour},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/shadow.png")?;
+
+    let analyzer = LightingAnalyzer::new();
+    let result = analyzer.analyze(&image)?;
+
+    println!("Objects: {}", result.objects.len());
+    for object in &result.objects {
+        println!(
+            "{}: azimuth {:.1} ± {:.1} deg, {} samples{}",
+            object.label,
+            object.azimuth.to_degrees(),
+            object.angular_uncertainty.to_degrees(),
+            object.samples,
+            if object.inconsistent {
+                " (inconsistent)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    if let Some(azimuth) = result.scene_azimuth {
+        println!("Scene light azimuth: {:.1} deg", azimuth.to_degrees());
+    }
+    println!(
+        "Consistency score: {:.1}%",
+        result.consistency_score * 100.0
+    );
+    println!(
+        "Manipulation probability: {:.1}%",
+        result.manipulation_probability * 100.0
+    );
+
+    let contour = OccludingContour::from_polyline(
+        "marked",
+        &[
+            (120.0, 200.0),
+            (140.0, 160.0),
+            (180.0, 140.0),
+            (220.0, 160.0),
+        ],
+    );
+    let marked = analyzer.analyze_contours(&image, &[contour])?;
+    for object in &marked.objects {
+        println!(
+            "{}: direction ({:.2}, {:.2}, {:.2})",
+            object.label, object.direction.0, object.direction.1, object.direction.2
+        );
+    }
+
+    result
+        .visualization
+        .save("output/lighting_directions.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use image_forensics::{analysis::median_filtering::MedianFilterAnalyzer, error::Result};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/resampled.jpg")?;
+
+    let analyzer = MedianFilterAnalyzer::new();
+    let result = analyzer.analyze(&image)?;
+
+    println!("Streaking ratio: {:.2}", result.global.streaking_ratio);
+    println!("Median residual ratio: {:.3}", result.global.residual_ratio);
+    println!(
+        "Median filtering probability: {:.2}",
+        result.global.probability
+    );
+    println!("Filtered blocks: {:.1}%", result.filtered_fraction * 100.0);
+
+    for region in &result.filtered_regions {
+        println!(
+            "Locally filtered region at ({}, {}) {}x{}",
+            region.x, region.y, region.width, region.height
+        );
+    }
+    println!(
+        "Manipulation probability: {:.2}",
+        result.manipulation_probability
+    );
+
+    result.visualization.save("output/median_filtering.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use image_forensics::{analysis::noise::NoiseAnalyzer, error::Result};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/splicing.png")?;
+
+    let noise_analyzer = NoiseAnalyzer::new().with_block_size(32);
+    let noise_result = noise_analyzer.analyze_wavelet(&image)?;
+
+    println!(
+        "Global noise sigma: {:.2} gray levels",
+        noise_result.global_sigma
+    );
+    println!("Calibration factor: {:.3}", noise_result.calibration_factor);
+    println!(
+        "Excluded blocks: {}/{}",
+        noise_result.excluded_blocks,
+        noise_result.sigma_map.len()
+    );
+    println!(
+        "Inconsistency score: {:.1}%",
+        noise_result.inconsistency_score * 100.0
+    );
+
+    for (i, region) in noise_result.anomalous_regions.iter().take(10).enumerate() {
+        let sigma = noise_result.sigma_map[[(region.y / 32) as usize, (region.x / 32) as usize]];
+        println!(
+            "  {}. ({}, {}) sigma {:.2}",
+            i + 1,
+            region.x,
+            region.y,
+            sigma
+        );
+    }
+
+    noise_result
+        .sigma_image
+        .save("output/noise_sigma_map.png")?;
+
+    Ok(())
+}
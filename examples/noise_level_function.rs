@@ -0,0 +1,41 @@
+use image_forensics::{analysis::noise_level_function::NoiseLevelAnalyzer, error::Result};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/splicing.png")?;
+
+    let analyzer = NoiseLevelAnalyzer::new();
+    let result = analyzer.analyze(&image)?;
+
+    for (curve, name) in result.curves.iter().zip(["Red", "Green", "Blue"]) {
+        println!(
+            "{} channel: sigma^2 = {:.3} * I + {:.2}",
+            name, curve.poisson_gain, curve.gaussian_variance
+        );
+
+        for (intensity, sigma) in curve.intensities.iter().zip(&curve.sigmas) {
+            println!("  I = {:5.1} -> sigma {:.2}", intensity, sigma);
+        }
+    }
+
+    println!(
+        "Inconsistency score: {:.1}%",
+        result.inconsistency_score * 100.0
+    );
+
+    for (i, region) in result.anomalous_regions.iter().enumerate() {
+        println!(
+            "  {}. ({}, {}) {}x{}",
+            i + 1,
+            region.x,
+            region.y,
+            region.width,
+            region.height
+        );
+    }
+
+    result
+        .deviation_map
+        .save("output/noise_level_deviation.png")?;
+
+    Ok(())
+}
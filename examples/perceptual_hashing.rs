@@ -0,0 +1,38 @@
+use image_forensics::{
+    ForensicsAnalyzer,
+    error::Result,
+    hashing::{index::PerceptualHashIndex, perceptual::HashKind},
+};
+
+fn main() -> Result<()> {
+    let mut index = PerceptualHashIndex::new();
+    let indexed = index.add_directory("evidences/corpus")?;
+    index.save("output/corpus_hashes.idx")?;
+
+    println!("Indexed images: {}", indexed.added);
+    println!("Skipped images: {}", indexed.skipped.len());
+
+    let analyzer = ForensicsAnalyzer::new("evidences/splicing.png")?;
+    let hashes = analyzer.perceptual_hashes();
+
+    println!("pHash: {:016x}", hashes.perceptual);
+    println!("Radial hash: {:016x}", hashes.radial);
+
+    let index = PerceptualHashIndex::open("output/corpus_hashes.idx")?;
+
+    for kind in HashKind::ALL {
+        let matches = index.query(&hashes, kind, 10);
+        println!("{:?}: {} near-duplicates", kind, matches.len());
+
+        for (i, hash_match) in matches.iter().take(5).enumerate() {
+            println!(
+                "  {}. {} (distance {})",
+                i + 1,
+                hash_match.path,
+                hash_match.distance
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use image_forensics::{
+    analysis::perspective_analysis::{GroundMarker, ObjectMarker, PerspectiveAnalyzer},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/street.jpg")?;
+
+    let objects = [
+        ObjectMarker {
+            label: "person left".to_string(),
+            base: (212.0, 455.0),
+            top: (209.0, 318.0),
+            reference_height: Some(1.75),
+        },
+        ObjectMarker {
+            label: "person right".to_string(),
+            base: (598.0, 402.0),
+            top: (596.0, 322.0),
+            reference_height: Some(1.75),
+        },
+        ObjectMarker {
+            label: "car".to_string(),
+            base: (420.0, 430.0),
+            top: (419.0, 370.0),
+            reference_height: Some(1.5),
+        },
+    ];
+    let planes = [GroundMarker {
+        label: "pavement".to_string(),
+        parallel_pairs: vec![[
+            ((100.0, 470.0), (300.0, 380.0)),
+            ((400.0, 470.0), (450.0, 380.0)),
+        ]],
+    }];
+
+    let analyzer = PerspectiveAnalyzer::new();
+    let result = analyzer.analyze_with_markers(&image, &objects, &planes)?;
+
+    println!("Line segments: {}", result.segments.len());
+    for vp in &result.vanishing_points {
+        match vp.position {
+            Some((x, y)) => println!(
+                "{:?} vanishing point at ({:.1}, {:.1}), {} segments",
+                vp.kind, x, y, vp.support
+            ),
+            None => println!(
+                "{:?} vanishing point at infinity, {} segments",
+                vp.kind, vp.support
+            ),
+        }
+    }
+    if let Some((a, b, c)) = result.horizon {
+        println!("Horizon: {:.3}x + {:.3}y + {:.1} = 0", a, b, c);
+    }
+    for height in &result.object_heights {
+        println!(
+            "{}: {:.2} camera heights{}",
+            height.label,
+            height.height_ratio,
+            height
+                .implied_camera_height
+                .map(|h| format!(", camera at {:.2}", h))
+                .unwrap_or_default()
+        );
+    }
+    for finding in &result.findings {
+        println!(
+            "{:?} {}: {}",
+            finding.kind, finding.label, finding.explanation
+        );
+    }
+    println!(
+        "Manipulation probability: {:.2}",
+        result.manipulation_probability
+    );
+
+    result.visualization.save("output/perspective.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use image_forensics::{
+    analysis::phylogeny::PhylogenyAnalyzer, error::Result, image_utils::list_images,
+};
+
+fn main() -> Result<()> {
+    let paths = list_images("evidences/near_duplicates")?;
+
+    let analyzer = PhylogenyAnalyzer::new();
+    let result = analyzer.analyze_paths(&paths)?;
+
+    println!("Most likely original: {}", result.original().name);
+
+    for node in &result.nodes {
+        let quality = node
+            .jpeg_quality
+            .map_or("unknown".to_string(), |q| q.to_string());
+
+        match node.parent {
+            Some(parent) => println!(
+                "  {} <- {} | Cost: {:.3} | Quality: {} | Size: {}x{}",
+                node.name, result.nodes[parent].name, node.cost, quality, node.width, node.height
+            ),
+            None => println!(
+                "  {} (root) | Quality: {} | Size: {}x{}",
+                node.name, quality, node.width, node.height
+            ),
+        }
+
+        if let (Some(transform), Some(crop)) = (node.transform, node.crop_box) {
+            println!(
+                "      Scale: {:.2} | Rotation: {:.1} deg | Crop: ({}, {}) {}x{}",
+                transform.scale, transform.rotation, crop.x, crop.y, crop.width, crop.height
+            );
+        }
+    }
+
+    for index in result.unrelated() {
+        println!("Unrelated: {}", result.nodes[index].name);
+    }
+
+    Ok(())
+}
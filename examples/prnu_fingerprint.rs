@@ -0,0 +1,24 @@
+use image_forensics::{
+    analysis::prnu_fingerprint::{CameraFingerprint, PrnuFingerprintEstimator},
+    error::Result,
+    image_utils::list_images,
+};
+
+fn main() -> Result<()> {
+    let paths = list_images("evidences/camera")?;
+
+    let estimator = PrnuFingerprintEstimator::new();
+    let fingerprint = estimator.estimate_paths(&paths)?;
+
+    println!(
+        "Fingerprint {}x{} from {} images",
+        fingerprint.width, fingerprint.height, fingerprint.image_count
+    );
+
+    fingerprint.save("output/camera.prnu")?;
+
+    let loaded = CameraFingerprint::load("output/camera.prnu")?;
+    println!("Reloaded {}x{}", loaded.width, loaded.height);
+
+    Ok(())
+}
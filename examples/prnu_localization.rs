@@ -0,0 +1,38 @@
+use image_forensics::{
+    analysis::{prnu_fingerprint::CameraFingerprint, prnu_localization::PrnuLocalizer},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let fingerprint = CameraFingerprint::load("output/camera.prnu")?;
+    let image = image::open("evidences/splicing.png")?;
+
+    let localizer = PrnuLocalizer::new();
+    let result = localizer.localize(&image, &fingerprint)?;
+
+    println!(
+        "Tampered fraction: {:.1}%",
+        result.tampered_fraction * 100.0
+    );
+    println!(
+        "Correlation spread: {:.4} (authentic), {:.4} (no PRNU)",
+        result.predictor.residual_std, result.null_std
+    );
+
+    for (i, region) in result.tampered_regions.iter().enumerate() {
+        println!(
+            "  {}. ({}, {}) {}x{}",
+            i + 1,
+            region.x,
+            region.y,
+            region.width,
+            region.height
+        );
+    }
+
+    result
+        .probability_image
+        .save("output/prnu_tampering_probability.png")?;
+
+    Ok(())
+}
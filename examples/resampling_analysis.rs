@@ -14,12 +14,49 @@ fn main() -> Result<()> {
         "Periodic patterns: {}",
         resampling_result.periodic_patterns.len()
     );
-    // println!("Estimated factor: {:.2}", resampling_result.estimated_factor);
+    for pattern in &resampling_result.periodic_patterns {
+        println!(
+            "  period {:.2}px at {:.1} deg, strength {:.2}",
+            pattern.period,
+            pattern.direction.to_degrees(),
+            pattern.strength
+        );
+    }
+    if let Some(estimate) = &resampling_result.estimate {
+        println!(
+            "Estimated scale: {:.3} x {:.3}, rotation {:.1} deg",
+            estimate.scale_x,
+            estimate.scale_y,
+            estimate.rotation.to_degrees()
+        );
+    }
     println!(
         "Resampling probability: {:.1}%",
         resampling_result.resampling_probability * 100.0
     );
 
+    let local_result = resampling_detector.detect_local(&image)?;
+
+    println!(
+        "Regions with distinct factors: {}",
+        local_result.regions.len()
+    );
+    for region in &local_result.regions {
+        println!(
+            "  ({}, {}) {}x{}: scale {:.3} x {:.3}, rotation {:.1} deg",
+            region.bounds.x,
+            region.bounds.y,
+            region.bounds.width,
+            region.bounds.height,
+            region.estimate.scale_x,
+            region.estimate.scale_y,
+            region.estimate.rotation.to_degrees()
+        );
+    }
+
+    local_result
+        .factor_image
+        .save("output/resampling_factor_map.png")?;
     resampling_result
         .p_map
         .save("output/resampling_p_map.png")?;
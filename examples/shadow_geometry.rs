@@ -0,0 +1,43 @@
+use image_forensics::{
+    analysis::shadow_geometry::{ShadowConstraint, ShadowGeometryAnalyzer},
+    error::Result,
+};
+
+fn main() -> Result<()> {
+    let image = image::open("evidences/shadow.png")?;
+
+    let tolerance = 3f64.to_radians();
+    let constraints = [
+        ShadowConstraint::new((140.0, 260.0), (100.0, 200.0), tolerance),
+        ShadowConstraint::new((320.0, 270.0), (300.0, 150.0), tolerance),
+        ShadowConstraint::new((530.0, 370.0), (450.0, 220.0), tolerance),
+        ShadowConstraint::new((380.0, 330.0), (330.0, 260.0), tolerance),
+    ];
+
+    let analyzer = ShadowGeometryAnalyzer::new();
+    let result = analyzer.analyze(&image, &constraints)?;
+
+    println!("Feasible: {}", result.feasible);
+    println!("Light behind camera: {}", result.light_behind_camera);
+    if let Some((x, y)) = result.light_position {
+        println!(
+            "Projected light: ({:.1}, {:.1}){}",
+            x,
+            y,
+            if result.unbounded {
+                " (distant light)"
+            } else {
+                ""
+            }
+        );
+    }
+    println!("Consistent constraints: {:?}", result.consistent);
+    println!("Inconsistent constraints: {:?}", result.inconsistent);
+    for conflict in &result.conflicts {
+        println!("  conflicting set: {:?}", conflict);
+    }
+
+    result.visualization.save("output/shadow_geometry.png")?;
+
+    Ok(())
+}
@@ -0,0 +1,545 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+    image_utils::{draw_rectangle, median, rgb_to_luminance},
+};
+
+#[derive(Debug, Clone)]
+pub struct BlurConfig {
+    pub block_size: u32,
+    /// Standard deviation, in pixels, of the Gaussian used to re-blur the image.
+    pub reblur_sigma: f64,
+    /// Smallest gradient magnitude, in gray levels per pixel, of an edge pixel.
+    pub edge_threshold: f64,
+    /// Estimates are clamped to this blur, in pixels.
+    pub max_blur: f64,
+    /// Edge pixels a block needs for its own estimate.
+    pub min_edge_pixels: usize,
+    /// Weight of the smoothness prior relative to a block's own measurement.
+    pub smoothness: f64,
+    /// Color distance at which neighbouring blocks stop being assumed at the same depth.
+    pub appearance_sigma: f64,
+    pub propagation_iterations: usize,
+    /// Neighbouring blocks within this absolute or relative blur difference belong to the
+    /// same region.
+    pub region_tolerance: f64,
+    pub region_relative_tolerance: f64,
+    /// A region is flagged when its blur differs from its surroundings by at least this
+    /// many pixels and by this ratio.
+    pub min_blur_difference: f64,
+    pub min_blur_ratio: f64,
+    pub min_region_blocks: usize,
+    pub max_region_fraction: f64,
+}
+
+impl Default for BlurConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 16,
+            reblur_sigma: 1.0,
+            edge_threshold: 8.0,
+            max_blur: 10.0,
+            min_edge_pixels: 8,
+            smoothness: 1.0,
+            appearance_sigma: 20.0,
+            propagation_iterations: 200,
+            region_tolerance: 0.4,
+            region_relative_tolerance: 0.25,
+            min_blur_difference: 0.75,
+            min_blur_ratio: 1.5,
+            min_region_blocks: 2,
+            max_region_fraction: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlurInconsistency {
+    /// Sharper than its surroundings, e.g. an object pasted from a better focused photo.
+    Sharper,
+    /// Blurrier than its surroundings, e.g. a blur brush or a defocused paste.
+    Blurrier,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlurRegion {
+    pub region: SRegion,
+    pub kind: BlurInconsistency,
+    /// Median measured blur of the region, in pixels.
+    pub blur: f64,
+    /// Median blur of the blocks bordering the region, in pixels.
+    pub surrounding_blur: f64,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlurResult {
+    pub block_size: u32,
+    /// Median Gaussian blur of each block's edges, in pixels, where it has enough edges.
+    pub block_blur: Array2<Option<f64>>,
+    /// Dense blur, in pixels, after propagation; interpolated to every pixel.
+    pub blur_map: Array2<f64>,
+    pub median_blur: f64,
+    pub inconsistent_regions: Vec<BlurRegion>,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+pub struct BlurAnalyzer {
+    config: BlurConfig,
+}
+
+impl BlurAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(BlurConfig::default())
+    }
+
+    pub fn with_config(config: BlurConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn analyze(&self, image: &DynamicImage) -> Result<BlurResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let block_size = self.config.block_size.max(4);
+        if width < 2 * block_size || height < 2 * block_size {
+            return Err(ForensicsError::ImageTooSmall(2 * block_size));
+        }
+
+        let luminance = rgb_to_luminance(&rgb);
+        let edge_blur = self.edge_blur(&luminance);
+
+        let (rows, columns) = (
+            (height / block_size) as usize,
+            (width / block_size) as usize,
+        );
+        let block = block_size as usize;
+        let mut samples = vec![Vec::new(); rows * columns];
+        for ((y, x), sigma) in edge_blur.indexed_iter() {
+            if let Some(sigma) = sigma
+                && y / block < rows
+                && x / block < columns
+            {
+                samples[(y / block) * columns + x / block].push(*sigma);
+            }
+        }
+
+        let mut block_blur = Array2::<Option<f64>>::from_elem((rows, columns), None);
+        let mut weights = Array2::<f64>::zeros((rows, columns));
+        for row in 0..rows {
+            for column in 0..columns {
+                let values = &mut samples[row * columns + column];
+                if !values.is_empty() && values.len() >= self.config.min_edge_pixels {
+                    block_blur[[row, column]] = Some(median(values));
+                    weights[[row, column]] =
+                        (values.len() as f64 / self.config.min_edge_pixels as f64).min(4.0);
+                }
+            }
+        }
+
+        let mut measured = block_blur.iter().flatten().copied().collect::<Vec<_>>();
+        if measured.is_empty() {
+            return Err(ForensicsError::AnalysisFailed(
+                "No block has enough edges to estimate blur".into(),
+            ));
+        }
+        let median_blur = median(&mut measured);
+
+        let appearance = block_colors(&rgb, block, rows, columns);
+        let dense = self.propagate(&block_blur, &weights, &appearance, median_blur);
+        let inconsistent_regions = self.find_regions(&block_blur, &dense, median_blur, block_size);
+
+        let blur_map = upsample(&dense, block, width as usize, height as usize);
+        let manipulation_probability = inconsistent_regions
+            .iter()
+            .map(|r| r.confidence)
+            .fold(0.0, f64::max);
+        let visualization = self.create_visualization(&rgb, &blur_map, &inconsistent_regions);
+
+        Ok(BlurResult {
+            block_size,
+            block_blur,
+            blur_map,
+            median_blur,
+            inconsistent_regions,
+            visualization,
+            manipulation_probability,
+        })
+    }
+
+    /// Blur at edge pixels from the gradient ratio between the image and a re-blurred copy:
+    /// for a Gaussian-blurred step of scale `s`, the ratio is `sqrt(s^2 + s0^2) / s`.
+    fn edge_blur(&self, luminance: &Array2<f64>) -> Array2<Option<f64>> {
+        let (height, width) = luminance.dim();
+        let sigma0 = self.config.reblur_sigma;
+        let reblurred = gaussian_blur(luminance, sigma0);
+        let mut result = Array2::from_elem((height, width), None);
+
+        let gradient = |image: &Array2<f64>, x: usize, y: usize| {
+            let gx = (image[[y, x + 1]] - image[[y, x - 1]]) / 2.0;
+            let gy = (image[[y + 1, x]] - image[[y - 1, x]]) / 2.0;
+            (gx, gy)
+        };
+
+        let mut magnitude = Array2::<f64>::zeros((height, width));
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let (gx, gy) = gradient(luminance, x, y);
+                magnitude[[y, x]] = gx.hypot(gy);
+            }
+        }
+
+        for y in 2..height - 2 {
+            for x in 2..width - 2 {
+                let m = magnitude[[y, x]];
+                if m < self.config.edge_threshold {
+                    continue;
+                }
+
+                // Non-maximum suppression across the edge keeps one estimate per edge.
+                let (gx, gy) = gradient(luminance, x, y);
+                let (dx, dy) = ((gx / m).round() as isize, (gy / m).round() as isize);
+                let ahead = magnitude[[(y as isize + dy) as usize, (x as isize + dx) as usize]];
+                let behind = magnitude[[(y as isize - dy) as usize, (x as isize - dx) as usize]];
+                if m < ahead || m <= behind {
+                    continue;
+                }
+
+                let (rx, ry) = gradient(&reblurred, x, y);
+                let reblurred_magnitude = rx.hypot(ry);
+                if reblurred_magnitude <= 1e-9 {
+                    continue;
+                }
+                let ratio = m / reblurred_magnitude;
+                let sigma = if ratio > 1.0 + 1e-6 {
+                    (sigma0 / (ratio * ratio - 1.0).sqrt()).min(self.config.max_blur)
+                } else {
+                    self.config.max_blur
+                };
+                result[[y, x]] = Some(sigma);
+            }
+        }
+
+        result
+    }
+
+    /// Fills in and smooths the block blur by weighted Jacobi iterations. Neighbours of
+    /// similar color, likely at the same depth, pull hardest, so blur may still jump at
+    /// object boundaries.
+    fn propagate(
+        &self,
+        measured: &Array2<Option<f64>>,
+        weights: &Array2<f64>,
+        appearance: &Array2<[f64; 3]>,
+        initial: f64,
+    ) -> Array2<f64> {
+        let (rows, columns) = measured.dim();
+        let two_sigma_sq = 2.0 * self.config.appearance_sigma.powi(2);
+
+        let mut affinities = Array2::<[f64; 8]>::from_elem((rows, columns), [0.0; 8]);
+        for ((row, column), affinity) in affinities.indexed_iter_mut() {
+            for (k, (dr, dc)) in NEIGHBOURS.iter().enumerate() {
+                if let Some((r, c)) = offset(row, column, *dr, *dc, rows, columns) {
+                    let a = appearance[[row, column]];
+                    let b = appearance[[r, c]];
+                    let distance = (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>();
+                    affinity[k] = (-distance / two_sigma_sq).exp();
+                }
+            }
+        }
+
+        let mut dense = measured.map(|m| m.unwrap_or(initial));
+        for _ in 0..self.config.propagation_iterations {
+            let previous = dense.clone();
+            for ((row, column), value) in dense.indexed_iter_mut() {
+                let data = weights[[row, column]];
+                let mut numerator = data * measured[[row, column]].unwrap_or(0.0);
+                let mut denominator = data;
+                for (k, (dr, dc)) in NEIGHBOURS.iter().enumerate() {
+                    if let Some((r, c)) = offset(row, column, *dr, *dc, rows, columns) {
+                        let w = self.config.smoothness * affinities[[row, column]][k];
+                        numerator += w * previous[[r, c]];
+                        denominator += w;
+                    }
+                }
+                if denominator > 1e-12 {
+                    *value = numerator / denominator;
+                }
+            }
+        }
+
+        dense
+    }
+
+    /// Groups neighbouring blocks of similar blur and flags small groups whose blur departs
+    /// from the blocks around them.
+    fn find_regions(
+        &self,
+        measured: &Array2<Option<f64>>,
+        dense: &Array2<f64>,
+        median_blur: f64,
+        block_size: u32,
+    ) -> Vec<BlurRegion> {
+        let (rows, columns) = dense.dim();
+        let similar = |a: f64, b: f64| {
+            (a - b).abs()
+                <= self
+                    .config
+                    .region_tolerance
+                    .max(self.config.region_relative_tolerance * a.min(b))
+        };
+
+        let mut labels = Array2::<usize>::from_elem((rows, columns), usize::MAX);
+        let mut groups = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                if labels[[row, column]] != usize::MAX {
+                    continue;
+                }
+                // Compare with the group's mean rather than the neighbour, so that slow
+                // depth ramps do not chain everything into one group.
+                let label = groups.len();
+                labels[[row, column]] = label;
+                let mut group = vec![(row, column)];
+                let mut sum = dense[[row, column]];
+                let mut next = 0;
+                while next < group.len() {
+                    let (r, c) = group[next];
+                    next += 1;
+                    for (dr, dc) in NEIGHBOURS {
+                        if let Some((nr, nc)) = offset(r, c, dr, dc, rows, columns)
+                            && labels[[nr, nc]] == usize::MAX
+                            && similar(sum / group.len() as f64, dense[[nr, nc]])
+                        {
+                            labels[[nr, nc]] = label;
+                            group.push((nr, nc));
+                            sum += dense[[nr, nc]];
+                        }
+                    }
+                }
+                groups.push(group);
+            }
+        }
+
+        let max_blocks = self.config.max_region_fraction * (rows * columns) as f64;
+        let mut regions = Vec::new();
+
+        for (label, group) in groups.iter().enumerate() {
+            let mut own = group
+                .iter()
+                .filter_map(|&(r, c)| measured[[r, c]])
+                .collect::<Vec<_>>();
+            if own.len() < self.config.min_region_blocks || group.len() as f64 > max_blocks {
+                continue;
+            }
+
+            let mut ring = Vec::new();
+            for &(r, c) in group {
+                for (dr, dc) in NEIGHBOURS {
+                    if let Some((nr, nc)) = offset(r, c, dr, dc, rows, columns)
+                        && labels[[nr, nc]] != label
+                    {
+                        ring.push(dense[[nr, nc]]);
+                    }
+                }
+            }
+            if ring.is_empty() {
+                continue;
+            }
+
+            // The region must stand out both locally and against the whole image; a patch of
+            // ordinary blur next to a real anomaly only passes the first test.
+            let blur = median(&mut own);
+            let surrounding_blur = median(&mut ring);
+            let ratio = [surrounding_blur, median_blur]
+                .iter()
+                .map(|&other| {
+                    let (low, high) = (blur.min(other), blur.max(other));
+                    if high - low < self.config.min_blur_difference
+                        || (other < blur) != (surrounding_blur < blur)
+                    {
+                        1.0
+                    } else {
+                        high / low.max(1e-6)
+                    }
+                })
+                .fold(f64::MAX, f64::min);
+            // A ratio of one means no qualifying difference, whatever the configured minimum.
+            if ratio <= 1.0 || ratio < self.config.min_blur_ratio {
+                continue;
+            }
+
+            let (min_row, max_row) = group
+                .iter()
+                .fold((usize::MAX, 0), |(lo, hi), &(r, _)| (lo.min(r), hi.max(r)));
+            let (min_column, max_column) = group
+                .iter()
+                .fold((usize::MAX, 0), |(lo, hi), &(_, c)| (lo.min(c), hi.max(c)));
+
+            regions.push(BlurRegion {
+                region: SRegion {
+                    x: min_column as u32 * block_size,
+                    y: min_row as u32 * block_size,
+                    width: (max_column - min_column + 1) as u32 * block_size,
+                    height: (max_row - min_row + 1) as u32 * block_size,
+                },
+                kind: if blur < surrounding_blur {
+                    BlurInconsistency::Sharper
+                } else {
+                    BlurInconsistency::Blurrier
+                },
+                blur,
+                surrounding_blur,
+                confidence: 1.0
+                    - 0.5 * (self.config.min_blur_ratio - 1.0).max(0.0)
+                        / (ratio - 1.0).max(f64::EPSILON),
+            });
+        }
+
+        regions.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        regions
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        blur_map: &Array2<f64>,
+        regions: &[BlurRegion],
+    ) -> RgbImage {
+        let mut vis = original.clone();
+        let max_blur = self
+            .config
+            .max_blur
+            .min(blur_map.iter().copied().fold(0.0, f64::max).max(1e-6));
+
+        for (x, y, pixel) in vis.enumerate_pixels_mut() {
+            let gray = (pixel[0] as f64 + pixel[1] as f64 + pixel[2] as f64) / 3.0;
+            let t = (blur_map[[y as usize, x as usize]] / max_blur).clamp(0.0, 1.0);
+            let heat = [255.0 * t, 64.0, 255.0 * (1.0 - t)];
+            *pixel = Rgb(heat.map(|h| (0.5 * gray + 0.5 * h) as u8));
+        }
+
+        for region in regions {
+            draw_rectangle(&mut vis, &region.region, Rgb([255, 255, 0]));
+        }
+
+        vis
+    }
+}
+
+impl Default for BlurAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+fn offset(
+    row: usize,
+    column: usize,
+    dr: isize,
+    dc: isize,
+    rows: usize,
+    columns: usize,
+) -> Option<(usize, usize)> {
+    let r = row.checked_add_signed(dr)?;
+    let c = column.checked_add_signed(dc)?;
+    (r < rows && c < columns).then_some((r, c))
+}
+
+fn block_colors(rgb: &RgbImage, block: usize, rows: usize, columns: usize) -> Array2<[f64; 3]> {
+    let mut colors = Array2::from_elem((rows, columns), [0.0; 3]);
+    let area = (block * block) as f64;
+    for ((row, column), color) in colors.indexed_iter_mut() {
+        for y in row * block..(row + 1) * block {
+            for x in column * block..(column + 1) * block {
+                let pixel = rgb.get_pixel(x as u32, y as u32);
+                for c in 0..3 {
+                    color[c] += pixel[c] as f64 / area;
+                }
+            }
+        }
+    }
+    colors
+}
+
+/// Separable Gaussian blur with mirrored borders.
+fn gaussian_blur(input: &Array2<f64>, sigma: f64) -> Array2<f64> {
+    let radius = (3.0 * sigma).ceil().max(1.0) as isize;
+    let kernel = (-radius..=radius)
+        .map(|i| (-(i * i) as f64 / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let total = kernel.iter().sum::<f64>();
+    let kernel = kernel.iter().map(|k| k / total).collect::<Vec<_>>();
+
+    let (height, width) = input.dim();
+    let mirror = |i: isize, n: usize| -> usize {
+        let n = n as isize;
+        let mut i = i;
+        if i < 0 {
+            i = -i - 1;
+        }
+        if i >= n {
+            i = 2 * n - i - 1;
+        }
+        i.clamp(0, n - 1) as usize
+    };
+
+    let mut horizontal = Array2::<f64>::zeros((height, width));
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[[y, x]] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * input[[y, mirror(x as isize + k as isize - radius, width)]])
+                .sum();
+        }
+    }
+
+    let mut output = Array2::<f64>::zeros((height, width));
+    for y in 0..height {
+        for x in 0..width {
+            output[[y, x]] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * horizontal[[mirror(y as isize + k as isize - radius, height), x]])
+                .sum();
+        }
+    }
+    output
+}
+
+/// Bilinear interpolation of block values placed at block centers.
+fn upsample(blocks: &Array2<f64>, block: usize, width: usize, height: usize) -> Array2<f64> {
+    let (rows, columns) = blocks.dim();
+    let mut output = Array2::<f64>::zeros((height, width));
+    for y in 0..height {
+        let fy = ((y as f64 + 0.5) / block as f64 - 0.5).clamp(0.0, (rows - 1) as f64);
+        let r0 = fy.floor() as usize;
+        let r1 = (r0 + 1).min(rows - 1);
+        let ty = fy - r0 as f64;
+        for x in 0..width {
+            let fx = ((x as f64 + 0.5) / block as f64 - 0.5).clamp(0.0, (columns - 1) as f64);
+            let c0 = fx.floor() as usize;
+            let c1 = (c0 + 1).min(columns - 1);
+            let tx = fx - c0 as f64;
+            let top = blocks[[r0, c0]] * (1.0 - tx) + blocks[[r0, c1]] * tx;
+            let bottom = blocks[[r1, c0]] * (1.0 - tx) + blocks[[r1, c1]] * tx;
+            output[[y, x]] = top * (1.0 - ty) + bottom * ty;
+        }
+    }
+    output
+}
@@ -0,0 +1,382 @@
+use image::DynamicImage;
+use ndarray::Array2;
+use num_complex::Complex;
+use rayon::prelude::*;
+
+use crate::{
+    analysis::prnu_fingerprint::{
+        CameraFingerprint, PrnuFingerprintConfig, PrnuFingerprintEstimator,
+    },
+    error::{ForensicsError, Result},
+    image_utils::{fft_2d, ifft_2d, rgb_to_luminance},
+};
+
+#[derive(Debug, Clone)]
+pub struct CameraIdentificationConfig {
+    pub fingerprint: PrnuFingerprintConfig,
+    /// Decision threshold on the peak-to-correlation energy.
+    pub pce_threshold: f64,
+    pub min_scale: f64,
+    pub max_scale: f64,
+    /// Relative spacing of the scales tested at full resolution. PRNU decorrelates within a
+    /// fraction of a pixel, so this has to stay small.
+    pub scale_step: f64,
+    /// Downsampling of the coarse scale pass. Block-averaged PRNU tolerates a scale error
+    /// this many times larger, so the coarse pass steps by `scale_step * coarse_factor` at a
+    /// fraction of the cost.
+    pub coarse_factor: usize,
+    /// Best coarse scales refined at full resolution.
+    pub refine_candidates: usize,
+    /// Scales correlated at once; each holds several complex arrays of the sensor size.
+    pub max_parallel_scales: usize,
+    /// Half-size of the square around the peak left out of the correlation energy.
+    pub peak_exclusion: usize,
+}
+
+impl Default for CameraIdentificationConfig {
+    fn default() -> Self {
+        Self {
+            fingerprint: PrnuFingerprintConfig::default(),
+            pce_threshold: 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            scale_step: 0.005,
+            coarse_factor: 4,
+            refine_candidates: 2,
+            max_parallel_scales: 4,
+            peak_exclusion: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentificationResult {
+    pub pce: f64,
+    pub peak_correlation: f64,
+    /// Position of the query's top-left corner in the (rescaled) fingerprint.
+    pub offset_x: u32,
+    pub offset_y: u32,
+    /// Query size relative to the sensor (0.5 = downscaled by half).
+    pub scale: f64,
+    pub is_match: bool,
+}
+
+/// Noise residual and intensity of a query image, extracted once so the query can be
+/// matched against many fingerprints.
+#[derive(Debug, Clone)]
+pub struct QueryFeatures {
+    pub residual: Array2<f64>,
+    pub intensity: Array2<f64>,
+}
+
+pub struct CameraIdentifier {
+    config: CameraIdentificationConfig,
+    estimator: PrnuFingerprintEstimator,
+}
+
+impl CameraIdentifier {
+    pub fn new() -> Self {
+        Self::with_config(CameraIdentificationConfig::default())
+    }
+
+    pub fn with_config(config: CameraIdentificationConfig) -> Self {
+        Self {
+            estimator: PrnuFingerprintEstimator::with_config(config.fingerprint.clone()),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &CameraIdentificationConfig {
+        &self.config
+    }
+
+    /// Searches crop offsets and downscaling factors for the strongest correlation
+    /// between the query's noise residual and the fingerprint multiplied by the query's
+    /// intensity. Scales are found coarse-to-fine.
+    pub fn identify(
+        &self,
+        fingerprint: &CameraFingerprint,
+        query: &DynamicImage,
+    ) -> Result<IdentificationResult> {
+        self.identify_features(fingerprint, &self.query_features(query))
+    }
+
+    /// Extracts the Wiener-filtered noise residual and the luminance of `query`.
+    pub fn query_features(&self, query: &DynamicImage) -> QueryFeatures {
+        let residual = self.estimator.extract_residual(query).mapv(|v| v as f64);
+
+        QueryFeatures {
+            residual: self.estimator.wiener_in_dft(&residual),
+            intensity: rgb_to_luminance(&query.to_rgb8()),
+        }
+    }
+
+    /// Whether a query fits inside a `width` x `height` sensor at the largest tested scale.
+    pub fn fits_sensor(&self, width: u32, height: u32, features: &QueryFeatures) -> bool {
+        let (query_h, query_w) = features.residual.dim();
+        let scale = self.config.max_scale.min(1.0);
+
+        (height as f64 * scale).round() as usize >= query_h
+            && (width as f64 * scale).round() as usize >= query_w
+    }
+
+    /// Same as [`identify`](Self::identify) for a query whose features are already extracted.
+    pub fn identify_features(
+        &self,
+        fingerprint: &CameraFingerprint,
+        features: &QueryFeatures,
+    ) -> Result<IdentificationResult> {
+        let (residual, intensity) = (&features.residual, &features.intensity);
+        let reference = fingerprint.data.mapv(|v| v as f64);
+
+        let factor = self.config.coarse_factor.max(1);
+        let coarse_step = (self.config.scale_step * factor as f64).min(0.5);
+        let mut candidates = if factor > 1 {
+            let residual = Self::downsample(residual, factor);
+            let intensity = Self::downsample(intensity, factor);
+            let reference = Self::downsample(&reference, factor);
+            self.search_scales(
+                &reference,
+                &residual,
+                &intensity,
+                &self.scales(self.config.min_scale, self.config.max_scale, coarse_step),
+            )
+        } else {
+            Vec::new()
+        };
+        candidates.sort_by(|a, b| b.pce.total_cmp(&a.pce));
+        candidates.truncate(self.config.refine_candidates.max(1));
+
+        let fine_scales = if candidates.is_empty() {
+            self.scales(
+                self.config.min_scale,
+                self.config.max_scale,
+                self.config.scale_step,
+            )
+        } else {
+            let mut scales: Vec<f64> = candidates
+                .iter()
+                .flat_map(|c| {
+                    self.scales(
+                        (c.scale * (1.0 - coarse_step)).max(self.config.min_scale),
+                        (c.scale * (1.0 + coarse_step)).min(self.config.max_scale),
+                        self.config.scale_step,
+                    )
+                })
+                .collect();
+            scales.sort_by(|a, b| b.total_cmp(a));
+            scales.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+            scales
+        };
+
+        self.search_scales(&reference, residual, intensity, &fine_scales)
+            .into_iter()
+            .reduce(|a, b| if b.pce > a.pce { b } else { a })
+            .ok_or_else(|| {
+                ForensicsError::InvalidParameter(
+                    "Query image is larger than the fingerprint at every tested scale".into(),
+                )
+            })
+    }
+
+    /// Best offset at each scale the query fits in, a bounded number of scales at a time.
+    fn search_scales(
+        &self,
+        reference: &Array2<f64>,
+        residual: &Array2<f64>,
+        intensity: &Array2<f64>,
+        scales: &[f64],
+    ) -> Vec<IdentificationResult> {
+        let (query_h, query_w) = residual.dim();
+        let (ref_h, ref_w) = reference.dim();
+        let weighted = residual * intensity;
+
+        scales
+            .chunks(self.config.max_parallel_scales.max(1))
+            .flat_map(|chunk| {
+                chunk
+                    .par_iter()
+                    .filter_map(|&scale| {
+                        let scaled_h = (ref_h as f64 * scale).round() as usize;
+                        let scaled_w = (ref_w as f64 * scale).round() as usize;
+
+                        if scaled_h < query_h || scaled_w < query_w {
+                            return None;
+                        }
+
+                        let scaled = if scaled_h == ref_h && scaled_w == ref_w {
+                            reference.clone()
+                        } else {
+                            Self::resample_area(reference, scaled_h, scaled_w)
+                        };
+
+                        Some(self.search_offsets(&scaled, residual, &weighted, intensity, scale))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub fn identify_pattern(
+        &self,
+        pattern: &image::GrayImage,
+        query: &DynamicImage,
+    ) -> Result<IdentificationResult> {
+        self.identify(&CameraFingerprint::from_pattern(pattern), query)
+    }
+
+    /// Peak-to-correlation energy of two equally sized arrays at zero shift.
+    pub fn pce(&self, a: &Array2<f64>, b: &Array2<f64>) -> f64 {
+        let correlation = Self::cross_correlation(a, b);
+        self.peak_energy(&correlation, (0, 0))
+    }
+
+    fn scales(&self, min: f64, max: f64, step: f64) -> Vec<f64> {
+        let max = max.min(1.0);
+        let min = min.clamp(f64::EPSILON, max);
+
+        let ratio = 1.0 - step.clamp(1e-4, 0.5);
+
+        let mut scales = vec![max];
+        let mut scale = max * ratio;
+        while scale > min {
+            scales.push(scale);
+            scale *= ratio;
+        }
+
+        if max - min > 1e-9 {
+            scales.push(min);
+        }
+
+        scales
+    }
+
+    /// `weighted` is the residual times the query intensity, so correlating it with the
+    /// fingerprint correlates the residual with the intensity-weighted fingerprint.
+    fn search_offsets(
+        &self,
+        reference: &Array2<f64>,
+        residual: &Array2<f64>,
+        weighted: &Array2<f64>,
+        intensity: &Array2<f64>,
+        scale: f64,
+    ) -> IdentificationResult {
+        let (ref_h, ref_w) = reference.dim();
+        let (query_h, query_w) = residual.dim();
+
+        let mut padded = Array2::<f64>::zeros((ref_h, ref_w));
+        padded
+            .slice_mut(ndarray::s![..query_h, ..query_w])
+            .assign(weighted);
+
+        let correlation = Self::cross_correlation(&padded, reference);
+
+        let mut peak = (0, 0);
+        let mut peak_value = f64::MIN;
+        for dy in 0..=ref_h - query_h {
+            for dx in 0..=ref_w - query_w {
+                if correlation[[dy, dx]] > peak_value {
+                    peak_value = correlation[[dy, dx]];
+                    peak = (dy, dx);
+                }
+            }
+        }
+
+        let pce = self.peak_energy(&correlation, peak);
+
+        let expected = &reference.slice(ndarray::s![
+            peak.0..peak.0 + query_h,
+            peak.1..peak.1 + query_w
+        ]) * intensity;
+        let norm = (residual.iter().map(|v| v * v).sum::<f64>()
+            * expected.iter().map(|v| v * v).sum::<f64>())
+        .sqrt();
+
+        IdentificationResult {
+            pce,
+            peak_correlation: if norm > 0.0 { peak_value / norm } else { 0.0 },
+            offset_x: peak.1 as u32,
+            offset_y: peak.0 as u32,
+            scale,
+            is_match: pce > self.config.pce_threshold,
+        }
+    }
+
+    /// Circular cross-correlation `C(d) = sum_x a(x) * b(x + d)`.
+    fn cross_correlation(a: &Array2<f64>, b: &Array2<f64>) -> Array2<f64> {
+        let fa = fft_2d(&a.mapv(|v| Complex::new(v, 0.0)));
+        let fb = fft_2d(&b.mapv(|v| Complex::new(v, 0.0)));
+
+        let product = Array2::from_shape_fn(fa.dim(), |idx| fa[idx].conj() * fb[idx]);
+
+        ifft_2d(&product).mapv(|c| c.re)
+    }
+
+    fn peak_energy(&self, correlation: &Array2<f64>, peak: (usize, usize)) -> f64 {
+        let (rows, cols) = correlation.dim();
+        let radius = self.config.peak_exclusion as isize;
+        let peak_value = correlation[peak];
+
+        let near_peak = |y: usize, x: usize| {
+            let dy = Self::circular_distance(y, peak.0, rows);
+            let dx = Self::circular_distance(x, peak.1, cols);
+            dy <= radius && dx <= radius
+        };
+
+        let mut energy = 0.0;
+        let mut count = 0usize;
+        for ((y, x), &v) in correlation.indexed_iter() {
+            if !near_peak(y, x) {
+                energy += v * v;
+                count += 1;
+            }
+        }
+
+        if count == 0 || energy <= 0.0 {
+            return 0.0;
+        }
+
+        let energy = energy / count as f64;
+        peak_value.signum() * peak_value * peak_value / energy
+    }
+
+    fn circular_distance(a: usize, b: usize, n: usize) -> isize {
+        let d = (a as isize - b as isize).rem_euclid(n as isize);
+        d.min(n as isize - d)
+    }
+
+    /// Mean over non-overlapping `factor` x `factor` blocks.
+    fn downsample(input: &Array2<f64>, factor: usize) -> Array2<f64> {
+        let (rows, cols) = input.dim();
+        Self::resample_area(input, (rows / factor).max(1), (cols / factor).max(1))
+    }
+
+    /// Area-averaging resize, matching how PRNU survives an anti-aliased downscale.
+    fn resample_area(input: &Array2<f64>, rows: usize, cols: usize) -> Array2<f64> {
+        let (in_rows, in_cols) = input.dim();
+        let sy = in_rows as f64 / rows as f64;
+        let sx = in_cols as f64 / cols as f64;
+
+        Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let y0 = (y as f64 * sy) as usize;
+            let x0 = (x as f64 * sx) as usize;
+            let y1 = (((y + 1) as f64 * sy).ceil() as usize).clamp(y0 + 1, in_rows);
+            let x1 = (((x + 1) as f64 * sx).ceil() as usize).clamp(x0 + 1, in_cols);
+
+            let mut sum = 0.0;
+            for yy in y0..y1 {
+                for xx in x0..x1 {
+                    sum += input[[yy, xx]];
+                }
+            }
+
+            sum / ((y1 - y0) * (x1 - x0)) as f64
+        })
+    }
+}
+
+impl Default for CameraIdentifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,14 @@
 use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use ndarray::Array2;
+use num_complex::Complex;
+use rayon::prelude::*;
+use statrs::function::erf::erfc_inv;
 
-use crate::{SRegion, error::Result};
+use crate::{
+    MetadataResult, SRegion,
+    error::Result,
+    image_utils::{em_linear_predictor, fft_2d, solve_linear_system},
+};
 
 #[derive(Debug, Clone)]
 pub struct CfaConfig {
@@ -9,6 +17,16 @@ pub struct CfaConfig {
     pub mismatch_threshold: f64,
     pub min_variance: f64,
     pub detect_interpolation: bool,
+    pub em_block_size: u32,
+    pub em_iterations: usize,
+    /// Radius of the linear interpolation neighbourhood used by the EM model.
+    pub em_neighborhood: usize,
+    /// False-alarm rate for declaring a block's demosaicing peak present.
+    pub peak_significance: f64,
+    /// Minimum template correlation for a CFA layout to be reported.
+    pub layout_min_correlation: f64,
+    /// Relative RMS error below which a linear demosaicing kernel is considered exact.
+    pub kernel_fit_threshold: f64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -17,9 +35,89 @@ pub enum CfaPattern {
     BGGR,
     GRBG,
     GBRG,
+    /// Fujifilm 6x6 layout.
+    XTrans,
+    /// Bayer layout with every color site split into 2x2 pixels.
+    QuadBayer,
+    /// Kodak-style 4x4 layout with panchromatic pixels on a checkerboard.
+    Rgbw,
     Unknown,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CfaLayout {
+    Bayer,
+    XTrans,
+    QuadBayer,
+    Rgbw,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DemosaicingFamily {
+    /// Per-channel linear interpolation, e.g. bilinear.
+    Bilinear,
+    /// Linear with cross-channel correction, e.g. Malvar-He-Cutler.
+    GradientCorrected,
+    /// Edge-directed selection between interpolants, e.g. Hamilton-Adams or AHD.
+    AdaptiveHomogeneity,
+    Unknown,
+}
+
+const CANDIDATE_PATTERNS: [CfaPattern; 7] = [
+    CfaPattern::RGGB,
+    CfaPattern::BGGR,
+    CfaPattern::GRBG,
+    CfaPattern::GBRG,
+    CfaPattern::QuadBayer,
+    CfaPattern::XTrans,
+    CfaPattern::Rgbw,
+];
+
+/// Common multiple of all candidate tile sizes.
+const LAYOUT_PERIOD: usize = 12;
+
+impl CfaPattern {
+    pub fn layout(&self) -> CfaLayout {
+        match self {
+            CfaPattern::RGGB | CfaPattern::BGGR | CfaPattern::GRBG | CfaPattern::GBRG => {
+                CfaLayout::Bayer
+            }
+            CfaPattern::XTrans => CfaLayout::XTrans,
+            CfaPattern::QuadBayer => CfaLayout::QuadBayer,
+            CfaPattern::Rgbw => CfaLayout::Rgbw,
+            CfaPattern::Unknown => CfaLayout::Unknown,
+        }
+    }
+
+    /// Repeating tile, one string per row, with `W` for panchromatic pixels.
+    fn tile(&self) -> Option<&'static [&'static str]> {
+        match self {
+            CfaPattern::RGGB => Some(&["RG", "GB"]),
+            CfaPattern::BGGR => Some(&["BG", "GR"]),
+            CfaPattern::GRBG => Some(&["GR", "BG"]),
+            CfaPattern::GBRG => Some(&["GB", "RG"]),
+            CfaPattern::XTrans => {
+                Some(&["GGRGGB", "GGBGGR", "BRGRBG", "GGBGGR", "GGRGGB", "RBGBRG"])
+            }
+            CfaPattern::QuadBayer => Some(&["RRGG", "RRGG", "GGBB", "GGBB"]),
+            CfaPattern::Rgbw => Some(&["WBWG", "BWGW", "WGWR", "GWRW"]),
+            CfaPattern::Unknown => None,
+        }
+    }
+}
+
+/// Channel index sampled at image pixel `(y, x)`, or `None` for panchromatic pixels.
+fn tile_channel(tile: &[&str], phase: (usize, usize), y: usize, x: usize) -> Option<usize> {
+    let period = tile.len();
+    match tile[(y + phase.0) % period].as_bytes()[(x + phase.1) % period] {
+        b'R' => Some(0),
+        b'G' => Some(1),
+        b'B' => Some(2),
+        _ => None,
+    }
+}
+
 impl Default for CfaConfig {
     fn default() -> Self {
         Self {
@@ -28,6 +126,12 @@ impl Default for CfaConfig {
             mismatch_threshold: 0.3,
             min_variance: 10.0,
             detect_interpolation: true,
+            em_block_size: 64,
+            em_iterations: 30,
+            em_neighborhood: 1,
+            peak_significance: 0.001,
+            layout_min_correlation: 0.3,
+            kernel_fit_threshold: 0.2,
         }
     }
 }
@@ -64,6 +168,121 @@ pub struct CfaPatternStats {
     pub unknown_count: usize,
 }
 
+#[derive(Debug, Clone)]
+pub struct CfaInterpolationModel {
+    /// Linear weights over the `(2N + 1)^2 - 1` neighbours, row-major without the center.
+    pub coefficients: Vec<f64>,
+    pub sigma: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CfaEmResult {
+    /// Per-channel EM model, in R, G, B order.
+    pub models: Vec<CfaInterpolationModel>,
+    /// Per-pixel probability of being a linear combination of its neighbours, averaged
+    /// over the three channels.
+    pub probability_map: Array2<f32>,
+    /// Strength of the 2-periodic spectral peak per block, relative to the median
+    /// spectral magnitude.
+    pub peak_scores: Array2<f64>,
+    /// Score a block without demosaicing traces exceeds with probability `peak_significance`.
+    pub peak_threshold: f64,
+    pub missing_peak: Array2<bool>,
+    /// Fraction of blocks with a significant peak.
+    pub cfa_detected_fraction: f64,
+    pub localization_map: GrayImage,
+    /// Blocks lacking the peak. Empty when most of the image lacks it, since then there
+    /// is no demosaicing reference to compare against.
+    pub missing_regions: Vec<SRegion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DemosaicingKernelFit {
+    /// RMS errors of linear models predicting interpolated green samples, relative to
+    /// the RMS deviation of sampled green pixels from their four neighbours' mean.
+    pub intra_channel: f64,
+    pub cross_channel: f64,
+    /// Cross-channel model fitted separately to horizontally and vertically smooth pixels.
+    pub directional: f64,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CfaLayoutResult {
+    pub pattern: CfaPattern,
+    /// Row and column of the image's top-left pixel within the pattern tile.
+    pub phase: (usize, usize),
+    pub confidence: f64,
+    /// Best template correlation of every candidate pattern.
+    pub pattern_scores: Vec<(CfaPattern, f64)>,
+    pub demosaicing: DemosaicingFamily,
+    pub kernel_fit: Option<DemosaicingKernelFit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CfaMetadataCheck {
+    pub camera: Option<String>,
+    /// Layout of the sensor in the camera named by EXIF, when known.
+    pub expected_layout: Option<CfaLayout>,
+    pub detected_layout: CfaLayout,
+    pub demosaicing: DemosaicingFamily,
+    pub findings: Vec<String>,
+}
+
+/// Accumulated least-squares normal equations.
+struct NormalEquations {
+    a: Vec<Vec<f64>>,
+    b: Vec<f64>,
+    yy: f64,
+    n: usize,
+}
+
+impl NormalEquations {
+    fn new(k: usize) -> Self {
+        Self {
+            a: vec![vec![0.0; k]; k],
+            b: vec![0.0; k],
+            yy: 0.0,
+            n: 0,
+        }
+    }
+
+    fn add(&mut self, values: &[f64], target: f64) {
+        for (i, (row, b)) in self.a.iter_mut().zip(self.b.iter_mut()).enumerate() {
+            *b += values[i] * target;
+            for (value, v) in row.iter_mut().zip(values) {
+                *value += values[i] * v;
+            }
+        }
+        self.yy += target * target;
+        self.n += 1;
+    }
+
+    /// Sum of squared residuals of the least-squares solution.
+    fn residual(&self) -> Option<f64> {
+        let k = self.b.len();
+        if self.n < 4 * k {
+            return None;
+        }
+
+        let mut a = self.a.clone();
+        for (i, row) in a.iter_mut().enumerate() {
+            row[i] += 1e-6;
+        }
+        let w = solve_linear_system(a, self.b.clone())?;
+
+        let wb = w.iter().zip(&self.b).map(|(w, b)| w * b).sum::<f64>();
+        let waw = self
+            .a
+            .iter()
+            .zip(&w)
+            .map(|(row, wi)| wi * row.iter().zip(&w).map(|(a, wj)| a * wj).sum::<f64>())
+            .sum::<f64>();
+
+        Some((self.yy - 2.0 * wb + waw).max(0.0))
+    }
+}
+
 pub struct CfaAnalyzer {
     config: CfaConfig,
 }
@@ -373,7 +592,7 @@ impl CfaAnalyzer {
                 CfaPattern::BGGR => stats.bggr_count += 1,
                 CfaPattern::GRBG => stats.grbg_count += 1,
                 CfaPattern::GBRG => stats.gbrg_count += 1,
-                CfaPattern::Unknown => stats.unknown_count += 1,
+                _ => stats.unknown_count += 1,
             }
         }
 
@@ -599,6 +818,511 @@ impl CfaAnalyzer {
 
         probability.min(1.0)
     }
+
+    /// Popescu-Farid EM estimate of the per-pixel probability of being interpolated,
+    /// followed by a per-block search for the 2-periodic peak demosaicing leaves in
+    /// that probability map.
+    pub fn analyze_em(&self, image: &DynamicImage) -> Result<CfaEmResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let block = self.config.em_block_size;
+
+        if width < block * 2 || height < block * 2 {
+            return Err(crate::error::ForensicsError::ImageTooSmall(block * 2));
+        }
+
+        let channels = Self::channel_arrays(&rgb);
+
+        let (models, probabilities): (Vec<_>, Vec<_>) = channels
+            .par_iter()
+            .map(|channel| self.em_interpolation(channel))
+            .unzip();
+
+        let probability_map = Array2::from_shape_fn(probabilities[0].dim(), |idx| {
+            (probabilities.iter().map(|p| p[idx]).sum::<f64>() / 3.0) as f32
+        });
+
+        let block = block as usize;
+        let blocks_y = height as usize / block;
+        let blocks_x = width as usize / block;
+
+        let peak_scores = Array2::from_shape_fn((blocks_y, blocks_x), |(by, bx)| {
+            probabilities
+                .iter()
+                .map(|p| self.periodic_peak(p, by * block, bx * block, block))
+                .fold(0.0, f64::max)
+        });
+
+        // The median is taken over complex bins, whose magnitudes are Rayleigh, so it equals
+        // sigma * sqrt(2 ln 2). The tested bins sit at Nyquist and are purely real: under the
+        // no-peak hypothesis each is half-normal with standard deviation sqrt(2) * sigma, and
+        // its ratio to the median exceeds t with probability erfc(t * sqrt(ln 2 / 2)). The
+        // block score is the max of 9 such values.
+        let tests = 9.0;
+        let per_test = 1.0 - (1.0 - self.config.peak_significance).powf(1.0 / tests);
+        let peak_threshold = (2.0 / std::f64::consts::LN_2).sqrt() * erfc_inv(per_test);
+
+        let missing_peak = peak_scores.mapv(|s| s < peak_threshold);
+        let detected = missing_peak.iter().filter(|&&m| !m).count();
+        let cfa_detected_fraction = detected as f64 / missing_peak.len().max(1) as f64;
+
+        let localization_map = GrayImage::from_fn(width, height, |x, y| {
+            let by = (y as usize / block).min(blocks_y - 1);
+            let bx = (x as usize / block).min(blocks_x - 1);
+            let score = peak_scores[[by, bx]];
+            Luma([(255.0 * (1.0 - score / (2.0 * peak_threshold))).clamp(0.0, 255.0) as u8])
+        });
+
+        let mut missing_regions = Vec::new();
+        if cfa_detected_fraction > 0.5 {
+            for ((by, bx), &missing) in missing_peak.indexed_iter() {
+                if missing {
+                    missing_regions.push(SRegion {
+                        x: (bx * block) as u32,
+                        y: (by * block) as u32,
+                        width: block as u32,
+                        height: block as u32,
+                    });
+                }
+            }
+        }
+
+        Ok(CfaEmResult {
+            models,
+            probability_map,
+            peak_scores,
+            peak_threshold,
+            missing_peak,
+            cfa_detected_fraction,
+            localization_map,
+            missing_regions: self.merge_regions(missing_regions),
+        })
+    }
+
+    /// Identifies the CFA layout from the periodic pattern of interpolation residuals,
+    /// then the demosaicing family from how well linear kernels explain the
+    /// interpolated green samples.
+    pub fn analyze_layout(&self, image: &DynamicImage) -> Result<CfaLayoutResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+
+        if width < self.config.block_size * 2 || height < self.config.block_size * 2 {
+            return Err(crate::error::ForensicsError::ImageTooSmall(
+                self.config.block_size * 2,
+            ));
+        }
+
+        let channels = Self::channel_arrays(&rgb);
+        let tables = self.phase_residual_tables(&channels);
+
+        let mut pattern_scores = Vec::new();
+        let mut best = (CfaPattern::Unknown, (0, 0), f64::MIN);
+
+        for pattern in CANDIDATE_PATTERNS {
+            let Some(tile) = pattern.tile() else {
+                continue;
+            };
+            let period = tile.len();
+
+            // The four Bayer patterns already enumerate the Bayer phases.
+            let phases = if pattern.layout() == CfaLayout::Bayer {
+                vec![(0, 0)]
+            } else {
+                (0..period)
+                    .flat_map(|y| (0..period).map(move |x| (y, x)))
+                    .collect()
+            };
+
+            let (phase, score) = phases
+                .into_iter()
+                .map(|phase| (phase, self.template_correlation(&tables, tile, phase)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or(((0, 0), 0.0));
+
+            pattern_scores.push((pattern, score));
+            if score > best.2 {
+                best = (pattern, phase, score);
+            }
+        }
+
+        let (pattern, phase, confidence) = if best.2 >= self.config.layout_min_correlation {
+            best
+        } else {
+            (CfaPattern::Unknown, (0, 0), best.2.max(0.0))
+        };
+
+        let kernel_fit = self.fit_demosaicing_kernels(&channels, pattern, phase);
+        let demosaicing = kernel_fit
+            .as_ref()
+            .map_or(DemosaicingFamily::Unknown, |fit| {
+                self.classify_demosaicing(fit)
+            });
+
+        Ok(CfaLayoutResult {
+            pattern,
+            phase,
+            confidence,
+            pattern_scores,
+            demosaicing,
+            kernel_fit,
+        })
+    }
+
+    /// Compares the detected layout and demosaicer with what the camera named in the
+    /// metadata would produce.
+    pub fn check_metadata(
+        &self,
+        layout: &CfaLayoutResult,
+        metadata: &MetadataResult,
+    ) -> CfaMetadataCheck {
+        let clean = |s: &String| {
+            s.trim_matches(|c: char| c == '"' || c.is_whitespace())
+                .to_string()
+        };
+        let make = metadata.camera_make.as_ref().map(clean);
+        let model = metadata.camera_model.as_ref().map(clean);
+
+        let camera = match (&make, &model) {
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (Some(name), None) | (None, Some(name)) => Some(name.clone()),
+            (None, None) => None,
+        };
+        let expected_layout = Self::expected_layout(
+            make.as_deref().unwrap_or(""),
+            model.as_deref().unwrap_or(""),
+        );
+        let detected_layout = layout.pattern.layout();
+
+        let mut findings = Vec::new();
+
+        if let (Some(expected), Some(camera)) = (expected_layout, &camera) {
+            if detected_layout != CfaLayout::Unknown && detected_layout != expected {
+                findings.push(format!(
+                    "Detected {:?} CFA layout, but the {} has a {:?} sensor",
+                    detected_layout, camera, expected
+                ));
+            }
+
+            if layout.demosaicing == DemosaicingFamily::Bilinear {
+                findings.push(format!(
+                    "Bilinear demosaicing is not used in-camera; image was not rendered by the {}",
+                    camera
+                ));
+            }
+        }
+
+        CfaMetadataCheck {
+            camera,
+            expected_layout,
+            detected_layout,
+            demosaicing: layout.demosaicing,
+            findings,
+        }
+    }
+
+    /// Sensor layout of well-known camera makes. Phone makers are left out since their
+    /// Quad-Bayer sensors are often remosaiced to Bayer before demosaicing, and so are
+    /// Fujifilm's EXR sensors, which have no matching layout.
+    fn expected_layout(make: &str, model: &str) -> Option<CfaLayout> {
+        let make = make.to_lowercase();
+        let model = model.to_uppercase().replace("FUJIFILM", "");
+        let model = model.trim();
+
+        if make.contains("fujifilm") {
+            let exr_x = ["X10", "X-S1", "XF1"];
+            if exr_x.contains(&model) {
+                return None;
+            }
+
+            let bayer_x = ["X-A", "X-T100", "X-T200"];
+            if model.starts_with('X')
+                && model != "X100"
+                && !bayer_x.iter().any(|m| model.starts_with(m))
+            {
+                return Some(CfaLayout::XTrans);
+            }
+            return Some(CfaLayout::Bayer);
+        }
+
+        let bayer_makes = [
+            "canon",
+            "nikon",
+            "sony",
+            "olympus",
+            "om digital",
+            "panasonic",
+            "pentax",
+            "ricoh",
+            "leica",
+            "hasselblad",
+            "phase one",
+        ];
+
+        bayer_makes
+            .iter()
+            .any(|m| make.contains(m))
+            .then_some(CfaLayout::Bayer)
+    }
+
+    fn channel_arrays(rgb: &RgbImage) -> Vec<Array2<f64>> {
+        let (width, height) = rgb.dimensions();
+
+        (0..3)
+            .map(|c| {
+                Array2::from_shape_fn((height as usize, width as usize), |(y, x)| {
+                    rgb.get_pixel(x as u32, y as u32)[c] as f64
+                })
+            })
+            .collect()
+    }
+
+    /// Mean absolute deviation from the four-neighbour mean per position modulo
+    /// `LAYOUT_PERIOD`, relative to the channel mean. Sampled pixels deviate more than
+    /// interpolated ones.
+    fn phase_residual_tables(&self, channels: &[Array2<f64>]) -> Vec<Array2<f64>> {
+        channels
+            .par_iter()
+            .map(|channel| {
+                let (height, width) = channel.dim();
+                let mut sums = Array2::<f64>::zeros((LAYOUT_PERIOD, LAYOUT_PERIOD));
+                let mut counts = Array2::<f64>::zeros((LAYOUT_PERIOD, LAYOUT_PERIOD));
+
+                for y in 1..height - 1 {
+                    for x in 1..width - 1 {
+                        let mean = (channel[[y - 1, x]]
+                            + channel[[y + 1, x]]
+                            + channel[[y, x - 1]]
+                            + channel[[y, x + 1]])
+                            / 4.0;
+                        let cell = [y % LAYOUT_PERIOD, x % LAYOUT_PERIOD];
+                        sums[cell] += (channel[[y, x]] - mean).abs();
+                        counts[cell] += 1.0;
+                    }
+                }
+
+                let table = sums / counts.mapv(|c: f64| c.max(1.0));
+                let mean = table.mean().unwrap_or(0.0);
+                if mean > 0.0 {
+                    table.mapv(|v| v / mean - 1.0)
+                } else {
+                    table.mapv(|_| 0.0)
+                }
+            })
+            .collect()
+    }
+
+    fn template_correlation(
+        &self,
+        tables: &[Array2<f64>],
+        tile: &[&str],
+        phase: (usize, usize),
+    ) -> f64 {
+        let mut dot = 0.0;
+        let mut table_energy = 0.0;
+        let mut mask_energy = 0.0;
+
+        for (c, table) in tables.iter().enumerate() {
+            let mask = Array2::from_shape_fn((LAYOUT_PERIOD, LAYOUT_PERIOD), |(y, x)| {
+                if tile_channel(tile, phase, y, x) == Some(c) {
+                    1.0
+                } else {
+                    0.0
+                }
+            });
+            let mean = mask.mean().unwrap_or(0.0);
+
+            for (&t, &m) in table.iter().zip(mask.iter()) {
+                let m = m - mean;
+                dot += t * m;
+                table_energy += t * t;
+                mask_energy += m * m;
+            }
+        }
+
+        if table_energy <= 0.0 || mask_energy <= 0.0 {
+            return 0.0;
+        }
+
+        dot / (table_energy * mask_energy).sqrt()
+    }
+
+    /// Least-squares fits of interpolated green samples, one model per tile position:
+    /// from the 3x3 green neighbourhood alone, with the sampled channel's center and
+    /// distance-2 values added, and the latter split by local edge direction.
+    fn fit_demosaicing_kernels(
+        &self,
+        channels: &[Array2<f64>],
+        pattern: CfaPattern,
+        phase: (usize, usize),
+    ) -> Option<DemosaicingKernelFit> {
+        let tile = pattern.tile()?;
+        let period = tile.len();
+        let green = &channels[1];
+        let (height, width) = green.dim();
+
+        let mut intra = (0..period * period)
+            .map(|_| NormalEquations::new(8))
+            .collect::<Vec<_>>();
+        let mut cross = Vec::with_capacity(period * period);
+        let mut directional = Vec::with_capacity(period * period);
+        for y in 0..period {
+            for x in 0..period {
+                let k = if tile_channel(tile, phase, y, x).is_some() {
+                    13
+                } else {
+                    8
+                };
+                cross.push(NormalEquations::new(k));
+                directional.push([NormalEquations::new(k), NormalEquations::new(k)]);
+            }
+        }
+
+        let mut reference = 0.0;
+        let mut reference_count = 0usize;
+        let clipped = |v: f64| !(1.0..=254.0).contains(&v);
+
+        for y in 2..height - 2 {
+            for x in 2..width - 2 {
+                let sampled = tile_channel(tile, phase, y, x);
+                let target = green[[y, x]];
+
+                if sampled == Some(1) {
+                    let mean = (green[[y - 1, x]]
+                        + green[[y + 1, x]]
+                        + green[[y, x - 1]]
+                        + green[[y, x + 1]])
+                        / 4.0;
+                    reference += (target - mean).powi(2);
+                    reference_count += 1;
+                    continue;
+                }
+
+                let mut values = Vec::with_capacity(13);
+                for dy in 0..3 {
+                    for dx in 0..3 {
+                        if (dy, dx) != (1, 1) {
+                            values.push(green[[y + dy - 1, x + dx - 1]]);
+                        }
+                    }
+                }
+
+                let mut horizontal = (green[[y, x - 1]] - green[[y, x + 1]]).abs();
+                let mut vertical = (green[[y - 1, x]] - green[[y + 1, x]]).abs();
+
+                if let Some(c) = sampled {
+                    let other = &channels[c];
+                    values.extend([
+                        other[[y, x]],
+                        other[[y - 2, x]],
+                        other[[y + 2, x]],
+                        other[[y, x - 2]],
+                        other[[y, x + 2]],
+                    ]);
+                    horizontal +=
+                        (2.0 * other[[y, x]] - other[[y, x - 2]] - other[[y, x + 2]]).abs();
+                    vertical += (2.0 * other[[y, x]] - other[[y - 2, x]] - other[[y + 2, x]]).abs();
+                }
+
+                if clipped(target) || values.iter().any(|&v| clipped(v)) {
+                    continue;
+                }
+
+                let cell = ((y + phase.0) % period) * period + (x + phase.1) % period;
+                intra[cell].add(&values[..8], target);
+                cross[cell].add(&values, target);
+                directional[cell][usize::from(horizontal > vertical)].add(&values, target);
+            }
+        }
+
+        if reference_count == 0 || reference <= 0.0 {
+            return None;
+        }
+        let reference = (reference / reference_count as f64).sqrt();
+
+        // Under-sampled cells are left out; the fit is only trusted when the solvable cells
+        // cover at least half of the samples.
+        let relative_rms = |fits: &mut dyn Iterator<Item = &NormalEquations>| {
+            let (sse, n, total) =
+                fits.fold((0.0, 0usize, 0usize), |(sse, n, total), fit| {
+                    match fit.residual() {
+                        Some(residual) => (sse + residual, n + fit.n, total + fit.n),
+                        None => (sse, n, total + fit.n),
+                    }
+                });
+            (n > 0 && 2 * n >= total).then(|| ((sse / n as f64).sqrt() / reference, n))
+        };
+
+        let (intra_channel, samples) = relative_rms(&mut intra.iter())?;
+        let (cross_channel, _) = relative_rms(&mut cross.iter())?;
+        let (directional, _) = relative_rms(&mut directional.iter().flatten())?;
+
+        Some(DemosaicingKernelFit {
+            intra_channel,
+            cross_channel,
+            directional,
+            samples,
+        })
+    }
+
+    fn classify_demosaicing(&self, fit: &DemosaicingKernelFit) -> DemosaicingFamily {
+        let threshold = self.config.kernel_fit_threshold;
+
+        if fit.intra_channel < threshold {
+            DemosaicingFamily::Bilinear
+        } else if fit.cross_channel < threshold {
+            DemosaicingFamily::GradientCorrected
+        } else if fit.directional < 2.0 * threshold && fit.directional < 0.6 * fit.cross_channel {
+            DemosaicingFamily::AdaptiveHomogeneity
+        } else {
+            DemosaicingFamily::Unknown
+        }
+    }
+
+    fn em_interpolation(&self, channel: &Array2<f64>) -> (CfaInterpolationModel, Array2<f64>) {
+        let (coefficients, sigma, probability) = em_linear_predictor(
+            channel,
+            self.config.em_neighborhood,
+            self.config.em_iterations,
+        );
+
+        (
+            CfaInterpolationModel {
+                coefficients,
+                sigma,
+            },
+            probability,
+        )
+    }
+
+    /// Largest magnitude at the three Nyquist frequencies of a block's probability map,
+    /// divided by the median magnitude of the rest of the spectrum.
+    fn periodic_peak(&self, probability: &Array2<f64>, y0: usize, x0: usize, size: usize) -> f64 {
+        let block = probability.slice(ndarray::s![y0..y0 + size, x0..x0 + size]);
+        let mean = block.mean().unwrap_or(0.0);
+
+        let spectrum = fft_2d(&block.mapv(|v| Complex::new(v - mean, 0.0)));
+        let magnitude = spectrum.mapv(|c| c.norm());
+
+        let half = size / 2;
+        let peaks = [(half, half), (0, half), (half, 0)];
+
+        let mut rest = magnitude
+            .indexed_iter()
+            .filter(|&(idx, _)| idx != (0, 0) && !peaks.contains(&idx))
+            .map(|(_, &m)| m)
+            .collect::<Vec<_>>();
+        rest.sort_by(|a, b| a.total_cmp(b));
+
+        let median = rest.get(rest.len() / 2).copied().unwrap_or(0.0);
+        if median <= 0.0 {
+            return 0.0;
+        }
+
+        peaks
+            .iter()
+            .map(|&idx| magnitude[idx] / median)
+            .fold(0.0, f64::max)
+    }
 }
 
 impl Default for CfaAnalyzer {
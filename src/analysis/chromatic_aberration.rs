@@ -1,6 +1,14 @@
+use std::{fs, path::Path};
+
 use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
 
-use crate::{SRegion, error::Result};
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+    image_utils::solve_linear_system,
+};
 
 #[derive(Debug, Clone)]
 pub struct ChromaticAbberationConfig {
@@ -9,6 +17,18 @@ pub struct ChromaticAbberationConfig {
     pub min_edge_strength: f64,
     pub max_aberration: f64,
     pub inconsistency_threshold: f64,
+    /// Number of terms of the radial shift polynomial, starting at the linear term.
+    pub polynomial_order: usize,
+    pub fit_iterations: usize,
+    /// Huber threshold, in robust standard deviations, above which blocks are down-weighted.
+    pub huber_threshold: f64,
+    /// Largest distance of the fitted optical center from the image center, as a fraction
+    /// of half the shorter image side.
+    pub max_center_offset: f64,
+    /// Extra RMS error, in pixels, a lens profile may show over the fitted model.
+    pub profile_tolerance: f64,
+    /// Peak shift, in pixels, below which the image is considered free of lateral CA.
+    pub corrected_shift: f64,
 }
 
 impl Default for ChromaticAbberationConfig {
@@ -19,6 +39,12 @@ impl Default for ChromaticAbberationConfig {
             min_edge_strength: 20.0,
             max_aberration: 5.0,
             inconsistency_threshold: 1.5,
+            polynomial_order: 3,
+            fit_iterations: 30,
+            huber_threshold: 1.345,
+            max_center_offset: 0.5,
+            profile_tolerance: 0.3,
+            corrected_shift: 0.4,
         }
     }
 }
@@ -43,19 +69,119 @@ pub struct ChromaticAberrationResult {
     pub inconsistent_regions: Vec<SRegion>,
     pub optical_center: Option<(f64, f64)>,
     pub radial_model: Option<RadialAberrationModel>,
+    /// Deviation of every measurement from the radial model.
+    pub residuals: Vec<AberrationResidual>,
+    /// Residual magnitude on the measurement grid (half-block step), `None` where a block
+    /// had too few edges.
+    pub residual_map: Array2<Option<f64>>,
     pub consistency_score: f64,
     pub manipulation_probability: f64,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Lateral chromatic aberration as a radial shift of red and blue against green.
+///
+/// At distance `r` from the optical center the shift along the radius is
+/// `R * sum(c[i] * (r / R)^(i + 1))` pixels, where `R` is `normalization_radius`.
+#[derive(Debug, Clone)]
 pub struct RadialAberrationModel {
     pub center_x: f64,
     pub center_y: f64,
-    pub k_red: f64,
-    pub k_blue: f64,
+    /// False when the aberration was too weak to locate the center, which then stays at
+    /// the image center.
+    pub center_estimated: bool,
+    pub red_coefficients: Vec<f64>,
+    pub blue_coefficients: Vec<f64>,
+    /// Half of the shorter image side.
+    pub normalization_radius: f64,
+    /// Robust standard deviation of the block residuals, in pixels.
+    pub residual_scale: f64,
     pub fit_quality: f64,
 }
 
+impl RadialAberrationModel {
+    /// Expected red and blue shifts at pixel `(x, y)`.
+    pub fn expected_shift(&self, x: f64, y: f64) -> ((f64, f64), (f64, f64)) {
+        let (dx, dy) = (x - self.center_x, y - self.center_y);
+        (
+            radial_shift(&self.red_coefficients, dx, dy, self.normalization_radius),
+            radial_shift(&self.blue_coefficients, dx, dy, self.normalization_radius),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AberrationResidual {
+    pub x: u32,
+    pub y: u32,
+    pub red: (f64, f64),
+    pub blue: (f64, f64),
+    pub magnitude: f64,
+    /// Confidence times robust weight; near zero for blocks the fit treated as outliers.
+    pub weight: f64,
+}
+
+/// Lateral CA expected from a lens, in the coefficient convention of
+/// [`RadialAberrationModel`] with the optical center at the image center.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LensProfile {
+    pub lens: String,
+    pub red_coefficients: Vec<f64>,
+    pub blue_coefficients: Vec<f64>,
+}
+
+impl LensProfile {
+    /// Reads a profile stored as JSON.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| ForensicsError::UnsupportedFormat(e.to_string()))
+    }
+
+    /// Builds a profile from lensfun `poly3` TCA coefficients `(v, c, b)` per channel,
+    /// which map a green radius `r` to `r * (b * r^2 + c * r + v)`.
+    pub fn from_lensfun_poly3(lens: &str, red: [f64; 3], blue: [f64; 3]) -> Self {
+        let convert = |[v, c, b]: [f64; 3]| vec![v - 1.0, c, b];
+
+        Self {
+            lens: lens.to_string(),
+            red_coefficients: convert(red),
+            blue_coefficients: convert(blue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LensProfileVerdict {
+    Consistent,
+    /// The measured CA does not follow the profile.
+    Mismatch,
+    /// The profile predicts visible CA but the image has almost none.
+    SoftwareCorrected,
+    Inconclusive,
+}
+
+#[derive(Debug, Clone)]
+pub struct LensProfileComparison {
+    pub lens: String,
+    pub verdict: LensProfileVerdict,
+    /// Largest red or blue shift inside the frame predicted by the profile, in pixels.
+    pub expected_peak_shift: f64,
+    pub measured_peak_shift: f64,
+    /// Weighted RMS difference between the measurements and the profile.
+    pub profile_error: f64,
+    /// Weighted RMS residual of the fitted model.
+    pub model_error: f64,
+    pub findings: Vec<String>,
+}
+
+/// Polynomial fit for a fixed optical center.
+struct CenterFit {
+    red: Vec<f64>,
+    blue: Vec<f64>,
+    /// `[red x, red y, blue x, blue y]` per measurement.
+    residuals: Vec<[f64; 4]>,
+    cost: f64,
+}
+
 pub struct ChromaticAberrationAnalyzer {
     config: ChromaticAbberationConfig,
 }
@@ -87,8 +213,16 @@ impl ChromaticAberrationAnalyzer {
 
         let radial_model = self.fit_radial_model(&measurements, width, height);
 
-        let expected_aberrations =
-            radial_model.map(|model| self.calculate_expected_aberrations(&measurements, &model));
+        let expected_aberrations = radial_model
+            .as_ref()
+            .map(|model| self.calculate_expected_aberrations(&measurements, model));
+
+        let residuals = radial_model
+            .as_ref()
+            .map(|model| self.calculate_residuals(&measurements, model))
+            .unwrap_or_default();
+
+        let residual_map = self.create_residual_map(width, height, &residuals);
 
         let inconsistency_map = self.create_inconsistency_map(
             width,
@@ -111,7 +245,7 @@ impl ChromaticAberrationAnalyzer {
             height,
         );
 
-        let optical_center = radial_model.map(|m| (m.center_x, m.center_y));
+        let optical_center = radial_model.as_ref().map(|m| (m.center_x, m.center_y));
 
         Ok(ChromaticAberrationResult {
             measurements,
@@ -121,6 +255,8 @@ impl ChromaticAberrationAnalyzer {
             inconsistent_regions,
             optical_center,
             radial_model,
+            residuals,
+            residual_map,
             consistency_score,
             manipulation_probability,
         })
@@ -295,7 +431,31 @@ impl ChromaticAberrationAnalyzer {
             }
         }
 
-        (best_shift_x, best_shift_y, best_correlation)
+        let step = 1.0 / 3.0;
+        let correlation_at =
+            |x: f64, y: f64| self.calculate_edge_correlation(channel, reference, edge_points, x, y);
+        let vertex = |minus: f64, plus: f64| {
+            let curvature = minus - 2.0 * best_correlation + plus;
+            if curvature < 0.0 {
+                (0.5 * (minus - plus) / curvature).clamp(-0.5, 0.5) * step
+            } else {
+                0.0
+            }
+        };
+        let refine_x = vertex(
+            correlation_at(best_shift_x - step, best_shift_y),
+            correlation_at(best_shift_x + step, best_shift_y),
+        );
+        let refine_y = vertex(
+            correlation_at(best_shift_x, best_shift_y - step),
+            correlation_at(best_shift_x, best_shift_y + step),
+        );
+
+        (
+            best_shift_x + refine_x,
+            best_shift_y + refine_y,
+            best_correlation,
+        )
     }
 
     fn calculate_edge_correlation(
@@ -400,101 +560,364 @@ impl ChromaticAberrationAnalyzer {
         map
     }
 
+    pub fn compare_lens_profile(
+        &self,
+        result: &ChromaticAberrationResult,
+        profile: &LensProfile,
+    ) -> LensProfileComparison {
+        let (width, height) = result.aberration_map.dimensions();
+        let radius = width.min(height) as f64 / 2.0;
+        let max_rho = (width as f64).hypot(height as f64) / 2.0 / radius;
+        let (center_x, center_y) = (width as f64 / 2.0, height as f64 / 2.0);
+
+        let expected_peak_shift = peak_shift(&profile.red_coefficients, radius, max_rho)
+            .max(peak_shift(&profile.blue_coefficients, radius, max_rho));
+
+        let mut comparison = LensProfileComparison {
+            lens: profile.lens.clone(),
+            verdict: LensProfileVerdict::Inconclusive,
+            expected_peak_shift,
+            measured_peak_shift: 0.0,
+            profile_error: 0.0,
+            model_error: 0.0,
+            findings: Vec::new(),
+        };
+
+        let Some(model) = result.radial_model.as_ref() else {
+            comparison
+                .findings
+                .push("Too few edge measurements to fit a lateral CA model".to_string());
+            return comparison;
+        };
+
+        comparison.measured_peak_shift = peak_shift(&model.red_coefficients, radius, max_rho)
+            .max(peak_shift(&model.blue_coefficients, radius, max_rho));
+
+        let mut profile_sum = 0.0;
+        let mut model_sum = 0.0;
+        let mut total_weight = 0.0;
+
+        for (m, residual) in result.measurements.iter().zip(&result.residuals) {
+            let (dx, dy) = (m.x as f64 - center_x, m.y as f64 - center_y);
+            let red = radial_shift(&profile.red_coefficients, dx, dy, radius);
+            let blue = radial_shift(&profile.blue_coefficients, dx, dy, radius);
+
+            profile_sum += residual.weight
+                * ((m.rg_shift_x - red.0).powi(2)
+                    + (m.rg_shift_y - red.1).powi(2)
+                    + (m.bg_shift_x - blue.0).powi(2)
+                    + (m.bg_shift_y - blue.1).powi(2));
+            model_sum += residual.weight
+                * (residual.red.0.powi(2)
+                    + residual.red.1.powi(2)
+                    + residual.blue.0.powi(2)
+                    + residual.blue.1.powi(2));
+            total_weight += residual.weight;
+        }
+
+        if total_weight <= 0.0 {
+            comparison
+                .findings
+                .push("No reliable edge measurements to compare".to_string());
+            return comparison;
+        }
+
+        comparison.profile_error = (profile_sum / (2.0 * total_weight)).sqrt();
+        comparison.model_error = (model_sum / (2.0 * total_weight)).sqrt();
+
+        let corrected = self.config.corrected_shift;
+        let measurable = model.center_estimated && comparison.measured_peak_shift >= corrected;
+        comparison.verdict = if expected_peak_shift >= corrected && !measurable {
+            comparison.findings.push(format!(
+                "{} should shift red/blue by up to {:.2}px but the image shows {:.2}px; \
+                 CA was likely corrected in software",
+                profile.lens, expected_peak_shift, comparison.measured_peak_shift
+            ));
+            LensProfileVerdict::SoftwareCorrected
+        } else if comparison.profile_error > comparison.model_error + self.config.profile_tolerance
+        {
+            comparison.findings.push(format!(
+                "CA signature deviates from the {} profile by {:.2}px RMS (model residual {:.2}px)",
+                profile.lens, comparison.profile_error, comparison.model_error
+            ));
+            LensProfileVerdict::Mismatch
+        } else {
+            LensProfileVerdict::Consistent
+        };
+
+        if model.center_estimated {
+            let offset = (model.center_x - center_x).hypot(model.center_y - center_y);
+            if offset > 0.25 * radius {
+                comparison.findings.push(format!(
+                    "Optical center is {:.0}px from the image center, suggesting a crop or shift",
+                    offset
+                ));
+            }
+        }
+
+        comparison
+    }
+
     fn fit_radial_model(
         &self,
         measurements: &[AberrationMeasurement],
         width: u32,
         height: u32,
     ) -> Option<RadialAberrationModel> {
-        if measurements.len() < 10 {
+        let order = self.config.polynomial_order.max(1);
+        if measurements.len() < 10.max(2 * order) {
             return None;
         }
 
-        let center_x = width as f64 / 2.0;
-        let center_y = height as f64 / 2.0;
+        let radius = width.min(height) as f64 / 2.0;
+        let origin = (width as f64 / 2.0, height as f64 / 2.0);
+        let max_offset = self.config.max_center_offset * radius;
+        let max_rho = (width as f64).hypot(height as f64) / 2.0 / radius;
 
-        let mut sum_r_sq = 0.0;
-        let mut sum_r_shift_red = 0.0;
-        let mut sum_r_shift_blue = 0.0;
-        let mut count = 0.0;
+        let mut weights = measurements
+            .iter()
+            .map(|m| m.confidence)
+            .collect::<Vec<_>>();
+        let mut center = origin;
+        let CenterFit {
+            mut red,
+            mut blue,
+            mut residuals,
+            ..
+        } = self.fit_at_center(measurements, &weights, center, radius)?;
+
+        let (_, scale) = self.robust_weights(measurements, &residuals);
+        let amplitude = peak_shift(&red, radius, max_rho).max(peak_shift(&blue, radius, max_rho));
+        let center_estimated = amplitude > 2.0 * scale;
+
+        let mut lambda = 1e-3;
+
+        for _ in 0..self.config.fit_iterations {
+            weights = self.robust_weights(measurements, &residuals).0;
+            let fit = self.fit_at_center(measurements, &weights, center, radius)?;
+            let fit_cost = fit.cost;
+            (red, blue, residuals) = (fit.red, fit.blue, fit.residuals);
+
+            if !center_estimated {
+                continue;
+            }
 
-        for m in measurements {
-            let dx = m.x as f64 - center_x;
-            let dy = m.y as f64 - center_y;
-            let r = (dx * dx + dy * dy).sqrt();
+            let h = 0.5;
+            let mut jacobian = [Vec::new(), Vec::new()];
+            for (axis, column) in jacobian.iter_mut().enumerate() {
+                let offset = |sign: f64| {
+                    let mut c = center;
+                    if axis == 0 {
+                        c.0 += sign * h;
+                    } else {
+                        c.1 += sign * h;
+                    }
+                    c
+                };
+                let plus = self.fit_at_center(measurements, &weights, offset(1.0), radius)?;
+                let minus = self.fit_at_center(measurements, &weights, offset(-1.0), radius)?;
+                *column = plus
+                    .residuals
+                    .iter()
+                    .zip(&minus.residuals)
+                    .map(|(p, m)| std::array::from_fn::<f64, 4, _>(|k| (p[k] - m[k]) / (2.0 * h)))
+                    .collect::<Vec<_>>();
+            }
 
-            if r < 10.0 {
-                continue;
+            let mut jtj = [[0.0; 2]; 2];
+            let mut gradient = [0.0; 2];
+            for (i, w) in weights.iter().enumerate() {
+                for a in 0..2 {
+                    for k in 0..4 {
+                        gradient[a] += w * jacobian[a][i][k] * residuals[i][k];
+                        for b in 0..2 {
+                            jtj[a][b] += w * jacobian[a][i][k] * jacobian[b][i][k];
+                        }
+                    }
+                }
             }
 
-            let radial_dir_x = dx / r;
-            let radial_dir_y = dy / r;
+            let mut step = None;
+            while lambda < 1e8 {
+                let a = (0..2)
+                    .map(|r| {
+                        (0..2)
+                            .map(|c| {
+                                if r == c {
+                                    jtj[r][c] * (1.0 + lambda) + 1e-12
+                                } else {
+                                    jtj[r][c]
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let Some(delta) = solve_linear_system(a, vec![-gradient[0], -gradient[1]]) else {
+                    break;
+                };
+
+                let mut candidate = (center.0 + delta[0], center.1 + delta[1]);
+                let (ox, oy) = (candidate.0 - origin.0, candidate.1 - origin.1);
+                let offset = ox.hypot(oy);
+                if offset > max_offset {
+                    candidate = (
+                        origin.0 + ox * max_offset / offset,
+                        origin.1 + oy * max_offset / offset,
+                    );
+                }
 
-            let rg_radial = m.rg_shift_x * radial_dir_x + m.rg_shift_y * radial_dir_y;
-            let bg_radial = m.bg_shift_x * radial_dir_x + m.bg_shift_y * radial_dir_y;
+                if let Some(fit) = self.fit_at_center(measurements, &weights, candidate, radius)
+                    && fit.cost < fit_cost
+                {
+                    step = Some((candidate.0 - center.0).hypot(candidate.1 - center.1));
+                    center = candidate;
+                    (red, blue, residuals) = (fit.red, fit.blue, fit.residuals);
+                    lambda *= 0.3;
+                    break;
+                }
+                lambda *= 10.0;
+            }
 
-            sum_r_sq += r * r * m.confidence;
-            sum_r_shift_red += r * rg_radial * m.confidence;
-            sum_r_shift_blue += r * bg_radial * m.confidence;
-            count += m.confidence;
+            if step.is_none_or(|s| s < 1e-3) {
+                break;
+            }
         }
 
-        if sum_r_sq < 1e-10 {
-            return None;
+        let (weights, residual_scale) = self.robust_weights(measurements, &residuals);
+        let fit_quality = self.calculate_model_fit(measurements, &weights, &residuals);
+
+        Some(RadialAberrationModel {
+            center_x: center.0,
+            center_y: center.1,
+            center_estimated,
+            red_coefficients: red,
+            blue_coefficients: blue,
+            normalization_radius: radius,
+            residual_scale,
+            fit_quality,
+        })
+    }
+
+    /// Weighted least-squares polynomial coefficients for a fixed optical center.
+    fn fit_at_center(
+        &self,
+        measurements: &[AberrationMeasurement],
+        weights: &[f64],
+        center: (f64, f64),
+        radius: f64,
+    ) -> Option<CenterFit> {
+        let order = self.config.polynomial_order.max(1);
+        let mut normal = vec![vec![0.0; order]; order];
+        let mut red_rhs = vec![0.0; order];
+        let mut blue_rhs = vec![0.0; order];
+
+        for (m, &w) in measurements.iter().zip(weights) {
+            let (dx, dy) = (m.x as f64 - center.0, m.y as f64 - center.1);
+            let rho = dx.hypot(dy) / radius;
+            let basis = (0..order).map(|i| rho.powi(i as i32)).collect::<Vec<_>>();
+            let r_sq = dx * dx + dy * dy;
+
+            for i in 0..order {
+                for j in 0..order {
+                    normal[i][j] += w * basis[i] * basis[j] * r_sq;
+                }
+                red_rhs[i] += w * basis[i] * (m.rg_shift_x * dx + m.rg_shift_y * dy);
+                blue_rhs[i] += w * basis[i] * (m.bg_shift_x * dx + m.bg_shift_y * dy);
+            }
         }
 
-        let k_red = sum_r_shift_red / sum_r_sq;
-        let k_blue = sum_r_shift_blue / sum_r_sq;
+        let ridge = 1e-9 * (0..order).map(|i| normal[i][i]).sum::<f64>().max(1e-12);
+        for (i, row) in normal.iter_mut().enumerate() {
+            row[i] += ridge;
+        }
 
-        let fit_quality = self.calculate_model_fit(measurements, center_x, center_y, k_red, k_blue);
+        let red = solve_linear_system(normal.clone(), red_rhs)?;
+        let blue = solve_linear_system(normal, blue_rhs)?;
 
-        Some(RadialAberrationModel {
-            center_x,
-            center_y,
-            k_red,
-            k_blue,
-            fit_quality,
+        let residuals = measurements
+            .iter()
+            .map(|m| {
+                let (dx, dy) = (m.x as f64 - center.0, m.y as f64 - center.1);
+                let (rx, ry) = radial_shift(&red, dx, dy, radius);
+                let (bx, by) = radial_shift(&blue, dx, dy, radius);
+                [
+                    m.rg_shift_x - rx,
+                    m.rg_shift_y - ry,
+                    m.bg_shift_x - bx,
+                    m.bg_shift_y - by,
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let cost = residuals
+            .iter()
+            .zip(weights)
+            .map(|(r, w)| w * r.iter().map(|v| v * v).sum::<f64>())
+            .sum();
+
+        Some(CenterFit {
+            red,
+            blue,
+            residuals,
+            cost,
         })
     }
 
+    /// Huber weights scaled by measurement confidence, with the robust residual scale.
+    fn robust_weights(
+        &self,
+        measurements: &[AberrationMeasurement],
+        residuals: &[[f64; 4]],
+    ) -> (Vec<f64>, f64) {
+        let norms = residuals
+            .iter()
+            .map(|r| (r.iter().map(|v| v * v).sum::<f64>() / 2.0).sqrt())
+            .collect::<Vec<_>>();
+
+        let mut sorted = norms.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let scale = (1.4826 * sorted.get(sorted.len() / 2).copied().unwrap_or(0.0)).max(0.05);
+        let threshold = self.config.huber_threshold * scale;
+
+        let weights = measurements
+            .iter()
+            .zip(&norms)
+            .map(|(m, &n)| m.confidence * if n > threshold { threshold / n } else { 1.0 })
+            .collect();
+
+        (weights, scale)
+    }
+
     fn calculate_model_fit(
         &self,
         measurements: &[AberrationMeasurement],
-        center_x: f64,
-        center_y: f64,
-        k_red: f64,
-        k_blue: f64,
+        weights: &[f64],
+        residuals: &[[f64; 4]],
     ) -> f64 {
-        if measurements.is_empty() {
+        let total_weight = weights.iter().sum::<f64>();
+        if total_weight <= 0.0 {
             return 0.0;
         }
 
-        let mean_rg_x =
-            measurements.iter().map(|m| m.rg_shift_x).sum::<f64>() / measurements.len() as f64;
-        let mean_rg_y =
-            measurements.iter().map(|m| m.rg_shift_y).sum::<f64>() / measurements.len() as f64;
+        let mut mean = [0.0; 4];
+        for (m, w) in measurements.iter().zip(weights) {
+            for (k, v) in [m.rg_shift_x, m.rg_shift_y, m.bg_shift_x, m.bg_shift_y]
+                .into_iter()
+                .enumerate()
+            {
+                mean[k] += w * v / total_weight;
+            }
+        }
 
         let mut ss_res = 0.0;
         let mut ss_tot = 0.0;
 
-        for m in measurements {
-            let dx = m.x as f64 - center_x;
-            let dy = m.y as f64 - center_y;
-            let r = (dx * dx + dy * dy).sqrt();
-
-            if r < 10.0 {
-                continue;
+        for ((m, w), r) in measurements.iter().zip(weights).zip(residuals) {
+            let values = [m.rg_shift_x, m.rg_shift_y, m.bg_shift_x, m.bg_shift_y];
+            for k in 0..4 {
+                ss_res += w * r[k] * r[k];
+                ss_tot += w * (values[k] - mean[k]).powi(2);
             }
-
-            let radial_dir_x = dx / r;
-            let radial_dir_y = dy / r;
-
-            let expected_rg_x = k_red * r * radial_dir_x;
-            let expected_rg_y = k_red * r * radial_dir_y;
-
-            ss_res +=
-                (m.rg_shift_x - expected_rg_x).powi(2) + (m.rg_shift_y - expected_rg_y).powi(2);
-            ss_tot += (m.rg_shift_x - mean_rg_x).powi(2) + (m.rg_shift_y - mean_rg_y).powi(2);
         }
 
         if ss_tot < 1e-10 {
@@ -512,21 +935,68 @@ impl ChromaticAberrationAnalyzer {
         measurements
             .iter()
             .map(|m| {
-                let dx = m.x as f64 - model.center_x;
-                let dy = m.y as f64 - model.center_y;
-                let r = (dx * dx + dy * dy).sqrt();
-
-                let radial_dir_x = if r > 0.0 { dx / r } else { 0.0 };
-                let radial_dir_y = if r > 0.0 { dy / r } else { 0.0 };
+                let ((rx, ry), (bx, by)) = model.expected_shift(m.x as f64, m.y as f64);
+                (rx, ry, bx, by)
+            })
+            .collect::<Vec<_>>()
+    }
 
-                let expected_rg_x = model.k_red * r * radial_dir_x;
-                let expected_rg_y = model.k_red * r * radial_dir_y;
-                let expected_bg_x = model.k_blue * r * radial_dir_x;
-                let expected_bg_y = model.k_blue * r * radial_dir_y;
+    fn calculate_residuals(
+        &self,
+        measurements: &[AberrationMeasurement],
+        model: &RadialAberrationModel,
+    ) -> Vec<AberrationResidual> {
+        let threshold = self.config.huber_threshold * model.residual_scale;
 
-                (expected_rg_x, expected_rg_y, expected_bg_x, expected_bg_y)
+        measurements
+            .iter()
+            .map(|m| {
+                let ((rx, ry), (bx, by)) = model.expected_shift(m.x as f64, m.y as f64);
+                let red = (m.rg_shift_x - rx, m.rg_shift_y - ry);
+                let blue = (m.bg_shift_x - bx, m.bg_shift_y - by);
+                let magnitude = (red.0.hypot(red.1) + blue.0.hypot(blue.1)) / 2.0;
+                let norm = ((red.0.powi(2) + red.1.powi(2) + blue.0.powi(2) + blue.1.powi(2))
+                    / 2.0)
+                    .sqrt();
+
+                AberrationResidual {
+                    x: m.x,
+                    y: m.y,
+                    red,
+                    blue,
+                    magnitude,
+                    weight: m.confidence
+                        * if norm > threshold {
+                            threshold / norm
+                        } else {
+                            1.0
+                        },
+                }
             })
-            .collect::<Vec<_>>()
+            .collect()
+    }
+
+    fn create_residual_map(
+        &self,
+        width: u32,
+        height: u32,
+        residuals: &[AberrationResidual],
+    ) -> Array2<Option<f64>> {
+        let block_size = self.config.block_size;
+        let step = block_size / 2;
+        let rows = (height - block_size).div_ceil(step) as usize;
+        let cols = (width - block_size).div_ceil(step) as usize;
+        let mut map = Array2::from_elem((rows, cols), None);
+
+        for residual in residuals {
+            let row = ((residual.y - block_size / 2) / step) as usize;
+            let col = ((residual.x - block_size / 2) / step) as usize;
+            if let Some(cell) = map.get_mut([row, col]) {
+                *cell = Some(residual.magnitude);
+            }
+        }
+
+        map
     }
 
     fn create_inconsistency_map(
@@ -769,6 +1239,33 @@ impl ChromaticAberrationAnalyzer {
     }
 }
 
+/// Shift vector at offset `(dx, dy)` from the center for a radial polynomial.
+fn radial_shift(coefficients: &[f64], dx: f64, dy: f64, radius: f64) -> (f64, f64) {
+    let rho = dx.hypot(dy) / radius;
+    let factor = coefficients
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * rho.powi(i as i32))
+        .sum::<f64>();
+
+    (factor * dx, factor * dy)
+}
+
+/// Largest absolute radial shift, in pixels, for normalized radii up to `max_rho`.
+fn peak_shift(coefficients: &[f64], radius: f64, max_rho: f64) -> f64 {
+    (0..=64)
+        .map(|i| {
+            let rho = max_rho * i as f64 / 64.0;
+            let shift = coefficients
+                .iter()
+                .enumerate()
+                .map(|(k, c)| c * rho.powi(k as i32 + 1))
+                .sum::<f64>();
+            (shift * radius).abs()
+        })
+        .fold(0.0, f64::max)
+}
+
 impl Default for ChromaticAberrationAnalyzer {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,590 @@
+use std::f64::consts::PI;
+
+use image::{DynamicImage, GrayImage, Rgb, RgbImage, imageops};
+use ndarray::Array2;
+use num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+    image_utils::{calculate_histogram, median},
+};
+
+#[derive(Debug, Clone)]
+pub struct ContrastConfig {
+    pub block_size: u32,
+    /// Bins at either end of the histogram ignored as saturated.
+    pub saturation_margin: usize,
+    /// Lowest DFT frequency counted as high-frequency. Single-level peaks and gaps reach up to
+    /// 128, while natural modes a couple of levels wide have died out by 64.
+    pub frequency_cutoff: usize,
+    /// High-frequency energy, relative to the sampling noise floor, at which the evidence is
+    /// balanced.
+    pub energy_threshold: f64,
+    /// Logistic scale on the natural log of the energy ratio.
+    pub energy_scale: f64,
+    /// Blocks with a smaller channel standard deviation have too narrow a histogram to judge.
+    pub min_block_std: f64,
+    /// Robust z-score, in log energy, beyond which a block departs from the image.
+    pub block_deviation: f64,
+    pub min_region_blocks: usize,
+    pub max_region_fraction: f64,
+    /// Smallest correlation between observed and predicted peak/gap patterns for a mapping
+    /// estimate to be reported.
+    pub min_mapping_fit: f64,
+}
+
+impl Default for ContrastConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 64,
+            saturation_margin: 8,
+            frequency_cutoff: 96,
+            energy_threshold: 4.0,
+            energy_scale: 0.5,
+            min_block_std: 8.0,
+            block_deviation: 3.0,
+            min_region_blocks: 2,
+            max_region_fraction: 0.4,
+            min_mapping_fit: 0.5,
+        }
+    }
+}
+
+/// Pixel value mapping that explains a histogram's peaks and gaps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContrastMapping {
+    /// `y = 255 (x / 255)^gamma`
+    Gamma(f64),
+    /// `y = gain x + offset`
+    Linear { gain: f64, offset: f64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MappingEstimate {
+    pub mapping: ContrastMapping,
+    /// Correlation between the observed and predicted peak/gap pattern.
+    pub fit: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelEnhancement {
+    /// High-frequency energy of the histogram spectrum over the level expected from sampling
+    /// noise alone; close to one for an unaltered histogram.
+    pub energy_ratio: f64,
+    pub probability: f64,
+    pub mapping: Option<MappingEstimate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnhancedRegion {
+    pub region: SRegion,
+    /// Mean log energy ratio of the region's blocks, and the median over all blocks.
+    pub log_energy: f64,
+    pub image_log_energy: f64,
+    /// Largest energy ratio of the region's pooled channel histograms.
+    pub energy_ratio: f64,
+    /// Mapping estimated from the region's pooled channel histograms.
+    pub mapping: Option<MappingEstimate>,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContrastResult {
+    /// Red, green and blue.
+    pub channels: [ChannelEnhancement; 3],
+    pub block_size: u32,
+    /// Log energy ratio per block averaged over the channels, `None` for blocks too flat to
+    /// judge.
+    pub block_energy: Array2<Option<f64>>,
+    pub median_log_energy: f64,
+    pub enhanced_regions: Vec<EnhancedRegion>,
+    pub global_probability: f64,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+pub struct ContrastAnalyzer {
+    config: ContrastConfig,
+}
+
+impl ContrastAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(ContrastConfig::default())
+    }
+
+    pub fn with_config(config: ContrastConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn analyze(&self, image: &DynamicImage) -> Result<ContrastResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let block_size = self.config.block_size.max(16);
+        if width < block_size || height < block_size {
+            return Err(ForensicsError::ImageTooSmall(block_size));
+        }
+
+        let planes: Vec<GrayImage> = (0..3)
+            .map(|c| {
+                GrayImage::from_fn(width, height, |x, y| image::Luma([rgb.get_pixel(x, y)[c]]))
+            })
+            .collect();
+
+        let channels = [0, 1, 2].map(|c| {
+            let histogram = calculate_histogram(&planes[c]);
+            let energy_ratio = self.energy_ratio(&histogram).unwrap_or(1.0);
+            let probability = self.probability(energy_ratio);
+            let mapping = if probability > 0.5 {
+                self.estimate_mapping(&histogram)
+            } else {
+                None
+            };
+            ChannelEnhancement {
+                energy_ratio,
+                probability,
+                mapping,
+            }
+        });
+        let global_probability = channels.iter().map(|c| c.probability).sum::<f64>() / 3.0;
+
+        let block_energy = self.block_energy(&planes, block_size);
+        let mut values: Vec<f64> = block_energy.iter().flatten().copied().collect();
+        if values.is_empty() {
+            return Err(ForensicsError::AnalysisFailed(
+                "No block has enough contrast to judge its histogram".into(),
+            ));
+        }
+        let median_log_energy = median(&mut values);
+        let mut deviations: Vec<f64> = values
+            .iter()
+            .map(|v| (v - median_log_energy).abs())
+            .collect();
+        // Floor the spread so an image of uniform blocks does not turn noise into regions.
+        let sigma = (1.4826 * median(&mut deviations)).max(self.config.energy_scale);
+
+        let global_mappings: Vec<MappingEstimate> =
+            channels.iter().filter_map(|c| c.mapping).collect();
+        let enhanced_regions = self.find_regions(
+            &block_energy,
+            median_log_energy,
+            sigma,
+            block_size,
+            &planes,
+            &global_mappings,
+        );
+
+        let manipulation_probability = enhanced_regions
+            .iter()
+            .map(|r| r.confidence)
+            .fold(global_probability, f64::max);
+
+        let visualization =
+            self.create_visualization(&rgb, &block_energy, median_log_energy, block_size);
+
+        Ok(ContrastResult {
+            channels,
+            block_size,
+            block_energy,
+            median_log_energy,
+            enhanced_regions,
+            global_probability,
+            visualization,
+            manipulation_probability,
+        })
+    }
+
+    /// Mean power of the tapered histogram's spectrum above the cutoff, over the power that
+    /// Poisson sampling of the same histogram would leave there.
+    pub fn energy_ratio(&self, histogram: &[u32; 256]) -> Option<f64> {
+        let weights = self.taper();
+        let mut spectrum: Vec<Complex<f64>> = histogram
+            .iter()
+            .zip(&weights)
+            .map(|(&h, &w)| Complex::new(h as f64 * w, 0.0))
+            .collect();
+        let noise_floor: f64 = histogram
+            .iter()
+            .zip(&weights)
+            .map(|(&h, &w)| h as f64 * w * w)
+            .sum();
+        if noise_floor < 1.0 {
+            return None;
+        }
+
+        FftPlanner::new()
+            .plan_fft_forward(spectrum.len())
+            .process(&mut spectrum);
+
+        let cutoff = self.config.frequency_cutoff.clamp(1, 128);
+        let band = &spectrum[cutoff..=128];
+        let power = band.iter().map(|c| c.norm_sqr()).sum::<f64>() / band.len() as f64;
+
+        Some(power / noise_floor)
+    }
+
+    /// Zero over the saturated ends with a raised-cosine ramp into the interior, so clipping
+    /// spikes and the histogram's own support edges add no high frequencies.
+    fn taper(&self) -> Vec<f64> {
+        let margin = self.config.saturation_margin.min(100);
+        let ramp = 16.0;
+        (0..256)
+            .map(|y| {
+                let distance = (y as f64 - margin as f64).min((255 - margin) as f64 - y as f64);
+                if distance <= 0.0 {
+                    0.0
+                } else if distance >= ramp {
+                    1.0
+                } else {
+                    0.5 - 0.5 * (PI * distance / ramp).cos()
+                }
+            })
+            .collect()
+    }
+
+    fn probability(&self, energy_ratio: f64) -> f64 {
+        let score = (energy_ratio.max(1e-9).ln() - self.config.energy_threshold.ln())
+            / self.config.energy_scale;
+        1.0 / (1.0 + (-score).exp())
+    }
+
+    fn block_energy(&self, planes: &[GrayImage], block_size: u32) -> Array2<Option<f64>> {
+        let (width, height) = planes[0].dimensions();
+        let rows = (height / block_size) as usize;
+        let columns = (width / block_size) as usize;
+
+        Array2::from_shape_fn((rows, columns), |(row, column)| {
+            let (x, y) = (column as u32 * block_size, row as u32 * block_size);
+            let energies: Vec<f64> = planes
+                .iter()
+                .filter_map(|plane| {
+                    let block = imageops::crop_imm(plane, x, y, block_size, block_size).to_image();
+                    let histogram = calculate_histogram(&block);
+                    if histogram_std(&histogram) < self.config.min_block_std {
+                        return None;
+                    }
+                    self.energy_ratio(&histogram).map(|e| e.max(1e-9).ln())
+                })
+                .collect();
+            (!energies.is_empty()).then(|| energies.iter().sum::<f64>() / energies.len() as f64)
+        })
+    }
+
+    /// Searches gamma curves and linear stretches for the one whose pattern of output levels
+    /// hit zero, once or several times best matches the observed peaks and gaps.
+    pub fn estimate_mapping(&self, histogram: &[u32; 256]) -> Option<MappingEstimate> {
+        let counts: Vec<f64> = histogram.iter().map(|&h| h as f64).collect();
+        let smooth = smooth_levels(&counts);
+        let margin = self.config.saturation_margin.min(100);
+        let levels: Vec<usize> = (margin..256 - margin)
+            .filter(|&y| smooth[y] >= 5.0)
+            .collect();
+        if levels.len() < 32 {
+            return None;
+        }
+        let observed: Vec<f64> = levels.iter().map(|&y| counts[y] / smooth[y]).collect();
+
+        let mut best: Option<MappingEstimate> = None;
+        let mut consider = |mapping: ContrastMapping, curve: &dyn Fn(f64) -> f64| {
+            let mut hits = vec![0.0; 256];
+            for x in 0..256 {
+                let y = curve(x as f64).round();
+                if (0.0..=255.0).contains(&y) {
+                    hits[y as usize] += 1.0;
+                }
+            }
+            let expected = smooth_levels(&hits);
+            let predicted: Vec<f64> = levels
+                .iter()
+                .map(|&y| {
+                    if expected[y] > 1e-6 {
+                        hits[y] / expected[y]
+                    } else {
+                        0.0
+                    }
+                })
+                .collect();
+            let fit = correlation(&observed, &predicted);
+            if best.is_none_or(|b| fit > b.fit) {
+                best = Some(MappingEstimate { mapping, fit });
+            }
+        };
+
+        for step in 30..=300 {
+            let gamma = step as f64 / 100.0;
+            if (gamma - 1.0).abs() < 0.02 {
+                continue;
+            }
+            consider(ContrastMapping::Gamma(gamma), &|x| {
+                255.0 * (x / 255.0).powf(gamma)
+            });
+        }
+        for step in 40..=300 {
+            let gain = step as f64 / 100.0;
+            if (gain - 1.0).abs() < 0.02 {
+                continue;
+            }
+            // Offsets a whole gain apart produce the same set of output levels.
+            let phases = (gain / 0.05).ceil() as usize;
+            for phase in 0..phases {
+                let offset = phase as f64 * 0.05;
+                consider(ContrastMapping::Linear { gain, offset }, &|x| {
+                    gain * x + offset
+                });
+            }
+        }
+
+        best.filter(|b| b.fit >= self.config.min_mapping_fit)
+    }
+
+    fn find_regions(
+        &self,
+        block_energy: &Array2<Option<f64>>,
+        median_log_energy: f64,
+        sigma: f64,
+        block_size: u32,
+        planes: &[GrayImage],
+        global_mappings: &[MappingEstimate],
+    ) -> Vec<EnhancedRegion> {
+        let (rows, columns) = block_energy.dim();
+        let judged = block_energy.iter().flatten().count();
+        let deviant = block_energy.map(|e| {
+            e.map(|e| (e - median_log_energy) / sigma)
+                .filter(|z| z.abs() > self.config.block_deviation)
+                .map(f64::signum)
+        });
+
+        let mut visited = Array2::from_elem((rows, columns), false);
+        let mut regions = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let Some(sign) = deviant[[row, column]] else {
+                    continue;
+                };
+                if visited[[row, column]] {
+                    continue;
+                }
+                visited[[row, column]] = true;
+                let mut group = vec![(row, column)];
+                let mut next = 0;
+                while next < group.len() {
+                    let (r, c) = group[next];
+                    next += 1;
+                    for (dr, dc) in [(-1isize, 0isize), (1, 0), (0, -1), (0, 1)] {
+                        let (Some(nr), Some(nc)) =
+                            (r.checked_add_signed(dr), c.checked_add_signed(dc))
+                        else {
+                            continue;
+                        };
+                        if nr < rows
+                            && nc < columns
+                            && !visited[[nr, nc]]
+                            && deviant[[nr, nc]] == Some(sign)
+                        {
+                            visited[[nr, nc]] = true;
+                            group.push((nr, nc));
+                        }
+                    }
+                }
+
+                if group.len() < self.config.min_region_blocks
+                    || group.len() as f64 > self.config.max_region_fraction * judged as f64
+                {
+                    continue;
+                }
+
+                let log_energy = group
+                    .iter()
+                    .filter_map(|&(r, c)| block_energy[[r, c]])
+                    .sum::<f64>()
+                    / group.len() as f64;
+                let z = (log_energy - median_log_energy).abs() / sigma;
+                let confidence = (z / (2.0 * self.config.block_deviation)).min(1.0);
+
+                // Pooled per channel, since luminance would blend the channels' gap patterns.
+                let histograms = planes.iter().map(|plane| {
+                    let mut histogram = [0u32; 256];
+                    for &(r, c) in &group {
+                        let block = imageops::crop_imm(
+                            plane,
+                            c as u32 * block_size,
+                            r as u32 * block_size,
+                            block_size,
+                            block_size,
+                        )
+                        .to_image();
+                        for (total, count) in histogram.iter_mut().zip(calculate_histogram(&block))
+                        {
+                            *total += count;
+                        }
+                    }
+                    histogram
+                });
+                let mut energy_ratio = 0.0;
+                let mut mapping: Option<MappingEstimate> = None;
+                for histogram in histograms {
+                    energy_ratio = self
+                        .energy_ratio(&histogram)
+                        .unwrap_or(0.0)
+                        .max(energy_ratio);
+                    if let Some(estimate) = self.estimate_mapping(&histogram)
+                        && mapping.is_none_or(|m| estimate.fit > m.fit)
+                    {
+                        mapping = Some(estimate);
+                    }
+                }
+
+                // The peaks and gaps of a shared mapping add up coherently when blocks are
+                // pooled, while spikes of flat or compressed content land on different levels.
+                if sign > 0.0 && energy_ratio < self.config.energy_threshold * group.len() as f64 {
+                    continue;
+                }
+                // A globally enhanced image shows the same mapping everywhere, more strongly in
+                // some blocks than others; that is not a region enhanced differently.
+                if median_log_energy > self.config.energy_threshold.ln()
+                    && let Some(estimate) = mapping
+                    && global_mappings
+                        .iter()
+                        .any(|global| same_mapping(global.mapping, estimate.mapping))
+                {
+                    continue;
+                }
+
+                let (r0, r1) = group
+                    .iter()
+                    .fold((usize::MAX, 0), |(lo, hi), &(r, _)| (lo.min(r), hi.max(r)));
+                let (c0, c1) = group
+                    .iter()
+                    .fold((usize::MAX, 0), |(lo, hi), &(_, c)| (lo.min(c), hi.max(c)));
+
+                regions.push(EnhancedRegion {
+                    region: SRegion {
+                        x: c0 as u32 * block_size,
+                        y: r0 as u32 * block_size,
+                        width: (c1 - c0 + 1) as u32 * block_size,
+                        height: (r1 - r0 + 1) as u32 * block_size,
+                    },
+                    log_energy,
+                    image_log_energy: median_log_energy,
+                    energy_ratio,
+                    mapping,
+                    confidence,
+                });
+            }
+        }
+
+        regions
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        block_energy: &Array2<Option<f64>>,
+        median_log_energy: f64,
+        block_size: u32,
+    ) -> RgbImage {
+        let mut vis = original.clone();
+        let (rows, columns) = block_energy.dim();
+        let block = block_size as usize;
+
+        for (x, y, pixel) in vis.enumerate_pixels_mut() {
+            let (row, column) = (y as usize / block, x as usize / block);
+            let gray = (pixel[0] as f64 + pixel[1] as f64 + pixel[2] as f64) / 3.0;
+            let energy = if row < rows && column < columns {
+                block_energy[[row, column]]
+            } else {
+                None
+            };
+            *pixel = match energy {
+                Some(e) => {
+                    // Red for more histogram structure than the image, blue for less.
+                    let t = ((e - median_log_energy) / 4.0).clamp(-1.0, 1.0);
+                    Rgb([
+                        (0.5 * gray + 127.5 * t.max(0.0)) as u8,
+                        (0.5 * gray) as u8,
+                        (0.5 * gray + 127.5 * (-t).max(0.0)) as u8,
+                    ])
+                }
+                None => Rgb([gray as u8, gray as u8, gray as u8]),
+            };
+        }
+
+        vis
+    }
+}
+
+/// Whether two estimates describe the same curve, up to the search resolution.
+fn same_mapping(a: ContrastMapping, b: ContrastMapping) -> bool {
+    match (a, b) {
+        (ContrastMapping::Gamma(a), ContrastMapping::Gamma(b)) => (a - b).abs() <= 0.05,
+        (ContrastMapping::Linear { gain: a, .. }, ContrastMapping::Linear { gain: b, .. }) => {
+            (a - b).abs() <= 0.05
+        }
+        _ => false,
+    }
+}
+
+fn histogram_std(histogram: &[u32; 256]) -> f64 {
+    let total: f64 = histogram.iter().map(|&h| h as f64).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let mean = histogram
+        .iter()
+        .enumerate()
+        .map(|(y, &h)| y as f64 * h as f64)
+        .sum::<f64>()
+        / total;
+    let variance = histogram
+        .iter()
+        .enumerate()
+        .map(|(y, &h)| (y as f64 - mean).powi(2) * h as f64)
+        .sum::<f64>()
+        / total;
+    variance.sqrt()
+}
+
+/// Gaussian smoothing over levels with a sigma of three bins, renormalized at the ends.
+fn smooth_levels(values: &[f64]) -> Vec<f64> {
+    let radius = 9isize;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|d| (-(d * d) as f64 / 18.0).exp())
+        .collect();
+    (0..values.len() as isize)
+        .map(|i| {
+            let (mut sum, mut weight) = (0.0, 0.0);
+            for (k, d) in (-radius..=radius).enumerate() {
+                if let Ok(j) = usize::try_from(i + d)
+                    && let Some(&v) = values.get(j)
+                {
+                    sum += kernel[k] * v;
+                    weight += kernel[k];
+                }
+            }
+            sum / weight
+        })
+        .collect()
+}
+
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+    let (mut covariance, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a < 1e-12 || var_b < 1e-12 {
+        return 0.0;
+    }
+    covariance / (var_a * var_b).sqrt()
+}
+
+impl Default for ContrastAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
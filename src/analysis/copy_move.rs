@@ -8,7 +8,10 @@ use rustfft::FftPlanner;
 use crate::{
     CopyMoveResult, MatchPair, SRegion,
     error::{ForensicsError, Result},
-    image_utils::{block_variance, extract_block, rgb_to_gray},
+    image_utils::{
+        block_variance, coefficient_similarity, draw_rectangle, extract_block, regions_overlap,
+        rgb_to_gray,
+    },
 };
 
 pub struct CopyMoveDetector {
@@ -170,7 +173,7 @@ impl CopyMoveDetector {
                         continue;
                     }
 
-                    let similarity = self.calculate_similarity(&f1.dct_coeffs, &f2.dct_coeffs);
+                    let similarity = coefficient_similarity(&f1.dct_coeffs, &f2.dct_coeffs);
 
                     if similarity >= self.similarity_threshold {
                         matches.push(MatchPair {
@@ -196,34 +199,6 @@ impl CopyMoveDetector {
         self.filter_matches(matches)
     }
 
-    fn calculate_similarity(&self, coeffs1: &[f64], coeffs2: &[f64]) -> f64 {
-        if coeffs1.len() != coeffs2.len() || coeffs1.is_empty() {
-            return 0.0;
-        }
-
-        let mean1 = coeffs1.iter().sum::<f64>() / coeffs1.len() as f64;
-        let mean2 = coeffs2.iter().sum::<f64>() / coeffs2.len() as f64;
-
-        let mut numerator = 0.0;
-        let mut denom1 = 0.0;
-        let mut denom2 = 0.0;
-
-        for (&c1, &c2) in coeffs1.iter().zip(coeffs2.iter()) {
-            let d1 = c1 - mean1;
-            let d2 = c2 - mean2;
-            numerator += d1 * d2;
-            denom1 += d1 * d1;
-            denom2 += d2 * d2;
-        }
-
-        let denom = (denom1 * denom2).sqrt();
-        if denom < 1e-10 {
-            0.0
-        } else {
-            (numerator / denom).max(0.0)
-        }
-    }
-
     fn filter_matches(&self, mut matches: Vec<MatchPair>) -> Result<Vec<MatchPair>> {
         matches.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap());
 
@@ -231,10 +206,10 @@ impl CopyMoveDetector {
 
         for m in matches {
             let overlaps = filtered.iter().any(|existing: &MatchPair| {
-                self.regions_overlap(&m.source, &existing.source)
-                    || self.regions_overlap(&m.target, &existing.source)
-                    || self.regions_overlap(&m.source, &existing.target)
-                    || self.regions_overlap(&m.target, &existing.target)
+                regions_overlap(&m.source, &existing.source)
+                    || regions_overlap(&m.target, &existing.source)
+                    || regions_overlap(&m.source, &existing.target)
+                    || regions_overlap(&m.target, &existing.target)
             });
 
             if !overlaps {
@@ -245,13 +220,6 @@ impl CopyMoveDetector {
         Ok(filtered)
     }
 
-    fn regions_overlap(&self, a: &SRegion, b: &SRegion) -> bool {
-        let overlap_x = a.x < b.x + b.width && a.x + a.width > b.x;
-        let overlap_y = a.y < b.y + b.height && a.y + a.height > b.y;
-
-        overlap_x && overlap_y
-    }
-
     fn create_visualization(&self, original: &RgbImage, matches: &[MatchPair]) -> RgbImage {
         let mut vis = original.clone();
 
@@ -262,8 +230,8 @@ impl CopyMoveDetector {
                 ((i * 120 + 50) % 255) as u8,
             ]);
 
-            self.draw_rectangle(&mut vis, &match_pair.source, color);
-            self.draw_rectangle(&mut vis, &match_pair.target, color);
+            draw_rectangle(&mut vis, &match_pair.source, color);
+            draw_rectangle(&mut vis, &match_pair.target, color);
 
             self.draw_line(
                 &mut vis,
@@ -278,28 +246,6 @@ impl CopyMoveDetector {
         vis
     }
 
-    fn draw_rectangle(&self, image: &mut RgbImage, region: &SRegion, color: Rgb<u8>) {
-        let (width, height) = image.dimensions();
-
-        for x in region.x..(region.x + region.width).min(width) {
-            if region.y < height {
-                image.put_pixel(x, region.y, color);
-            }
-            if region.y + region.height - 1 < height {
-                image.put_pixel(x, region.y + region.height - 1, color);
-            }
-        }
-
-        for y in region.y..(region.y + region.height).min(height) {
-            if region.x < width {
-                image.put_pixel(region.x, y, color);
-            }
-            if region.x + region.width - 1 < width {
-                image.put_pixel(region.x + region.width - 1, y, color);
-            }
-        }
-    }
-
     fn draw_line(&self, image: &mut RgbImage, x0: u32, y0: u32, x1: u32, y1: u32, color: Rgb<u8>) {
         let (width, height) = image.dimensions();
 
@@ -0,0 +1,756 @@
+use std::collections::VecDeque;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    detection::{ConfidenceLevel, DetectedManipulation, ManipulationType},
+    error::{ForensicsError, Result},
+    image_utils::{bilinear_sample, draw_rectangle, rgb_to_luminance},
+};
+
+/// Radius of the cornea relative to the visible iris (7.8 mm over 5.8 mm on average).
+const CORNEA_TO_IRIS: f64 = 1.34;
+
+#[derive(Debug, Clone)]
+pub struct CornealReflectionConfig {
+    /// Iris radius range, in pixels, searched by the automatic detector.
+    pub min_iris_radius: u32,
+    pub max_iris_radius: u32,
+    pub edge_threshold: f64,
+    /// Smallest fraction of the iris boundary with a clear dark-to-bright edge.
+    pub min_edge_support: f64,
+    /// Smallest luminance difference between the sclera ring and the iris.
+    pub min_iris_contrast: f64,
+    pub max_detected_eyes: usize,
+    /// Highlight pixels must exceed both this luminance and the iris mean by
+    /// `highlight_sigma` standard deviations.
+    pub highlight_level: f64,
+    pub highlight_sigma: f64,
+    /// Largest angle, in radians, between the light directions of two eyes of one person.
+    pub eye_tolerance: f64,
+    /// Largest angle, in radians, between a person's light direction and the other people's.
+    pub person_tolerance: f64,
+}
+
+impl Default for CornealReflectionConfig {
+    fn default() -> Self {
+        Self {
+            min_iris_radius: 5,
+            max_iris_radius: 40,
+            edge_threshold: 20.0,
+            min_edge_support: 0.4,
+            min_iris_contrast: 30.0,
+            max_detected_eyes: 8,
+            highlight_level: 180.0,
+            highlight_sigma: 2.5,
+            eye_tolerance: 15f64.to_radians(),
+            person_tolerance: 20f64.to_radians(),
+        }
+    }
+}
+
+/// Iris of one eye. Eyes with the same `person` are checked against each other.
+#[derive(Debug, Clone)]
+pub struct EyeRegion {
+    pub person: String,
+    pub center: (f64, f64),
+    pub radius: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct EyeReflection {
+    pub eye: EyeRegion,
+    /// Centroid of the brightest specular highlight inside the iris.
+    pub highlight: Option<(f64, f64)>,
+    /// Corneal surface normal at the highlight, with x right, y down and z toward the camera.
+    pub normal: Option<[f64; 3]>,
+    /// Unit vector toward the light in the same frame.
+    pub light_direction: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PersonLighting {
+    pub person: String,
+    pub eyes: Vec<usize>,
+    pub light_direction: Option<[f64; 3]>,
+    /// Largest angle, in radians, between the light directions of any two of the person's
+    /// eyes.
+    pub eye_disagreement: Option<f64>,
+    /// Angle, in radians, to the closest light direction of any other person. With only
+    /// two people both are flagged, since either may be the inserted one.
+    pub scene_disagreement: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CornealReflectionResult {
+    pub eyes: Vec<EyeReflection>,
+    pub people: Vec<PersonLighting>,
+    pub manipulations: Vec<DetectedManipulation>,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+pub struct CornealReflectionAnalyzer {
+    config: CornealReflectionConfig,
+}
+
+impl CornealReflectionAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(CornealReflectionConfig::default())
+    }
+
+    pub fn with_config(config: CornealReflectionConfig) -> Self {
+        Self { config }
+    }
+
+    /// Finds iris circles automatically and pairs them into people; unpaired discs are
+    /// dropped.
+    pub fn analyze(&self, image: &DynamicImage) -> Result<CornealReflectionResult> {
+        let eyes = self.detect_eyes(image);
+        self.analyze_eyes(image, &eyes)
+    }
+
+    pub fn analyze_eyes(
+        &self,
+        image: &DynamicImage,
+        eyes: &[EyeRegion],
+    ) -> Result<CornealReflectionResult> {
+        if let Some(eye) = eyes.iter().find(|e| e.radius.is_nan() || e.radius <= 1.0) {
+            return Err(ForensicsError::InvalidParameter(format!(
+                "Iris radius of {} must exceed one pixel",
+                eye.person
+            )));
+        }
+
+        let rgb = image.to_rgb8();
+        let luminance = rgb_to_luminance(&rgb);
+
+        let reflections = eyes
+            .iter()
+            .map(|eye| {
+                let highlight = self.find_highlight(&luminance, eye);
+                let normal = highlight.and_then(|h| corneal_normal(eye, h));
+                EyeReflection {
+                    eye: eye.clone(),
+                    highlight,
+                    normal,
+                    light_direction: normal.map(reflect_view),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut people = Vec::<PersonLighting>::new();
+        for (i, reflection) in reflections.iter().enumerate() {
+            match people
+                .iter_mut()
+                .find(|p| p.person == reflection.eye.person)
+            {
+                Some(person) => person.eyes.push(i),
+                None => people.push(PersonLighting {
+                    person: reflection.eye.person.clone(),
+                    eyes: vec![i],
+                    light_direction: None,
+                    eye_disagreement: None,
+                    scene_disagreement: None,
+                }),
+            }
+        }
+
+        for person in &mut people {
+            let directions = person
+                .eyes
+                .iter()
+                .filter_map(|&i| reflections[i].light_direction)
+                .collect::<Vec<_>>();
+            person.light_direction = mean_direction(&directions);
+            person.eye_disagreement = (directions.len() >= 2).then(|| {
+                let mut worst = 0.0f64;
+                for (n, a) in directions.iter().enumerate() {
+                    for b in &directions[n + 1..] {
+                        worst = worst.max(angle_between(*a, *b));
+                    }
+                }
+                worst
+            });
+        }
+
+        for i in 0..people.len() {
+            let Some(own) = people[i].light_direction else {
+                continue;
+            };
+            people[i].scene_disagreement = people
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .filter_map(|(_, p)| p.light_direction)
+                .map(|d| angle_between(own, d))
+                .min_by(|a, b| a.total_cmp(b));
+        }
+
+        let manipulations = self.build_manipulations(&reflections, &people);
+        let manipulation_probability = manipulations
+            .iter()
+            .map(|m| m.confidence)
+            .fold(0.0, f64::max);
+        let visualization = self.create_visualization(&rgb, &reflections, &manipulations);
+
+        Ok(CornealReflectionResult {
+            eyes: reflections,
+            people,
+            manipulations,
+            visualization,
+            manipulation_probability,
+        })
+    }
+
+    /// Gradient Hough transform for dark discs on a brighter surround, followed by a radius
+    /// search on each center and pairing of similar irises side by side into people.
+    pub fn detect_eyes(&self, image: &DynamicImage) -> Vec<EyeRegion> {
+        let luminance = rgb_to_luminance(&image.to_rgb8());
+        let (height, width) = luminance.dim();
+        let (min_radius, max_radius) = (
+            self.config.min_iris_radius.max(2) as f64,
+            self.config
+                .max_iris_radius
+                .max(self.config.min_iris_radius.max(2)) as f64,
+        );
+        if width < 3 || height < 3 {
+            return Vec::new();
+        }
+
+        let mut accumulator = Array2::<f64>::zeros((height, width));
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let (gx, gy) = sobel(&luminance, x, y);
+                let magnitude = gx.hypot(gy);
+                if magnitude < self.config.edge_threshold {
+                    continue;
+                }
+                // The iris is darker than the sclera, so the gradient points away from it.
+                let (ux, uy) = (gx / magnitude, gy / magnitude);
+                let mut r = min_radius;
+                while r <= max_radius {
+                    let (cx, cy) = (x as f64 + 0.5 - r * ux, y as f64 + 0.5 - r * uy);
+                    if cx >= 0.0 && cy >= 0.0 && (cx as usize) < width && (cy as usize) < height {
+                        accumulator[[cy as usize, cx as usize]] += 1.0 / r;
+                    }
+                    r += 1.0;
+                }
+            }
+        }
+
+        let mut peaks = Vec::new();
+        let window = min_radius as usize;
+        for y in 0..height {
+            for x in 0..width {
+                let value = accumulator[[y, x]];
+                if value < 2.0 {
+                    continue;
+                }
+                let is_peak = (y.saturating_sub(window)..(y + window + 1).min(height)).all(|ny| {
+                    (x.saturating_sub(window)..(x + window + 1).min(width))
+                        .all(|nx| accumulator[[ny, nx]] < value || (nx, ny) <= (x, y))
+                });
+                if is_peak {
+                    peaks.push((value, x, y));
+                }
+            }
+        }
+        peaks.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut irises: Vec<(f64, f64, f64, f64)> = Vec::new();
+        for (_, x, y) in peaks {
+            if irises.len() >= 4 * self.config.max_detected_eyes {
+                break;
+            }
+            let Some((cx, cy, radius, support)) = self.refine_circle(
+                &luminance,
+                x as f64 + 0.5,
+                y as f64 + 0.5,
+                min_radius,
+                max_radius,
+            ) else {
+                continue;
+            };
+            if irises
+                .iter()
+                .any(|&(ox, oy, or, _)| (ox - cx).hypot(oy - cy) < or.max(radius))
+            {
+                continue;
+            }
+            irises.push((cx, cy, radius, support));
+        }
+
+        // Only discs holding a catch light are treated as irises.
+        let mut candidates = irises
+            .into_iter()
+            .filter(|&(cx, cy, radius, _)| {
+                let eye = EyeRegion {
+                    person: String::new(),
+                    center: (cx, cy),
+                    radius,
+                };
+                self.find_highlight(&luminance, &eye).is_some()
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.3.total_cmp(&a.3));
+        candidates.truncate(self.config.max_detected_eyes);
+
+        let mut paired = vec![false; candidates.len()];
+        let mut eyes = Vec::new();
+        let mut person = 0;
+        for i in 0..candidates.len() {
+            if paired[i] {
+                continue;
+            }
+            let (x0, y0, r0, _) = candidates[i];
+            let partner = (i + 1..candidates.len())
+                .filter(|&j| !paired[j])
+                .filter(|&j| {
+                    let (x1, y1, r1, _) = candidates[j];
+                    let radius = 0.5 * (r0 + r1);
+                    (r0 - r1).abs() < 0.3 * radius
+                        && (y1 - y0).abs() < 1.5 * radius
+                        && (3.0 * radius..12.0 * radius).contains(&(x1 - x0).abs())
+                })
+                .min_by(|&a, &b| {
+                    let da = (candidates[a].0 - x0).hypot(candidates[a].1 - y0);
+                    let db = (candidates[b].0 - x0).hypot(candidates[b].1 - y0);
+                    da.total_cmp(&db)
+                });
+
+            // A lone disc is more likely a button or a lamp than a face seen from the side.
+            let Some(j) = partner else {
+                continue;
+            };
+            paired[i] = true;
+            paired[j] = true;
+            person += 1;
+            let label = format!("person {}", person);
+            let (x1, y1, r1, _) = candidates[j];
+            eyes.push(EyeRegion {
+                person: label.clone(),
+                center: (x0, y0),
+                radius: r0,
+            });
+            eyes.push(EyeRegion {
+                person: label,
+                center: (x1, y1),
+                radius: r1,
+            });
+        }
+
+        eyes
+    }
+
+    /// Searches sub-pixel offsets of a Hough center for the circle with the strongest edge.
+    fn refine_circle(
+        &self,
+        luminance: &Array2<f64>,
+        cx: f64,
+        cy: f64,
+        min_radius: f64,
+        max_radius: f64,
+    ) -> Option<(f64, f64, f64, f64)> {
+        let (_, _, support) = self.fit_radius(luminance, cx, cy, min_radius, max_radius)?;
+        let mut best = (cx, cy, 0.0, 0.0, support);
+        for oy in -3..=3 {
+            for ox in -3..=3 {
+                let (x, y) = (cx + 0.25 * ox as f64, cy + 0.25 * oy as f64);
+                if let Some((radius, score, support)) =
+                    self.fit_radius(luminance, x, y, min_radius, max_radius)
+                    && score > best.3
+                {
+                    best = (x, y, radius, score, support);
+                }
+            }
+        }
+        let (x, y, radius, _, support) = best;
+        Some((x, y, radius, support))
+    }
+
+    /// Radius with the strongest outward dark-to-bright edge, its mean edge strength and the
+    /// fraction of the circle supporting it. Rejects circles whose inside is not clearly
+    /// darker than the ring.
+    fn fit_radius(
+        &self,
+        luminance: &Array2<f64>,
+        cx: f64,
+        cy: f64,
+        min_radius: f64,
+        max_radius: f64,
+    ) -> Option<(f64, f64, f64)> {
+        const SAMPLES: usize = 64;
+        // Pixel centers sit at half-integer image coordinates.
+        let (cx, cy) = (cx - 0.5, cy - 0.5);
+        let mut best: Option<(f64, f64, f64)> = None;
+
+        let mut r = min_radius;
+        while r <= max_radius {
+            let mut total = 0.0;
+            let mut supported = 0;
+            let mut valid = 0;
+            for k in 0..SAMPLES {
+                let angle = 2.0 * std::f64::consts::PI * k as f64 / SAMPLES as f64;
+                let (dx, dy) = (angle.cos(), angle.sin());
+                let (Some(inner), Some(outer)) = (
+                    bilinear_sample(luminance, cx + (r - 1.0) * dx, cy + (r - 1.0) * dy),
+                    bilinear_sample(luminance, cx + (r + 1.0) * dx, cy + (r + 1.0) * dy),
+                ) else {
+                    continue;
+                };
+                valid += 1;
+                let step = (outer - inner) / 2.0;
+                total += step.max(0.0);
+                if step * 4.0 > self.config.edge_threshold {
+                    supported += 1;
+                }
+            }
+            if valid == SAMPLES {
+                let score = total / SAMPLES as f64;
+                let support = supported as f64 / SAMPLES as f64;
+                if best.is_none_or(|(_, s, _)| score > s) {
+                    best = Some((r, score, support));
+                }
+            }
+            r += 0.5;
+        }
+
+        let (radius, score, support) = best?;
+        if support < self.config.min_edge_support {
+            return None;
+        }
+
+        let ring_mean = |from: f64, to: f64| {
+            let mut sum = 0.0;
+            let mut count = 0;
+            for k in 0..SAMPLES {
+                let angle = 2.0 * std::f64::consts::PI * k as f64 / SAMPLES as f64;
+                let mut t = from;
+                while t <= to {
+                    if let Some(v) = bilinear_sample(
+                        luminance,
+                        cx + t * radius * angle.cos(),
+                        cy + t * radius * angle.sin(),
+                    ) {
+                        sum += v;
+                        count += 1;
+                    }
+                    t += 0.25;
+                }
+            }
+            sum / count.max(1) as f64
+        };
+        (ring_mean(1.2, 1.5) - ring_mean(0.5, 0.8) >= self.config.min_iris_contrast)
+            .then_some((radius, score, support))
+    }
+
+    /// Centroid of the largest connected set of bright outliers within the iris.
+    fn find_highlight(&self, luminance: &Array2<f64>, eye: &EyeRegion) -> Option<(f64, f64)> {
+        let (height, width) = luminance.dim();
+        let (cx, cy) = eye.center;
+        let r = eye.radius;
+        let x0 = (cx - r).floor().max(0.0) as usize;
+        let y0 = (cy - r).floor().max(0.0) as usize;
+        let x1 = ((cx + r).ceil() as usize + 1).min(width);
+        let y1 = ((cy + r).ceil() as usize + 1).min(height);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        let inside = |x: usize, y: usize| (x as f64 + 0.5 - cx).hypot(y as f64 + 0.5 - cy) <= r;
+        let values = (y0..y1)
+            .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+            .filter(|&(x, y)| inside(x, y))
+            .map(|(x, y)| luminance[[y, x]])
+            .collect::<Vec<_>>();
+        if values.len() < 4 {
+            return None;
+        }
+
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        // The median and the spread of the lower half describe the iris without the highlight.
+        let median = sorted[sorted.len() / 2];
+        let lower = &sorted[..sorted.len() / 2];
+        let spread = (lower.iter().map(|v| (v - median).powi(2)).sum::<f64>()
+            / lower.len().max(1) as f64)
+            .sqrt();
+        let threshold = self
+            .config
+            .highlight_level
+            .max(median + self.config.highlight_sigma * spread);
+
+        let mut visited = Array2::from_elem((y1 - y0, x1 - x0), false);
+        let mut best: Option<(f64, f64, f64)> = None;
+        for sy in y0..y1 {
+            for sx in x0..x1 {
+                if visited[[sy - y0, sx - x0]]
+                    || !inside(sx, sy)
+                    || luminance[[sy, sx]] <= threshold
+                {
+                    continue;
+                }
+
+                visited[[sy - y0, sx - x0]] = true;
+                let mut queue = VecDeque::from([(sx, sy)]);
+                let (mut weight, mut mx, mut my) = (0.0, 0.0, 0.0);
+                while let Some((x, y)) = queue.pop_front() {
+                    let w = luminance[[y, x]] - threshold;
+                    weight += w;
+                    mx += w * (x as f64 + 0.5);
+                    my += w * (y as f64 + 0.5);
+                    for (dx, dy) in [(-1isize, 0isize), (1, 0), (0, -1), (0, 1)] {
+                        let (Some(nx), Some(ny)) =
+                            (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                        else {
+                            continue;
+                        };
+                        if nx < x0
+                            || ny < y0
+                            || nx >= x1
+                            || ny >= y1
+                            || visited[[ny - y0, nx - x0]]
+                            || !inside(nx, ny)
+                            || luminance[[ny, nx]] <= threshold
+                        {
+                            continue;
+                        }
+                        visited[[ny - y0, nx - x0]] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+
+                if weight > 0.0 && best.is_none_or(|(w, _, _)| weight > w) {
+                    best = Some((weight, mx / weight, my / weight));
+                }
+            }
+        }
+
+        best.map(|(_, x, y)| (x, y))
+    }
+
+    fn build_manipulations(
+        &self,
+        reflections: &[EyeReflection],
+        people: &[PersonLighting],
+    ) -> Vec<DetectedManipulation> {
+        let mut manipulations = Vec::new();
+
+        for person in people {
+            let region = eyes_region(person.eyes.iter().map(|&i| &reflections[i].eye));
+            let mut evidence = Vec::new();
+            let mut confidence = 0.0f64;
+            let mut lit_eyes = 0;
+            let (mut eyes_disagree, mut scene_disagrees) = (false, false);
+
+            for &i in &person.eyes {
+                if let Some(light) = reflections[i].light_direction {
+                    lit_eyes += 1;
+                    let (azimuth, elevation) = azimuth_elevation(light);
+                    evidence.push(format!(
+                        "Eye at ({:.0}, {:.0}) reflects a light at azimuth {:.0} deg, elevation {:.0} deg",
+                        reflections[i].eye.center.0,
+                        reflections[i].eye.center.1,
+                        azimuth,
+                        elevation
+                    ));
+                }
+            }
+
+            if let Some(angle) = person.eye_disagreement
+                && angle > self.config.eye_tolerance
+            {
+                evidence.push(if lit_eyes == 2 {
+                    format!(
+                        "Light directions of the two eyes differ by {:.0} deg",
+                        angle.to_degrees()
+                    )
+                } else {
+                    format!(
+                        "Light directions of the {} eyes differ by up to {:.0} deg",
+                        lit_eyes,
+                        angle.to_degrees()
+                    )
+                });
+                confidence = confidence.max(1.0 - self.config.eye_tolerance / angle);
+                eyes_disagree = true;
+            }
+
+            if let Some(angle) = person.scene_disagreement
+                && angle > self.config.person_tolerance
+            {
+                evidence.push(format!(
+                    "Light direction differs by at least {:.0} deg from every other person in the image",
+                    angle.to_degrees()
+                ));
+                confidence = confidence.max(1.0 - self.config.person_tolerance / angle);
+                scene_disagrees = true;
+            }
+
+            if confidence > 0.0 {
+                manipulations.push(DetectedManipulation {
+                    manipulation_type: ManipulationType::Splicing,
+                    region,
+                    confidence,
+                    confidence_level: ConfidenceLevel::from_score(confidence),
+                    description: match (eyes_disagree, scene_disagrees) {
+                        (true, false) => format!(
+                            "Corneal reflections of {} disagree with each other",
+                            person.person
+                        ),
+                        (false, _) => format!(
+                            "Corneal reflections of {} are inconsistent with the other people \
+                             in the scene",
+                            person.person
+                        ),
+                        (true, true) => format!(
+                            "Corneal reflections of {} disagree with each other and with the \
+                             other people in the scene",
+                            person.person
+                        ),
+                    },
+                    evidence,
+                });
+            }
+        }
+
+        manipulations
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        reflections: &[EyeReflection],
+        manipulations: &[DetectedManipulation],
+    ) -> RgbImage {
+        let mut vis = original.clone();
+
+        for reflection in reflections {
+            let (cx, cy) = reflection.eye.center;
+            let r = reflection.eye.radius;
+            let steps = (2.0 * std::f64::consts::PI * r).ceil().max(16.0) as usize;
+            for k in 0..steps {
+                let angle = 2.0 * std::f64::consts::PI * k as f64 / steps as f64;
+                put(
+                    &mut vis,
+                    cx + r * angle.cos(),
+                    cy + r * angle.sin(),
+                    Rgb([0, 255, 0]),
+                );
+            }
+
+            if let Some((hx, hy)) = reflection.highlight {
+                for d in -2..=2 {
+                    put(&mut vis, hx + d as f64, hy, Rgb([255, 0, 255]));
+                    put(&mut vis, hx, hy + d as f64, Rgb([255, 0, 255]));
+                }
+            }
+
+            if let Some(light) = reflection.light_direction {
+                let length = 3.0 * r;
+                for k in 0..(length as usize) {
+                    let t = k as f64;
+                    put(
+                        &mut vis,
+                        cx + t * light[0],
+                        cy + t * light[1],
+                        Rgb([255, 255, 0]),
+                    );
+                }
+            }
+        }
+
+        for manipulation in manipulations {
+            draw_rectangle(&mut vis, &manipulation.region, Rgb([255, 0, 0]));
+        }
+
+        vis
+    }
+}
+
+impl Default for CornealReflectionAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Corneal normal at a highlight, modelling the cornea as a sphere seen orthographically.
+fn corneal_normal(eye: &EyeRegion, highlight: (f64, f64)) -> Option<[f64; 3]> {
+    let cornea = CORNEA_TO_IRIS * eye.radius;
+    let nx = (highlight.0 - eye.center.0) / cornea;
+    let ny = (highlight.1 - eye.center.1) / cornea;
+    let planar = nx * nx + ny * ny;
+    (planar < 1.0).then(|| [nx, ny, (1.0 - planar).sqrt()])
+}
+
+/// Mirror direction of the view vector `(0, 0, 1)` about `normal`, i.e. toward the light.
+fn reflect_view(normal: [f64; 3]) -> [f64; 3] {
+    let d = 2.0 * normal[2];
+    [d * normal[0], d * normal[1], d * normal[2] - 1.0]
+}
+
+fn mean_direction(directions: &[[f64; 3]]) -> Option<[f64; 3]> {
+    let mut sum = [0.0; 3];
+    for d in directions {
+        for c in 0..3 {
+            sum[c] += d[c];
+        }
+    }
+    let norm = (sum[0] * sum[0] + sum[1] * sum[1] + sum[2] * sum[2]).sqrt();
+    (norm > 1e-9).then(|| sum.map(|v| v / norm))
+}
+
+fn angle_between(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (a[0] * b[0] + a[1] * b[1] + a[2] * b[2])
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+/// Azimuth counterclockwise from image right and elevation above the image plane, in degrees.
+fn azimuth_elevation(light: [f64; 3]) -> (f64, f64) {
+    (
+        (-light[1]).atan2(light[0]).to_degrees(),
+        light[2].clamp(-1.0, 1.0).asin().to_degrees(),
+    )
+}
+
+fn sobel(luminance: &Array2<f64>, x: usize, y: usize) -> (f64, f64) {
+    let p = |dx: usize, dy: usize| luminance[[y + dy - 1, x + dx - 1]];
+    let gx = p(2, 0) + 2.0 * p(2, 1) + p(2, 2) - p(0, 0) - 2.0 * p(0, 1) - p(0, 2);
+    let gy = p(0, 2) + 2.0 * p(1, 2) + p(2, 2) - p(0, 0) - 2.0 * p(1, 0) - p(2, 0);
+    (gx / 8.0, gy / 8.0)
+}
+
+fn eyes_region<'a>(eyes: impl Iterator<Item = &'a EyeRegion>) -> SRegion {
+    let (mut x0, mut y0, mut x1, mut y1) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for eye in eyes {
+        x0 = x0.min(eye.center.0 - 2.0 * eye.radius);
+        y0 = y0.min(eye.center.1 - 2.0 * eye.radius);
+        x1 = x1.max(eye.center.0 + 2.0 * eye.radius);
+        y1 = y1.max(eye.center.1 + 2.0 * eye.radius);
+    }
+    if x0 > x1 {
+        return SRegion {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+    }
+    let (x0, y0) = (x0.max(0.0), y0.max(0.0));
+    SRegion {
+        x: x0 as u32,
+        y: y0 as u32,
+        width: (x1 - x0).max(1.0) as u32,
+        height: (y1 - y0).max(1.0) as u32,
+    }
+}
+
+fn put(image: &mut RgbImage, x: f64, y: f64, color: Rgb<u8>) {
+    if x >= 0.0 && y >= 0.0 && x < image.width() as f64 && y < image.height() as f64 {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
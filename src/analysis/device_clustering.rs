@@ -0,0 +1,393 @@
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use ndarray::Array2;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    analysis::prnu_fingerprint::{PrnuFingerprintConfig, PrnuFingerprintEstimator},
+    error::{ForensicsError, Result},
+    image_utils::list_images,
+};
+
+#[derive(Debug, Clone)]
+pub struct DeviceClusteringConfig {
+    pub fingerprint: PrnuFingerprintConfig,
+    /// Side of the square center crop compared across images; reduced to the smallest
+    /// image when needed.
+    pub crop_size: u32,
+    /// Clusters stop merging once their average-linkage correlation falls below this
+    /// many standard deviations of the unrelated-pair correlation, `1 / crop_size`.
+    pub threshold_sigmas: f64,
+    pub min_crop_size: u32,
+}
+
+impl Default for DeviceClusteringConfig {
+    fn default() -> Self {
+        Self {
+            fingerprint: PrnuFingerprintConfig::default(),
+            crop_size: 512,
+            threshold_sigmas: 8.0,
+            min_crop_size: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterStatistics {
+    pub mean_correlation: f64,
+    pub min_correlation: f64,
+    pub max_correlation: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceCluster {
+    pub members: Vec<usize>,
+    /// Pairwise correlation between members; `None` for single-image clusters.
+    pub statistics: Option<ClusterStatistics>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DendrogramNode {
+    pub id: usize,
+    /// Image name for leaves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Average-linkage correlation at which the children were joined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+    pub size: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DendrogramNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceClusteringResult {
+    pub names: Vec<String>,
+    pub crop_size: u32,
+    pub correlation_threshold: f64,
+    pub correlations: Array2<f64>,
+    /// Clusters ordered by size, largest first.
+    pub clusters: Vec<DeviceCluster>,
+    /// Cluster index of every image.
+    pub assignments: Vec<usize>,
+    pub dendrogram: DendrogramNode,
+    /// Files that could not be opened or decoded and were left out of the clustering.
+    pub skipped: Vec<PathBuf>,
+}
+
+impl DeviceClusteringResult {
+    pub fn dendrogram_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.dendrogram)
+            .map_err(|e| ForensicsError::AnalysisFailed(e.to_string()))
+    }
+}
+
+pub struct DeviceClusterer {
+    config: DeviceClusteringConfig,
+    estimator: PrnuFingerprintEstimator,
+}
+
+impl DeviceClusterer {
+    pub fn new() -> Self {
+        Self::with_config(DeviceClusteringConfig::default())
+    }
+
+    pub fn with_config(config: DeviceClusteringConfig) -> Self {
+        Self {
+            estimator: PrnuFingerprintEstimator::with_config(config.fingerprint.clone()),
+            config,
+        }
+    }
+
+    pub fn cluster_directory<P: AsRef<Path>>(&self, dir: P) -> Result<DeviceClusteringResult> {
+        self.cluster_paths(&list_images(dir)?)
+    }
+
+    /// Images are loaded one at a time per worker, so large dumps need not fit in memory.
+    /// Files that cannot be opened or decoded are skipped and listed in the result.
+    pub fn cluster_paths(&self, paths: &[PathBuf]) -> Result<DeviceClusteringResult> {
+        let (readable, unreadable): (Vec<_>, Vec<_>) = paths
+            .iter()
+            .map(|path| (path, image::image_dimensions(path)))
+            .partition(|(_, dimensions)| dimensions.is_ok());
+
+        let dimensions = readable
+            .iter()
+            .filter_map(|(_, dimensions)| dimensions.as_ref().ok().copied())
+            .collect::<Vec<_>>();
+        let crop = self.crop_size(&dimensions)?;
+
+        let outcomes = readable
+            .par_iter()
+            .map(|(path, _)| {
+                image::open(path)
+                    .map(|image| (*path, self.residual(&image, crop)))
+                    .map_err(|_| *path)
+            })
+            .collect::<Vec<_>>();
+
+        let mut skipped = unreadable
+            .into_iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        let mut names = Vec::new();
+        let mut residuals = Vec::new();
+
+        for outcome in outcomes {
+            match outcome {
+                Ok((path, residual)) => {
+                    names.push(path.to_string_lossy().to_string());
+                    residuals.push(residual);
+                }
+                Err(path) => skipped.push(path.clone()),
+            }
+        }
+
+        if residuals.len() < 2 {
+            return Err(ForensicsError::InvalidParameter(
+                "Clustering needs at least two readable images".into(),
+            ));
+        }
+
+        let mut result = self.cluster_residuals(names, &residuals, crop);
+        result.skipped = skipped;
+
+        Ok(result)
+    }
+
+    pub fn cluster(&self, images: &[(String, DynamicImage)]) -> Result<DeviceClusteringResult> {
+        let dimensions = images
+            .iter()
+            .map(|(_, image)| (image.width(), image.height()))
+            .collect::<Vec<_>>();
+        let crop = self.crop_size(&dimensions)?;
+
+        let residuals = images
+            .par_iter()
+            .map(|(_, image)| self.residual(image, crop))
+            .collect::<Vec<_>>();
+
+        let names = images.iter().map(|(name, _)| name.clone()).collect();
+
+        Ok(self.cluster_residuals(names, &residuals, crop))
+    }
+
+    fn crop_size(&self, dimensions: &[(u32, u32)]) -> Result<u32> {
+        if dimensions.len() < 2 {
+            return Err(ForensicsError::InvalidParameter(
+                "Clustering needs at least two images".into(),
+            ));
+        }
+
+        let smallest = dimensions.iter().map(|&(w, h)| w.min(h)).min().unwrap_or(0);
+        let crop = self.config.crop_size.min(smallest);
+
+        if crop < self.config.min_crop_size {
+            return Err(ForensicsError::ImageTooSmall(self.config.min_crop_size));
+        }
+
+        Ok(crop)
+    }
+
+    /// Zero-mean, Wiener-filtered residual of the center crop, scaled to unit norm.
+    fn residual(&self, image: &DynamicImage, crop: u32) -> Array2<f64> {
+        let x = (image.width() - crop) / 2;
+        let y = (image.height() - crop) / 2;
+        let cropped = image.crop_imm(x, y, crop, crop);
+
+        let residual = self
+            .estimator
+            .wiener_in_dft(&self.estimator.extract_residual(&cropped).mapv(|v| v as f64));
+
+        let mean = residual.mean().unwrap_or(0.0);
+        let centered = residual.mapv(|v| v - mean);
+        let norm = centered.iter().map(|v| v * v).sum::<f64>().sqrt();
+
+        if norm > 0.0 {
+            centered / norm
+        } else {
+            centered
+        }
+    }
+
+    fn cluster_residuals(
+        &self,
+        names: Vec<String>,
+        residuals: &[Array2<f64>],
+        crop_size: u32,
+    ) -> DeviceClusteringResult {
+        let count = residuals.len();
+        let pairs = (0..count)
+            .flat_map(|i| (i + 1..count).map(move |j| (i, j)))
+            .collect::<Vec<_>>();
+
+        let values = pairs
+            .par_iter()
+            .map(|&(i, j)| {
+                residuals[i]
+                    .iter()
+                    .zip(residuals[j].iter())
+                    .map(|(a, b)| a * b)
+                    .sum::<f64>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut correlations = Array2::<f64>::eye(count);
+        for (&(i, j), &value) in pairs.iter().zip(&values) {
+            correlations[[i, j]] = value;
+            correlations[[j, i]] = value;
+        }
+
+        let correlation_threshold = self.config.threshold_sigmas / crop_size as f64;
+        let (dendrogram, groups) = Self::agglomerate(&names, &correlations, correlation_threshold);
+
+        let mut clusters = groups
+            .into_iter()
+            .map(|members| DeviceCluster {
+                statistics: Self::statistics(&members, &correlations),
+                members,
+            })
+            .collect::<Vec<_>>();
+        clusters.sort_by(|a, b| {
+            b.members
+                .len()
+                .cmp(&a.members.len())
+                .then(a.members[0].cmp(&b.members[0]))
+        });
+
+        let mut assignments = vec![0; count];
+        for (index, cluster) in clusters.iter().enumerate() {
+            for &member in &cluster.members {
+                assignments[member] = index;
+            }
+        }
+
+        DeviceClusteringResult {
+            names,
+            crop_size,
+            correlation_threshold,
+            correlations,
+            clusters,
+            assignments,
+            dendrogram,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Average-linkage agglomeration run to a single root. Clusters are cut where the
+    /// linkage first drops below the correlation threshold.
+    fn agglomerate(
+        names: &[String],
+        correlations: &Array2<f64>,
+        threshold: f64,
+    ) -> (DendrogramNode, Vec<Vec<usize>>) {
+        let count = names.len();
+        // A merged cluster takes the lower of its two slots; the other slot is emptied.
+        let mut slots = (0..count)
+            .map(|i| {
+                Some((
+                    vec![i],
+                    DendrogramNode {
+                        id: i,
+                        name: Some(names[i].clone()),
+                        similarity: None,
+                        size: 1,
+                        children: Vec::new(),
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+        let mut linkage = correlations.clone();
+
+        let mut groups = None;
+        let mut next_id = count;
+
+        for _ in 1..count {
+            let active = (0..count)
+                .filter(|&i| slots[i].is_some())
+                .collect::<Vec<_>>();
+
+            let mut best = (active[0], active[1], f64::MIN);
+            for (k, &a) in active.iter().enumerate() {
+                for &b in &active[k + 1..] {
+                    if linkage[[a, b]] > best.2 {
+                        best = (a, b, linkage[[a, b]]);
+                    }
+                }
+            }
+
+            let (a, b, similarity) = best;
+            if groups.is_none() && similarity < threshold {
+                groups = Some(
+                    slots
+                        .iter()
+                        .flatten()
+                        .map(|(m, _)| m.clone())
+                        .collect::<Vec<_>>(),
+                );
+            }
+
+            let (members_b, node_b) = slots[b].take().expect("active slot");
+            let (mut members_a, node_a) = slots[a].take().expect("active slot");
+
+            // Lance-Williams update for average linkage.
+            let (size_a, size_b) = (members_a.len() as f64, members_b.len() as f64);
+            for &k in &active {
+                if k == a || k == b {
+                    continue;
+                }
+                let merged =
+                    (size_a * linkage[[a, k]] + size_b * linkage[[b, k]]) / (size_a + size_b);
+                linkage[[a, k]] = merged;
+                linkage[[k, a]] = merged;
+            }
+
+            members_a.extend(members_b);
+            members_a.sort_unstable();
+
+            let node = DendrogramNode {
+                id: next_id,
+                name: None,
+                similarity: Some(similarity),
+                size: members_a.len(),
+                children: vec![node_a, node_b],
+            };
+            next_id += 1;
+
+            slots[a] = Some((members_a, node));
+        }
+
+        let (members, root) = slots
+            .into_iter()
+            .flatten()
+            .next()
+            .expect("at least one image");
+        (root, groups.unwrap_or_else(|| vec![members]))
+    }
+
+    fn statistics(members: &[usize], correlations: &Array2<f64>) -> Option<ClusterStatistics> {
+        let values = members
+            .iter()
+            .enumerate()
+            .flat_map(|(k, &i)| members[k + 1..].iter().map(move |&j| correlations[[i, j]]))
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        Some(ClusterStatistics {
+            mean_correlation: values.iter().sum::<f64>() / values.len() as f64,
+            min_correlation: values.iter().cloned().fold(f64::MAX, f64::min),
+            max_correlation: values.iter().cloned().fold(f64::MIN, f64::max),
+        })
+    }
+}
+
+impl Default for DeviceClusterer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,704 @@
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, GrayImage, Rgb, RgbImage, imageops::FilterType};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    MatchPair, SRegion,
+    error::{ForensicsError, Result},
+    image_utils::{
+        coefficient_similarity, draw_rectangle, list_images, regions_overlap, rgb_to_gray,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct DonorSearchConfig {
+    pub patch_radius: u32,
+    pub ring_count: usize,
+    pub max_keypoints: usize,
+    pub scales: Vec<f64>,
+    pub similarity_threshold: f64,
+    pub variance_threshold: f64,
+    pub min_matches: usize,
+    pub max_dimension: u32,
+    pub rotation_bin: f64,
+    pub translation_tolerance: f64,
+    pub ratio_test: f64,
+}
+
+impl Default for DonorSearchConfig {
+    fn default() -> Self {
+        Self {
+            patch_radius: 12,
+            ring_count: 8,
+            max_keypoints: 1500,
+            scales: vec![0.5, 0.625, 0.8, 1.0, 1.25, 1.6, 2.0],
+            similarity_threshold: 0.97,
+            variance_threshold: 60.0,
+            min_matches: 6,
+            max_dimension: 512,
+            rotation_bin: 10.0,
+            translation_tolerance: 12.0,
+            ratio_test: 0.85,
+        }
+    }
+}
+
+/// Maps donor coordinates onto the query: `query = scale * R(rotation) * donor + translation`.
+#[derive(Debug, Clone, Copy)]
+pub struct DonorTransform {
+    pub scale: f64,
+    pub rotation: f64, // degrees
+    pub translation_x: f64,
+    pub translation_y: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DonorMatch {
+    pub donor_path: PathBuf,
+    pub pair: MatchPair, // source = donor region, target = query region
+    pub transform: DonorTransform,
+    pub supporting_matches: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct DonorSearchResult {
+    pub matches: Vec<DonorMatch>,
+    pub searched_images: usize,
+    pub skipped_images: Vec<PathBuf>,
+    pub visualization: RgbImage,
+    pub confidence: f64,
+}
+
+#[derive(Clone)]
+struct PatchFeature {
+    x: f64,
+    y: f64,
+    descriptor: Vec<f64>,
+    orientation: f64,
+}
+
+struct PatchSample {
+    dx: i32,
+    dy: i32,
+    ring: usize,
+    cos: f64,
+    sin: f64,
+}
+
+struct PointMatch {
+    query: (f64, f64),
+    donor: (f64, f64),
+    scale_idx: usize,
+    rotation: f64,
+    similarity: f64,
+}
+
+pub struct DonorSearcher {
+    config: DonorSearchConfig,
+    samples: Vec<PatchSample>,
+}
+
+impl DonorSearcher {
+    pub fn new() -> Self {
+        Self::with_config(DonorSearchConfig::default())
+    }
+
+    pub fn with_config(config: DonorSearchConfig) -> Self {
+        // A zero ring count is rejected by `search_paths`.
+        let samples = Self::build_samples(config.patch_radius, config.ring_count.max(1));
+
+        Self { config, samples }
+    }
+
+    pub fn search<P: AsRef<Path>>(
+        &self,
+        query: &DynamicImage,
+        corpus_dir: P,
+    ) -> Result<DonorSearchResult> {
+        let paths = list_images(corpus_dir)?;
+        self.search_paths(query, &paths)
+    }
+
+    pub fn search_paths(
+        &self,
+        query: &DynamicImage,
+        paths: &[PathBuf],
+    ) -> Result<DonorSearchResult> {
+        if self.config.scales.is_empty() {
+            return Err(ForensicsError::InvalidParameter(
+                "At least one donor scale is required".into(),
+            ));
+        }
+
+        if self.config.ring_count == 0 {
+            return Err(ForensicsError::InvalidParameter(
+                "At least one descriptor ring is required".into(),
+            ));
+        }
+
+        let rgb = query.to_rgb8();
+        let gray = rgb_to_gray(&rgb);
+        let min_size = (self.config.patch_radius + 1) * 2;
+
+        if gray.width() < min_size || gray.height() < min_size {
+            return Err(ForensicsError::ImageTooSmall(min_size));
+        }
+
+        let (query_work, query_factor) = self.working_image(&gray);
+        let query_features = self.extract_features(&query_work);
+
+        let outcomes = paths
+            .par_iter()
+            .map(|path| match image::open(path) {
+                Ok(donor) => Ok(self.match_donor(
+                    path,
+                    &donor,
+                    &query_features,
+                    query_factor,
+                    gray.dimensions(),
+                )),
+                Err(_) => Err(path.clone()),
+            })
+            .collect::<Vec<_>>();
+
+        let mut matches = Vec::new();
+        let mut skipped_images = Vec::new();
+
+        for outcome in outcomes {
+            match outcome {
+                Ok(donor_matches) => matches.extend(donor_matches),
+                Err(path) => skipped_images.push(path),
+            }
+        }
+
+        matches.sort_by(|a, b| {
+            b.supporting_matches
+                .cmp(&a.supporting_matches)
+                .then(b.pair.similarity.partial_cmp(&a.pair.similarity).unwrap())
+        });
+
+        let visualization = self.create_visualization(&rgb, &matches);
+
+        let confidence = matches
+            .iter()
+            .map(|m| {
+                let support = m.supporting_matches as f64 / (self.config.min_matches * 3) as f64;
+                m.pair.similarity * support.min(1.0)
+            })
+            .fold(0.0, f64::max);
+
+        Ok(DonorSearchResult {
+            matches,
+            searched_images: paths.len() - skipped_images.len(),
+            skipped_images,
+            visualization,
+            confidence,
+        })
+    }
+
+    fn build_samples(radius: u32, ring_count: usize) -> Vec<PatchSample> {
+        let r = radius as i32;
+        let mut samples = Vec::new();
+
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let dist = ((dx * dx + dy * dy) as f64).sqrt();
+                if dist > radius as f64 {
+                    continue;
+                }
+
+                let ring = ((dist / (radius as f64 + 1e-9)) * ring_count as f64) as usize;
+                let (cos, sin) = if dist > 0.0 {
+                    (dx as f64 / dist, dy as f64 / dist)
+                } else {
+                    (0.0, 0.0)
+                };
+
+                samples.push(PatchSample {
+                    dx,
+                    dy,
+                    ring: ring.min(ring_count - 1),
+                    cos,
+                    sin,
+                });
+            }
+        }
+
+        samples
+    }
+
+    fn working_image(&self, gray: &GrayImage) -> (GrayImage, f64) {
+        let (width, height) = gray.dimensions();
+        let largest = width.max(height);
+
+        if largest <= self.config.max_dimension {
+            return (gray.clone(), 1.0);
+        }
+
+        let factor = self.config.max_dimension as f64 / largest as f64;
+        let resized = image::imageops::resize(
+            gray,
+            ((width as f64 * factor).round() as u32).max(1),
+            ((height as f64 * factor).round() as u32).max(1),
+            FilterType::Triangle,
+        );
+
+        (resized, factor)
+    }
+
+    fn extract_features(&self, gray: &GrayImage) -> Vec<PatchFeature> {
+        let (width, height) = gray.dimensions();
+        let radius = self.config.patch_radius;
+
+        if width <= radius * 2 || height <= radius * 2 {
+            return Vec::new();
+        }
+
+        self.detect_keypoints(gray)
+            .par_iter()
+            .filter_map(|&(x, y)| self.extract_patch_feature(gray, x, y))
+            .collect()
+    }
+
+    fn detect_keypoints(&self, gray: &GrayImage) -> Vec<(u32, u32)> {
+        let (width, height) = gray.dimensions();
+        let (w, h) = (width as usize, height as usize);
+        let margin = self.config.patch_radius as usize + 1;
+
+        let mut ixx = vec![0.0; w * h];
+        let mut iyy = vec![0.0; w * h];
+        let mut ixy = vec![0.0; w * h];
+
+        for y in 1..h - 1 {
+            for x in 1..w - 1 {
+                let p = |dx: i32, dy: i32| -> f64 {
+                    gray.get_pixel((x as i32 + dx) as u32, (y as i32 + dy) as u32)[0] as f64
+                };
+
+                let gx =
+                    -p(-1, -1) - 2.0 * p(-1, 0) - p(-1, 1) + p(1, -1) + 2.0 * p(1, 0) + p(1, 1);
+                let gy =
+                    -p(-1, -1) - 2.0 * p(0, -1) - p(1, -1) + p(-1, 1) + 2.0 * p(0, 1) + p(1, 1);
+
+                ixx[y * w + x] = gx * gx;
+                iyy[y * w + x] = gy * gy;
+                ixy[y * w + x] = gx * gy;
+            }
+        }
+
+        let window = 2usize;
+        let mut response = vec![0.0; w * h];
+
+        for y in margin..h - margin {
+            for x in margin..w - margin {
+                let mut sxx = 0.0;
+                let mut syy = 0.0;
+                let mut sxy = 0.0;
+
+                for yy in y - window..=y + window {
+                    for xx in x - window..=x + window {
+                        sxx += ixx[yy * w + xx];
+                        syy += iyy[yy * w + xx];
+                        sxy += ixy[yy * w + xx];
+                    }
+                }
+
+                let det = sxx * syy - sxy * sxy;
+                let trace = sxx + syy;
+                response[y * w + x] = det - 0.04 * trace * trace;
+            }
+        }
+
+        let max_response = response.iter().cloned().fold(0.0, f64::max);
+        if max_response <= 0.0 {
+            return Vec::new();
+        }
+
+        let threshold = max_response * 1e-4;
+        let suppression = 3usize;
+        let mut candidates = Vec::new();
+
+        for y in margin..h - margin {
+            for x in margin..w - margin {
+                let r = response[y * w + x];
+                if r <= threshold {
+                    continue;
+                }
+
+                let is_peak =
+                    (y.saturating_sub(suppression)..=(y + suppression).min(h - 1)).all(|yy| {
+                        (x.saturating_sub(suppression)..=(x + suppression).min(w - 1))
+                            .all(|xx| response[yy * w + xx] <= r)
+                    });
+
+                if is_peak {
+                    candidates.push((r, x as u32, y as u32));
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        candidates.truncate(self.config.max_keypoints);
+
+        candidates.into_iter().map(|(_, x, y)| (x, y)).collect()
+    }
+
+    fn extract_patch_feature(&self, gray: &GrayImage, cx: u32, cy: u32) -> Option<PatchFeature> {
+        let rings = self.config.ring_count;
+        let mut values = Vec::with_capacity(self.samples.len());
+
+        for s in &self.samples {
+            let px = (cx as i32 + s.dx) as u32;
+            let py = (cy as i32 + s.dy) as u32;
+            values.push(gray.get_pixel(px, py)[0] as f64);
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+        if variance < self.config.variance_threshold {
+            return None;
+        }
+
+        let std_dev = variance.sqrt();
+        let mut ring_sum = vec![0.0; rings];
+        let mut ring_count = vec![0.0f64; rings];
+        let mut ring_cos = vec![0.0; rings];
+        let mut ring_sin = vec![0.0; rings];
+        let mut total_cos = 0.0;
+        let mut total_sin = 0.0;
+
+        for (s, &v) in self.samples.iter().zip(values.iter()) {
+            let centered = v - mean;
+            ring_sum[s.ring] += v;
+            ring_count[s.ring] += 1.0;
+            ring_cos[s.ring] += centered * s.cos;
+            ring_sin[s.ring] += centered * s.sin;
+            total_cos += centered * s.cos;
+            total_sin += centered * s.sin;
+        }
+
+        let mut descriptor = ring_sum
+            .iter()
+            .zip(ring_count.iter())
+            .map(|(&sum, &count)| (sum / count.max(1.0) - mean) / std_dev)
+            .collect::<Vec<_>>();
+
+        descriptor.extend(
+            ring_cos
+                .iter()
+                .zip(ring_sin.iter())
+                .zip(ring_count.iter())
+                .map(|((&c, &s), &count)| c.hypot(s) / count.max(1.0) / std_dev),
+        );
+
+        Some(PatchFeature {
+            x: cx as f64,
+            y: cy as f64,
+            descriptor,
+            orientation: total_sin.atan2(total_cos),
+        })
+    }
+
+    fn match_donor(
+        &self,
+        path: &Path,
+        donor: &DynamicImage,
+        query_features: &[PatchFeature],
+        query_factor: f64,
+        query_dims: (u32, u32),
+    ) -> Vec<DonorMatch> {
+        let donor_gray = rgb_to_gray(&donor.to_rgb8());
+        let (donor_work, donor_factor) = self.working_image(&donor_gray);
+
+        let mut point_matches = Vec::new();
+
+        for (scale_idx, &scale) in self.config.scales.iter().enumerate() {
+            let width = (donor_work.width() as f64 * scale).round() as u32;
+            let height = (donor_work.height() as f64 * scale).round() as u32;
+
+            if width <= self.config.patch_radius * 2 || height <= self.config.patch_radius * 2 {
+                continue;
+            }
+
+            let scaled = if (scale - 1.0).abs() < 1e-9 {
+                donor_work.clone()
+            } else {
+                image::imageops::resize(&donor_work, width, height, FilterType::Triangle)
+            };
+
+            let donor_features = self.extract_features(&scaled);
+            let to_donor = 1.0 / (scale * donor_factor);
+
+            for (qi, di) in self.nearest_neighbours(query_features, &donor_features) {
+                let qf = &query_features[qi];
+                let df = &donor_features[di];
+
+                let similarity = coefficient_similarity(&qf.descriptor, &df.descriptor);
+
+                if similarity < self.config.similarity_threshold {
+                    continue;
+                }
+
+                point_matches.push(PointMatch {
+                    query: (qf.x / query_factor, qf.y / query_factor),
+                    donor: (df.x * to_donor, df.y * to_donor),
+                    scale_idx,
+                    rotation: Self::wrap_angle(qf.orientation - df.orientation),
+                    similarity,
+                });
+            }
+        }
+
+        let scale_of = |idx: usize| self.config.scales[idx] * donor_factor / query_factor;
+        let clusters = self.cluster_matches(&point_matches, &scale_of);
+
+        let query_radius = self.config.patch_radius as f64 / query_factor;
+        let mut results: Vec<DonorMatch> = Vec::new();
+
+        for (members, transform) in clusters {
+            let donor_radius = query_radius / transform.scale;
+            let source = Self::bounding_region(
+                members.iter().map(|&i| point_matches[i].donor),
+                donor_radius,
+                donor.width(),
+                donor.height(),
+            );
+            let target = Self::bounding_region(
+                members.iter().map(|&i| point_matches[i].query),
+                query_radius,
+                query_dims.0,
+                query_dims.1,
+            );
+
+            let similarity = members
+                .iter()
+                .map(|&i| point_matches[i].similarity)
+                .sum::<f64>()
+                / members.len() as f64;
+
+            let overlaps = results
+                .iter()
+                .any(|r| regions_overlap(&r.pair.target, &target));
+
+            if overlaps {
+                continue;
+            }
+
+            results.push(DonorMatch {
+                donor_path: path.to_path_buf(),
+                pair: MatchPair {
+                    source,
+                    target,
+                    similarity,
+                },
+                transform,
+                supporting_matches: members.len(),
+            });
+        }
+
+        results
+    }
+
+    fn nearest_neighbours(
+        &self,
+        query: &[PatchFeature],
+        donor: &[PatchFeature],
+    ) -> Vec<(usize, usize)> {
+        if donor.len() < 2 {
+            return Vec::new();
+        }
+
+        query
+            .par_iter()
+            .enumerate()
+            .filter_map(|(qi, qf)| {
+                let mut best = (f64::MAX, 0usize);
+                let mut second = f64::MAX;
+
+                for (di, df) in donor.iter().enumerate() {
+                    let dist = qf
+                        .descriptor
+                        .iter()
+                        .zip(df.descriptor.iter())
+                        .map(|(a, b)| (a - b).powi(2))
+                        .sum::<f64>();
+
+                    if dist < best.0 {
+                        second = best.0;
+                        best = (dist, di);
+                    } else if dist < second {
+                        second = dist;
+                    }
+                }
+
+                let ratio = self.config.ratio_test * self.config.ratio_test;
+                (best.0 < ratio * second).then_some((qi, best.1))
+            })
+            .collect()
+    }
+
+    fn cluster_matches(
+        &self,
+        matches: &[PointMatch],
+        scale_of: &dyn Fn(usize) -> f64,
+    ) -> Vec<(Vec<usize>, DonorTransform)> {
+        let bin_width = self.config.rotation_bin.to_radians();
+        let bin_count = ((2.0 * PI) / bin_width).round().max(1.0) as i64;
+
+        let mut groups: HashMap<(usize, i64), Vec<usize>> = HashMap::new();
+        for (i, m) in matches.iter().enumerate() {
+            let bin = ((m.rotation + PI) / bin_width).round() as i64 % bin_count;
+            groups.entry((m.scale_idx, bin)).or_default().push(i);
+        }
+
+        let mut keys = groups.keys().copied().collect::<Vec<_>>();
+        keys.sort_unstable();
+
+        let mut clusters = Vec::new();
+
+        for key in keys {
+            let members = &groups[&key];
+            if members.len() < self.config.min_matches {
+                continue;
+            }
+
+            let sin_sum = members
+                .iter()
+                .map(|&i| matches[i].rotation.sin())
+                .sum::<f64>();
+            let cos_sum = members
+                .iter()
+                .map(|&i| matches[i].rotation.cos())
+                .sum::<f64>();
+            let rotation = sin_sum.atan2(cos_sum);
+            let scale = scale_of(key.0);
+
+            let (sin_r, cos_r) = rotation.sin_cos();
+            let translations = members
+                .iter()
+                .map(|&i| {
+                    let (qx, qy) = matches[i].query;
+                    let (dx, dy) = matches[i].donor;
+                    (
+                        qx - scale * (cos_r * dx - sin_r * dy),
+                        qy - scale * (sin_r * dx + cos_r * dy),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let mut leaders: Vec<(f64, f64, Vec<usize>)> = Vec::new();
+            for (k, &(tx, ty)) in translations.iter().enumerate() {
+                let tolerance = self.config.translation_tolerance;
+                let leader = leaders.iter_mut().find(|(lx, ly, _)| {
+                    (lx - tx).abs() <= tolerance && (ly - ty).abs() <= tolerance
+                });
+
+                match leader {
+                    Some((lx, ly, indices)) => {
+                        let n = indices.len() as f64;
+                        *lx = (*lx * n + tx) / (n + 1.0);
+                        *ly = (*ly * n + ty) / (n + 1.0);
+                        indices.push(k);
+                    }
+                    None => leaders.push((tx, ty, vec![k])),
+                }
+            }
+
+            for (tx, ty, indices) in leaders {
+                if indices.len() < self.config.min_matches {
+                    continue;
+                }
+
+                clusters.push((
+                    indices.iter().map(|&k| members[k]).collect::<Vec<_>>(),
+                    DonorTransform {
+                        scale,
+                        rotation: rotation.to_degrees(),
+                        translation_x: tx,
+                        translation_y: ty,
+                    },
+                ));
+            }
+        }
+
+        clusters.sort_by_key(|c| std::cmp::Reverse(c.0.len()));
+        clusters
+    }
+
+    fn bounding_region(
+        points: impl Iterator<Item = (f64, f64)>,
+        radius: f64,
+        max_width: u32,
+        max_height: u32,
+    ) -> SRegion {
+        let mut min_x = f64::MAX;
+        let mut min_y = f64::MAX;
+        let mut max_x = f64::MIN;
+        let mut max_y = f64::MIN;
+
+        for (x, y) in points {
+            min_x = min_x.min(x - radius);
+            min_y = min_y.min(y - radius);
+            max_x = max_x.max(x + radius);
+            max_y = max_y.max(y + radius);
+        }
+
+        let x = min_x.max(0.0) as u32;
+        let y = min_y.max(0.0) as u32;
+        let x2 = (max_x.ceil().max(0.0) as u32).min(max_width);
+        let y2 = (max_y.ceil().max(0.0) as u32).min(max_height);
+
+        SRegion {
+            x,
+            y,
+            width: x2.saturating_sub(x).max(1),
+            height: y2.saturating_sub(y).max(1),
+        }
+    }
+
+    fn wrap_angle(angle: f64) -> f64 {
+        let mut a = angle;
+        while a > PI {
+            a -= 2.0 * PI;
+        }
+        while a <= -PI {
+            a += 2.0 * PI;
+        }
+        a
+    }
+
+    fn create_visualization(&self, original: &RgbImage, matches: &[DonorMatch]) -> RgbImage {
+        let mut vis = original.clone();
+        let mut donor_colors: HashMap<&Path, Rgb<u8>> = HashMap::new();
+
+        for m in matches {
+            let next = donor_colors.len();
+            let color = *donor_colors
+                .entry(m.donor_path.as_path())
+                .or_insert_with(|| {
+                    Rgb([
+                        ((next * 50) % 255) as u8,
+                        ((next * 80 + 100) % 255) as u8,
+                        ((next * 120 + 50) % 255) as u8,
+                    ])
+                });
+
+            draw_rectangle(&mut vis, &m.pair.target, color);
+        }
+
+        vis
+    }
+}
+
+impl Default for DonorSearcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
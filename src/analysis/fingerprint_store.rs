@@ -0,0 +1,291 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::DynamicImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::{
+        camera_identification::{
+            CameraIdentificationConfig, CameraIdentifier, IdentificationResult,
+        },
+        prnu_fingerprint::CameraFingerprint,
+    },
+    error::{ForensicsError, Result},
+};
+
+const STORE_INDEX_FILE: &str = "index.json";
+const STORE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub make: String,
+    pub model: String,
+    pub serial: String,
+    pub width: u32,
+    pub height: u32,
+    pub image_count: u32,
+    /// Fingerprint file name, relative to the store directory.
+    pub file: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceMatch {
+    pub device: DeviceInfo,
+    pub result: IdentificationResult,
+}
+
+#[derive(Debug)]
+pub struct StoreMatching {
+    /// Devices ranked by PCE, highest first.
+    pub matches: Vec<DeviceMatch>,
+    /// Ids of devices whose fingerprint could not be loaded or matched, with the error.
+    pub skipped: Vec<(String, ForensicsError)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoreIndex {
+    version: u32,
+    devices: Vec<DeviceInfo>,
+}
+
+/// Directory of camera fingerprints (`<id>.prnu`) described by an `index.json`.
+#[derive(Debug, Clone)]
+pub struct FingerprintStore {
+    root: PathBuf,
+    devices: Vec<DeviceInfo>,
+}
+
+impl FingerprintStore {
+    /// Opens the store at `root`, creating an empty one if the directory has no index.
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+
+        let index_path = root.join(STORE_INDEX_FILE);
+        if !index_path.exists() {
+            return Ok(Self {
+                root,
+                devices: Vec::new(),
+            });
+        }
+
+        let index: StoreIndex = serde_json::from_str(&fs::read_to_string(&index_path)?)
+            .map_err(|e| ForensicsError::UnsupportedFormat(e.to_string()))?;
+
+        if index.version != STORE_VERSION {
+            return Err(ForensicsError::UnsupportedFormat(format!(
+                "Unsupported fingerprint store version {}",
+                index.version
+            )));
+        }
+
+        Ok(Self {
+            root,
+            devices: index.devices,
+        })
+    }
+
+    pub fn devices(&self) -> &[DeviceInfo] {
+        &self.devices
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub fn device(&self, id: &str) -> Option<&DeviceInfo> {
+        self.devices.iter().find(|d| d.id == id)
+    }
+
+    /// Stores a fingerprint, replacing any device with the same make, model and serial.
+    /// A different device whose id would collide gets a numbered id instead.
+    pub fn add(
+        &mut self,
+        make: &str,
+        model: &str,
+        serial: &str,
+        fingerprint: &CameraFingerprint,
+    ) -> Result<DeviceInfo> {
+        let (make, model, serial) = (make.trim(), model.trim(), serial.trim());
+        let same_device = |d: &DeviceInfo| d.make == make && d.model == model && d.serial == serial;
+
+        let id = match self.devices.iter().find(|d| same_device(d)) {
+            Some(existing) => existing.id.clone(),
+            None => {
+                let base = Self::device_id(make, model, serial);
+                let mut id = base.clone();
+                let mut suffix = 2;
+                while self.device(&id).is_some() {
+                    id = format!("{}-{}", base, suffix);
+                    suffix += 1;
+                }
+                id
+            }
+        };
+        let file = format!("{}.prnu", id);
+
+        fingerprint.save(self.root.join(&file))?;
+
+        let device = DeviceInfo {
+            id: id.clone(),
+            make: make.to_string(),
+            model: model.to_string(),
+            serial: serial.to_string(),
+            width: fingerprint.width,
+            height: fingerprint.height,
+            image_count: fingerprint.image_count,
+            file,
+        };
+
+        self.devices.retain(|d| d.id != id);
+        self.devices.push(device.clone());
+        self.save_index()?;
+
+        Ok(device)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<bool> {
+        let Some(position) = self.devices.iter().position(|d| d.id == id) else {
+            return Ok(false);
+        };
+
+        let device = self.devices.remove(position);
+        let path = self.root.join(&device.file);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+
+        self.save_index()?;
+        Ok(true)
+    }
+
+    pub fn load(&self, id: &str) -> Result<CameraFingerprint> {
+        let device = self
+            .device(id)
+            .ok_or_else(|| ForensicsError::InvalidParameter(format!("Unknown device {}", id)))?;
+
+        CameraFingerprint::load(self.root.join(&device.file))
+    }
+
+    /// Matches `query` against every stored fingerprint in parallel and ranks devices by
+    /// PCE. Devices whose sensor is smaller than the query at all tested scales are left
+    /// out; devices whose fingerprint fails to load or match are reported as skipped.
+    /// Scales are searched one at a time per device, so only one set of sensor-sized
+    /// buffers is live per worker.
+    pub fn match_image(
+        &self,
+        query: &DynamicImage,
+        identifier: &CameraIdentifier,
+    ) -> Result<StoreMatching> {
+        let identifier = CameraIdentifier::with_config(CameraIdentificationConfig {
+            max_parallel_scales: 1,
+            ..identifier.config().clone()
+        });
+        let features = identifier.query_features(query);
+
+        let outcomes = self
+            .devices
+            .par_iter()
+            .filter(|device| identifier.fits_sensor(device.width, device.height, &features))
+            .map(|device| {
+                CameraFingerprint::load(self.root.join(&device.file))
+                    .and_then(|fingerprint| identifier.identify_features(&fingerprint, &features))
+                    .map(|result| DeviceMatch {
+                        device: device.clone(),
+                        result,
+                    })
+                    .map_err(|e| (device.id.clone(), e))
+            })
+            .collect::<Vec<_>>();
+
+        let mut matches = Vec::new();
+        let mut skipped = Vec::new();
+
+        for outcome in outcomes {
+            match outcome {
+                Ok(device_match) => matches.push(device_match),
+                Err(failure) => skipped.push(failure),
+            }
+        }
+
+        matches.sort_by(|a, b| b.result.pce.total_cmp(&a.result.pce));
+
+        Ok(StoreMatching { matches, skipped })
+    }
+
+    fn save_index(&self) -> Result<()> {
+        let index = StoreIndex {
+            version: STORE_VERSION,
+            devices: self.devices.clone(),
+        };
+
+        let json = serde_json::to_string_pretty(&index)
+            .map_err(|e| ForensicsError::AnalysisFailed(e.to_string()))?;
+        fs::write(self.root.join(STORE_INDEX_FILE), json)?;
+
+        Ok(())
+    }
+
+    fn device_id(make: &str, model: &str, serial: &str) -> String {
+        [make, model, serial]
+            .iter()
+            .map(|part| {
+                part.trim()
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() {
+                            c.to_ascii_lowercase()
+                        } else {
+                            '-'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array2;
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let fingerprint = CameraFingerprint {
+            width: 4,
+            height: 3,
+            image_count: 10,
+            data: Array2::from_shape_fn((3, 4), |(y, x)| (x as f32 - y as f32) * 0.01),
+        };
+
+        let mut store = FingerprintStore::open(dir.path()).unwrap();
+        store.add("Canon", "EOS 5D", "A1", &fingerprint).unwrap();
+        store.add("Nikon", "D750", "B2", &fingerprint).unwrap();
+        store.add("Canon", "EOS 5D", "A1", &fingerprint).unwrap();
+
+        let reopened = FingerprintStore::open(dir.path()).unwrap();
+        assert_eq!(reopened.len(), 2);
+
+        let device = reopened.device("canon_eos-5d_a1").unwrap();
+        assert_eq!((device.width, device.height), (4, 3));
+        assert_eq!(reopened.load(&device.id).unwrap().data, fingerprint.data);
+
+        let mut store = reopened;
+        let other = store.add("Canon", "EOS-5D", "A1", &fingerprint).unwrap();
+        assert_eq!(other.id, "canon_eos-5d_a1-2");
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.device("canon_eos-5d_a1").unwrap().model, "EOS 5D");
+    }
+}
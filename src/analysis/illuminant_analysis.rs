@@ -0,0 +1,878 @@
+use std::collections::VecDeque;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+    image_utils::gaussian_blur_3x3_f32,
+};
+
+#[derive(Debug, Clone)]
+pub struct IlluminantConfig {
+    /// Approximate side length, in pixels, of the SLIC superpixels.
+    pub superpixel_size: u32,
+    /// Weight of spatial distance against Lab color distance in SLIC.
+    pub compactness: f64,
+    pub slic_iterations: usize,
+    /// Pixels with a channel at or above this sRGB value are clipped and ignored.
+    pub saturation_level: u8,
+    /// Pixels darker than this sRGB luminance carry no usable color and are ignored.
+    pub dark_level: u8,
+    /// Minkowski norm of the gray-edge estimator.
+    pub edge_norm: f64,
+    /// Smallest mean linear gradient for a superpixel to get a gray-edge estimate.
+    pub min_edge_strength: f64,
+    /// Smallest relative spread of inverse intensity for an inverse-intensity chromaticity
+    /// fit; without it diffuse and specular pixels cannot be told apart.
+    pub min_iic_spread: f64,
+    /// Smallest correlation between chromaticity and inverse intensity for the fit.
+    pub min_iic_correlation: f64,
+    pub min_valid_pixels: usize,
+    /// Radius, in superpixel sizes, over which estimates are pooled.
+    pub neighbourhood_radius: f64,
+    /// Smallest angular error, in degrees, against the scene illuminant that can flag a
+    /// superpixel.
+    pub angle_threshold: f64,
+    /// Number of robust deviations above the median error a superpixel must lie.
+    pub robust_factor: f64,
+    /// Estimators that must each disagree with the scene illuminant before flagging.
+    pub min_agreeing_estimators: usize,
+    pub min_region_area: u32,
+    /// Flagged regions covering more of the image than this are treated as the scene.
+    pub max_region_fraction: f64,
+}
+
+impl Default for IlluminantConfig {
+    fn default() -> Self {
+        Self {
+            superpixel_size: 24,
+            compactness: 10.0,
+            slic_iterations: 10,
+            saturation_level: 250,
+            dark_level: 10,
+            edge_norm: 6.0,
+            min_edge_strength: 0.002,
+            min_iic_spread: 0.1,
+            min_iic_correlation: 0.6,
+            min_valid_pixels: 30,
+            neighbourhood_radius: 2.0,
+            angle_threshold: 4.0,
+            robust_factor: 3.0,
+            min_agreeing_estimators: 2,
+            min_region_area: 1500,
+            max_region_fraction: 0.4,
+        }
+    }
+}
+
+/// Illuminant estimates around one superpixel, each as a chromaticity triple summing to one.
+/// A single superpixel has nearly uniform color, so every estimator is pooled over the
+/// superpixels within `neighbourhood_radius` before it says anything about the light.
+#[derive(Debug, Clone)]
+pub struct SuperpixelIlluminant {
+    pub label: usize,
+    pub region: SRegion,
+    pub centroid: (f64, f64),
+    pub pixel_count: usize,
+    pub gray_world: Option<[f64; 3]>,
+    pub gray_edge: Option<[f64; 3]>,
+    /// Inverse-intensity chromaticity estimate, available only where specular highlights
+    /// mix with the diffuse color.
+    pub inverse_intensity: Option<[f64; 3]>,
+    /// Mean of the available estimates.
+    pub illuminant: Option<[f64; 3]>,
+    /// Angular error, in degrees, of `illuminant` against the scene illuminant.
+    pub deviation: f64,
+    pub flagged: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct IlluminantRegion {
+    pub region: SRegion,
+    pub superpixels: Vec<usize>,
+    pub pixel_count: usize,
+    /// Mean angular error, in degrees, of the region's superpixels.
+    pub mean_deviation: f64,
+    pub illuminant: [f64; 3],
+}
+
+#[derive(Debug, Clone)]
+pub struct IlluminantResult {
+    pub labels: Array2<usize>,
+    pub superpixels: Vec<SuperpixelIlluminant>,
+    pub scene_illuminant: [f64; 3],
+    /// Angular error threshold, in degrees, used for flagging.
+    pub threshold: f64,
+    /// Each superpixel painted with its estimated illuminant color.
+    pub illuminant_map: RgbImage,
+    /// Per-pixel angular error in degrees.
+    pub deviation_map: Array2<f64>,
+    pub inconsistent_regions: Vec<IlluminantRegion>,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+/// Running sums of one superpixel during SLIC.
+#[derive(Debug, Clone, Copy, Default)]
+struct Cluster {
+    l: f64,
+    a: f64,
+    b: f64,
+    x: f64,
+    y: f64,
+}
+
+pub struct IlluminantAnalyzer {
+    config: IlluminantConfig,
+}
+
+impl IlluminantAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(IlluminantConfig::default())
+    }
+
+    pub fn with_config(config: IlluminantConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn analyze(&self, image: &DynamicImage) -> Result<IlluminantResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let min_size = 2 * self.config.superpixel_size;
+        if width < min_size || height < min_size {
+            return Err(ForensicsError::ImageTooSmall(min_size));
+        }
+
+        let linear = linearize(&rgb);
+        let labels = self.segment(&rgb, &linear);
+        let mut superpixels = self.estimate(&rgb, &linear, &labels);
+        self.pool(&mut superpixels);
+
+        let scene_illuminant = scene_illuminant(&superpixels).ok_or_else(|| {
+            ForensicsError::AnalysisFailed("No superpixel has a usable illuminant".into())
+        })?;
+
+        for superpixel in &mut superpixels {
+            if let Some(illuminant) = superpixel.illuminant {
+                superpixel.deviation = angular_error(illuminant, scene_illuminant);
+            }
+        }
+        let threshold = self.flag(&mut superpixels, scene_illuminant);
+        let inconsistent_regions = self.group_regions(&superpixels, &labels);
+
+        let mut deviation_map = Array2::<f64>::zeros(labels.dim());
+        let mut illuminant_map = RgbImage::new(width, height);
+        for ((y, x), &label) in labels.indexed_iter() {
+            let superpixel = &superpixels[label];
+            deviation_map[[y, x]] = superpixel.deviation;
+            if let Some(c) = superpixel.illuminant {
+                let peak = c[0].max(c[1]).max(c[2]).max(1e-12);
+                illuminant_map.put_pixel(
+                    x as u32,
+                    y as u32,
+                    Rgb([
+                        (255.0 * c[0] / peak) as u8,
+                        (255.0 * c[1] / peak) as u8,
+                        (255.0 * c[2] / peak) as u8,
+                    ]),
+                );
+            }
+        }
+
+        let manipulation_probability = inconsistent_regions
+            .iter()
+            .map(|r| 1.0 - threshold / r.mean_deviation.max(threshold))
+            .fold(0.0, f64::max);
+
+        let visualization = self.create_visualization(&rgb, &labels, &inconsistent_regions);
+
+        Ok(IlluminantResult {
+            labels,
+            superpixels,
+            scene_illuminant,
+            threshold,
+            illuminant_map,
+            deviation_map,
+            inconsistent_regions,
+            visualization,
+            manipulation_probability,
+        })
+    }
+
+    /// SLIC superpixels in CIELAB, followed by merging of small disconnected fragments.
+    fn segment(&self, rgb: &RgbImage, linear: &[Array2<f32>; 3]) -> Array2<usize> {
+        let (width, height) = rgb.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let step = self.config.superpixel_size as usize;
+
+        let mut lab = Array2::<[f64; 3]>::from_elem((height, width), [0.0; 3]);
+        for ((y, x), value) in lab.indexed_iter_mut() {
+            *value = linear_to_lab([
+                linear[0][[y, x]] as f64,
+                linear[1][[y, x]] as f64,
+                linear[2][[y, x]] as f64,
+            ]);
+        }
+
+        let mut centers = Vec::new();
+        for gy in 0..height.div_ceil(step) {
+            for gx in 0..width.div_ceil(step) {
+                let x = (gx * step + step / 2).min(width - 1);
+                let y = (gy * step + step / 2).min(height - 1);
+                let [l, a, b] = lab[[y, x]];
+                centers.push(Cluster {
+                    l,
+                    a,
+                    b,
+                    x: x as f64,
+                    y: y as f64,
+                });
+            }
+        }
+
+        let spatial_weight = (self.config.compactness / step as f64).powi(2);
+        let mut labels = Array2::<usize>::zeros((height, width));
+        let mut distances = Array2::<f64>::from_elem((height, width), f64::MAX);
+
+        for _ in 0..self.config.slic_iterations {
+            distances.fill(f64::MAX);
+            for (k, center) in centers.iter().enumerate() {
+                let x0 = (center.x as usize).saturating_sub(step);
+                let y0 = (center.y as usize).saturating_sub(step);
+                let x1 = (center.x as usize + step + 1).min(width);
+                let y1 = (center.y as usize + step + 1).min(height);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let [l, a, b] = lab[[y, x]];
+                        let color = (l - center.l).powi(2)
+                            + (a - center.a).powi(2)
+                            + (b - center.b).powi(2);
+                        let spatial = (x as f64 - center.x).powi(2) + (y as f64 - center.y).powi(2);
+                        let distance = color + spatial_weight * spatial;
+                        if distance < distances[[y, x]] {
+                            distances[[y, x]] = distance;
+                            labels[[y, x]] = k;
+                        }
+                    }
+                }
+            }
+
+            let mut sums = vec![Cluster::default(); centers.len()];
+            let mut counts = vec![0usize; centers.len()];
+            for ((y, x), &k) in labels.indexed_iter() {
+                let [l, a, b] = lab[[y, x]];
+                let sum = &mut sums[k];
+                sum.l += l;
+                sum.a += a;
+                sum.b += b;
+                sum.x += x as f64;
+                sum.y += y as f64;
+                counts[k] += 1;
+            }
+            for ((center, sum), &count) in centers.iter_mut().zip(&sums).zip(&counts) {
+                if count > 0 {
+                    let n = count as f64;
+                    *center = Cluster {
+                        l: sum.l / n,
+                        a: sum.a / n,
+                        b: sum.b / n,
+                        x: sum.x / n,
+                        y: sum.y / n,
+                    };
+                }
+            }
+        }
+
+        self.enforce_connectivity(&labels, step * step / 4)
+    }
+
+    /// Relabels connected components, merging those below `min_size` pixels into the
+    /// previously labelled neighbour.
+    fn enforce_connectivity(&self, labels: &Array2<usize>, min_size: usize) -> Array2<usize> {
+        let (height, width) = labels.dim();
+        let mut relabelled = Array2::from_elem((height, width), usize::MAX);
+        let mut next_label = 0;
+
+        for sy in 0..height {
+            for sx in 0..width {
+                if relabelled[[sy, sx]] != usize::MAX {
+                    continue;
+                }
+
+                let adjacent = neighbours(sx, sy, width, height)
+                    .map(|(x, y)| relabelled[[y, x]])
+                    .find(|&l| l != usize::MAX);
+
+                let original = labels[[sy, sx]];
+                let mut component = vec![(sx, sy)];
+                let mut queue = VecDeque::from([(sx, sy)]);
+                relabelled[[sy, sx]] = next_label;
+                while let Some((x, y)) = queue.pop_front() {
+                    for (nx, ny) in neighbours(x, y, width, height) {
+                        if relabelled[[ny, nx]] == usize::MAX && labels[[ny, nx]] == original {
+                            relabelled[[ny, nx]] = next_label;
+                            component.push((nx, ny));
+                            queue.push_back((nx, ny));
+                        }
+                    }
+                }
+
+                match adjacent {
+                    Some(label) if component.len() < min_size => {
+                        for (x, y) in component {
+                            relabelled[[y, x]] = label;
+                        }
+                    }
+                    _ => next_label += 1,
+                }
+            }
+        }
+
+        relabelled
+    }
+
+    fn estimate(
+        &self,
+        rgb: &RgbImage,
+        linear: &[Array2<f32>; 3],
+        labels: &Array2<usize>,
+    ) -> Vec<SuperpixelIlluminant> {
+        let count = labels.iter().max().map_or(0, |&l| l + 1);
+        let (height, width) = labels.dim();
+
+        let blurred = linear.clone().map(|c| gaussian_blur_3x3_f32(&c));
+        let mut valid = Array2::from_elem((height, width), false);
+        for (x, y, pixel) in rgb.enumerate_pixels() {
+            let luminance =
+                0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+            valid[[y as usize, x as usize]] =
+                pixel.0.iter().all(|&c| c < self.config.saturation_level)
+                    && luminance >= self.config.dark_level as f64;
+        }
+
+        let mut members = vec![Vec::new(); count];
+        for ((y, x), &label) in labels.indexed_iter() {
+            members[label].push((x, y));
+        }
+
+        members
+            .iter()
+            .enumerate()
+            .map(|(label, pixels)| {
+                let region = bounding_region(pixels);
+                let usable = pixels
+                    .iter()
+                    .copied()
+                    .filter(|&(x, y)| valid[[y, x]])
+                    .collect::<Vec<_>>();
+
+                let n = pixels.len().max(1) as f64;
+                let mut superpixel = SuperpixelIlluminant {
+                    label,
+                    region,
+                    centroid: (
+                        pixels.iter().map(|p| p.0 as f64).sum::<f64>() / n,
+                        pixels.iter().map(|p| p.1 as f64).sum::<f64>() / n,
+                    ),
+                    pixel_count: pixels.len(),
+                    gray_world: None,
+                    gray_edge: None,
+                    inverse_intensity: None,
+                    illuminant: None,
+                    deviation: 0.0,
+                    flagged: false,
+                };
+                if usable.len() < self.config.min_valid_pixels {
+                    return superpixel;
+                }
+
+                superpixel.gray_world = self.gray_world(&usable, linear);
+                superpixel.gray_edge = self.gray_edge(&usable, &blurred);
+                superpixel.inverse_intensity = self.inverse_intensity(&usable, linear);
+                superpixel
+            })
+            .collect()
+    }
+
+    /// Replaces each superpixel's estimates by the pixel-weighted mean of its neighbours'
+    /// and sets `illuminant` to the mean of the pooled estimators.
+    fn pool(&self, superpixels: &mut [SuperpixelIlluminant]) {
+        let radius = self.config.neighbourhood_radius * self.config.superpixel_size as f64;
+        let cell = radius.max(1.0);
+        let columns = superpixels
+            .iter()
+            .map(|s| (s.centroid.0 / cell) as usize)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let rows = superpixels
+            .iter()
+            .map(|s| (s.centroid.1 / cell) as usize)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let mut grid = vec![Vec::new(); columns * rows];
+        for (i, superpixel) in superpixels.iter().enumerate() {
+            let (column, row) = (
+                (superpixel.centroid.0 / cell) as usize,
+                (superpixel.centroid.1 / cell) as usize,
+            );
+            grid[row * columns + column].push(i);
+        }
+
+        let raw = superpixels
+            .iter()
+            .map(|s| {
+                (
+                    s.centroid,
+                    s.pixel_count as f64,
+                    [s.gray_world, s.gray_edge, s.inverse_intensity],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for superpixel in superpixels.iter_mut() {
+            let (cx, cy) = superpixel.centroid;
+            let (column, row) = ((cx / cell) as usize, (cy / cell) as usize);
+            let mut sums = [[0.0; 3]; 3];
+            for r in row.saturating_sub(1)..(row + 2).min(rows) {
+                for c in column.saturating_sub(1)..(column + 2).min(columns) {
+                    for &j in &grid[r * columns + c] {
+                        let ((x, y), weight, estimates) = &raw[j];
+                        if (x - cx).hypot(y - cy) > radius {
+                            continue;
+                        }
+                        for (sum, estimate) in sums.iter_mut().zip(estimates) {
+                            if let Some(estimate) = estimate {
+                                for k in 0..3 {
+                                    sum[k] += weight * estimate[k];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            let [gray_world, gray_edge, inverse_intensity] = sums.map(chromaticity);
+            superpixel.gray_world = gray_world;
+            superpixel.gray_edge = gray_edge;
+            superpixel.inverse_intensity = inverse_intensity;
+
+            let available = [gray_world, gray_edge, inverse_intensity]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            if !available.is_empty() {
+                let mut mean = [0.0; 3];
+                for estimate in &available {
+                    for k in 0..3 {
+                        mean[k] += estimate[k];
+                    }
+                }
+                superpixel.illuminant = chromaticity(mean);
+            }
+        }
+    }
+
+    fn gray_world(&self, pixels: &[(usize, usize)], linear: &[Array2<f32>; 3]) -> Option<[f64; 3]> {
+        let mut sum = [0.0; 3];
+        for &(x, y) in pixels {
+            for c in 0..3 {
+                sum[c] += linear[c][[y, x]] as f64;
+            }
+        }
+        chromaticity(sum)
+    }
+
+    /// Minkowski mean of the per-channel gradient magnitudes of the smoothed image.
+    fn gray_edge(&self, pixels: &[(usize, usize)], blurred: &[Array2<f32>; 3]) -> Option<[f64; 3]> {
+        let (height, width) = blurred[0].dim();
+        let p = self.config.edge_norm;
+        let mut sum = [0.0; 3];
+        let mut mean_magnitude = 0.0;
+        let mut count = 0usize;
+
+        for &(x, y) in pixels {
+            if x == 0 || y == 0 || x + 1 >= width || y + 1 >= height {
+                continue;
+            }
+            count += 1;
+            for c in 0..3 {
+                let channel = &blurred[c];
+                let gx = (channel[[y, x + 1]] - channel[[y, x - 1]]) as f64 / 2.0;
+                let gy = (channel[[y + 1, x]] - channel[[y - 1, x]]) as f64 / 2.0;
+                let magnitude = gx.hypot(gy);
+                sum[c] += magnitude.powf(p);
+                mean_magnitude += magnitude / 3.0;
+            }
+        }
+
+        if count == 0 || mean_magnitude / (count as f64) < self.config.min_edge_strength {
+            return None;
+        }
+        chromaticity(sum.map(|s| (s / count as f64).powf(1.0 / p)))
+    }
+
+    /// Inverse-intensity chromaticity: in the dichromatic model, the chromaticity of pixels
+    /// mixing one diffuse color with the specular reflection is linear in the inverse
+    /// intensity, and the line meets the chromaticity axis at the illuminant. Only the
+    /// brighter half of the pixels, where highlights live, is fitted.
+    fn inverse_intensity(
+        &self,
+        pixels: &[(usize, usize)],
+        linear: &[Array2<f32>; 3],
+    ) -> Option<[f64; 3]> {
+        let mut samples = pixels
+            .iter()
+            .map(|&(x, y)| {
+                let value = [0, 1, 2].map(|c| linear[c][[y, x]] as f64);
+                (value[0] + value[1] + value[2], value)
+            })
+            .filter(|(sum, _)| *sum > 1e-6)
+            .collect::<Vec<_>>();
+        samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let bright = &samples[samples.len() / 2..];
+        if bright.len() < self.config.min_valid_pixels / 2 {
+            return None;
+        }
+
+        let n = bright.len() as f64;
+        let inverse = bright.iter().map(|(sum, _)| 1.0 / sum).collect::<Vec<_>>();
+        let mean_inverse = inverse.iter().sum::<f64>() / n;
+        let variance = inverse
+            .iter()
+            .map(|p| (p - mean_inverse).powi(2))
+            .sum::<f64>()
+            / n;
+        if variance.sqrt() < self.config.min_iic_spread * mean_inverse {
+            return None;
+        }
+
+        let mut intercepts = [0.0; 3];
+        let mut correlation = 0.0f64;
+        for c in 0..3 {
+            let chroma = bright.iter().map(|(sum, v)| v[c] / sum).collect::<Vec<_>>();
+            let mean_chroma = chroma.iter().sum::<f64>() / n;
+            let chroma_variance = chroma
+                .iter()
+                .map(|s| (s - mean_chroma).powi(2))
+                .sum::<f64>()
+                / n;
+            let covariance = inverse
+                .iter()
+                .zip(&chroma)
+                .map(|(p, s)| (p - mean_inverse) * (s - mean_chroma))
+                .sum::<f64>()
+                / n;
+            let slope = covariance / variance;
+            intercepts[c] = mean_chroma - slope * mean_inverse;
+            if chroma_variance > 1e-12 {
+                correlation =
+                    correlation.max(covariance.abs() / (variance * chroma_variance).sqrt());
+            }
+        }
+
+        // Shading alone spreads the intensity without moving the chromaticity; only a
+        // clear trend shows a specular component.
+        if correlation < self.config.min_iic_correlation || intercepts.iter().any(|&v| v <= 0.0) {
+            return None;
+        }
+        chromaticity(intercepts)
+    }
+
+    /// Flags superpixels whose illuminant is both far from the scene's and contradicted by
+    /// enough individual estimators, returning the threshold used.
+    fn flag(&self, superpixels: &mut [SuperpixelIlluminant], scene: [f64; 3]) -> f64 {
+        let mut deviations = superpixels
+            .iter()
+            .filter(|s| s.illuminant.is_some())
+            .map(|s| s.deviation)
+            .collect::<Vec<_>>();
+        deviations.sort_by(|a, b| a.total_cmp(b));
+        let median = deviations[deviations.len() / 2];
+        let mut absolute = deviations
+            .iter()
+            .map(|d| (d - median).abs())
+            .collect::<Vec<_>>();
+        absolute.sort_by(|a, b| a.total_cmp(b));
+        let sigma = 1.4826 * absolute[absolute.len() / 2];
+
+        let threshold = self
+            .config
+            .angle_threshold
+            .max(median + self.config.robust_factor * sigma);
+
+        for superpixel in superpixels.iter_mut() {
+            if superpixel.illuminant.is_none() || superpixel.deviation <= threshold {
+                continue;
+            }
+            let agreeing = [
+                superpixel.gray_world,
+                superpixel.gray_edge,
+                superpixel.inverse_intensity,
+            ]
+            .iter()
+            .flatten()
+            .filter(|&&estimate| angular_error(estimate, scene) > threshold)
+            .count();
+            superpixel.flagged = agreeing >= self.config.min_agreeing_estimators;
+        }
+
+        threshold
+    }
+
+    /// Groups adjacent flagged superpixels into regions.
+    fn group_regions(
+        &self,
+        superpixels: &[SuperpixelIlluminant],
+        labels: &Array2<usize>,
+    ) -> Vec<IlluminantRegion> {
+        let (height, width) = labels.dim();
+        let mut adjacency = vec![Vec::new(); superpixels.len()];
+        for ((y, x), &label) in labels.indexed_iter() {
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < width && ny < height {
+                    let other = labels[[ny, nx]];
+                    if other != label && !adjacency[label].contains(&other) {
+                        adjacency[label].push(other);
+                        adjacency[other].push(label);
+                    }
+                }
+            }
+        }
+
+        let max_pixels = self.config.max_region_fraction * (width * height) as f64;
+        let mut visited = vec![false; superpixels.len()];
+        let mut regions = Vec::new();
+
+        for start in 0..superpixels.len() {
+            if visited[start] || !superpixels[start].flagged {
+                continue;
+            }
+
+            visited[start] = true;
+            let mut group = vec![start];
+            let mut next = 0;
+            while next < group.len() {
+                let current = group[next];
+                next += 1;
+                for &neighbour in &adjacency[current] {
+                    if !visited[neighbour] && superpixels[neighbour].flagged {
+                        visited[neighbour] = true;
+                        group.push(neighbour);
+                    }
+                }
+            }
+
+            let pixel_count = group
+                .iter()
+                .map(|&i| superpixels[i].pixel_count)
+                .sum::<usize>();
+            if pixel_count < self.config.min_region_area as usize || pixel_count as f64 > max_pixels
+            {
+                continue;
+            }
+
+            let mut sum = [0.0; 3];
+            let mut deviation = 0.0;
+            for &i in &group {
+                let weight = superpixels[i].pixel_count as f64;
+                if let Some(illuminant) = superpixels[i].illuminant {
+                    for c in 0..3 {
+                        sum[c] += weight * illuminant[c];
+                    }
+                }
+                deviation += weight * superpixels[i].deviation;
+            }
+
+            let region = group.iter().map(|&i| superpixels[i].region).fold(
+                superpixels[start].region,
+                |a, b| {
+                    let x = a.x.min(b.x);
+                    let y = a.y.min(b.y);
+                    SRegion {
+                        x,
+                        y,
+                        width: (a.x + a.width).max(b.x + b.width) - x,
+                        height: (a.y + a.height).max(b.y + b.height) - y,
+                    }
+                },
+            );
+
+            regions.push(IlluminantRegion {
+                region,
+                superpixels: group,
+                pixel_count,
+                mean_deviation: deviation / pixel_count as f64,
+                illuminant: chromaticity(sum).unwrap_or([1.0 / 3.0; 3]),
+            });
+        }
+
+        regions.sort_by(|a, b| b.mean_deviation.total_cmp(&a.mean_deviation));
+        regions
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        labels: &Array2<usize>,
+        regions: &[IlluminantRegion],
+    ) -> RgbImage {
+        let mut vis = original.clone();
+        let (height, width) = labels.dim();
+
+        let mut flagged = vec![false; labels.iter().max().map_or(0, |&l| l + 1)];
+        for region in regions {
+            for &i in &region.superpixels {
+                flagged[i] = true;
+            }
+        }
+
+        for ((y, x), &label) in labels.indexed_iter() {
+            let boundary = (x + 1 < width && labels[[y, x + 1]] != label)
+                || (y + 1 < height && labels[[y + 1, x]] != label);
+            let pixel = vis.get_pixel_mut(x as u32, y as u32);
+            if flagged[label] {
+                if boundary {
+                    *pixel = Rgb([255, 0, 0]);
+                } else {
+                    pixel[0] = ((pixel[0] as u16 + 255) / 2) as u8;
+                }
+            } else if boundary {
+                *pixel = Rgb([pixel[0] / 2 + 64, pixel[1] / 2 + 64, pixel[2] / 2 + 64]);
+            }
+        }
+
+        vis
+    }
+}
+
+impl Default for IlluminantAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Linear-light RGB channels in `[0, 1]`, undoing the sRGB transfer curve.
+fn linearize(rgb: &RgbImage) -> [Array2<f32>; 3] {
+    let table = (0..256)
+        .map(|v| {
+            let s = v as f32 / 255.0;
+            if s <= 0.04045 {
+                s / 12.92
+            } else {
+                ((s + 0.055) / 1.055).powf(2.4)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let (width, height) = rgb.dimensions();
+    let mut channels = [0, 1, 2].map(|_| Array2::<f32>::zeros((height as usize, width as usize)));
+    for (x, y, pixel) in rgb.enumerate_pixels() {
+        for (c, channel) in channels.iter_mut().enumerate() {
+            channel[[y as usize, x as usize]] = table[pixel[c] as usize];
+        }
+    }
+    channels
+}
+
+fn linear_to_lab(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb;
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f64| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn chromaticity(v: [f64; 3]) -> Option<[f64; 3]> {
+    let sum = v[0] + v[1] + v[2];
+    (sum > 1e-12 && v.iter().all(|c| c.is_finite() && *c >= 0.0)).then(|| v.map(|c| c / sum))
+}
+
+/// Angle, in degrees, between two illuminant colors.
+fn angular_error(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let dot = a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let norms =
+        (a.iter().map(|v| v * v).sum::<f64>() * b.iter().map(|v| v * v).sum::<f64>()).sqrt();
+    if norms < 1e-300 {
+        return 0.0;
+    }
+    (dot / norms).clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// Pixel-weighted median of each chromaticity component over all estimated superpixels.
+fn scene_illuminant(superpixels: &[SuperpixelIlluminant]) -> Option<[f64; 3]> {
+    let estimated = superpixels
+        .iter()
+        .filter_map(|s| s.illuminant.map(|c| (c, s.pixel_count as f64)))
+        .collect::<Vec<_>>();
+    if estimated.is_empty() {
+        return None;
+    }
+    let total = estimated.iter().map(|(_, w)| w).sum::<f64>();
+
+    let mut median = [0.0; 3];
+    for (c, value) in median.iter_mut().enumerate() {
+        let mut components = estimated
+            .iter()
+            .map(|(e, w)| (e[c], *w))
+            .collect::<Vec<_>>();
+        components.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut cumulative = 0.0;
+        for (component, weight) in components {
+            cumulative += weight;
+            if cumulative >= total / 2.0 {
+                *value = component;
+                break;
+            }
+        }
+    }
+    chromaticity(median)
+}
+
+fn bounding_region(pixels: &[(usize, usize)]) -> SRegion {
+    let (min_x, max_x, min_y, max_y) = pixels.iter().fold(
+        (usize::MAX, 0, usize::MAX, 0),
+        |(x0, x1, y0, y1), &(x, y)| (x0.min(x), x1.max(x), y0.min(y), y1.max(y)),
+    );
+    if pixels.is_empty() {
+        return SRegion {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+    }
+    SRegion {
+        x: min_x as u32,
+        y: min_y as u32,
+        width: (max_x - min_x + 1) as u32,
+        height: (max_y - min_y + 1) as u32,
+    }
+}
+
+fn neighbours(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    [(-1isize, 0isize), (1, 0), (0, -1), (0, 1)]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let nx = x.checked_add_signed(dx)?;
+            let ny = y.checked_add_signed(dy)?;
+            (nx < width && ny < height).then_some((nx, ny))
+        })
+}
@@ -0,0 +1,355 @@
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    analysis::{
+        jpeg_analysis::JpegAnalyzer,
+        registration::{ImageRegistrar, Registration, RegistrationConfig},
+    },
+    error::{ForensicsError, Result},
+    image_utils::{draw_rectangle, merge_regions},
+    report::visualization::Visualizer,
+};
+
+#[derive(Debug, Clone)]
+pub struct ComparisonConfig {
+    pub registration: RegistrationConfig,
+    pub block_size: u32,
+    pub change_threshold: f64,
+    pub amplification: f64,
+}
+
+impl Default for ComparisonConfig {
+    fn default() -> Self {
+        Self {
+            registration: RegistrationConfig::default(),
+            block_size: 16,
+            change_threshold: 12.0,
+            amplification: 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransformSummary {
+    /// Published size relative to the original (0.5 = downscaled by half).
+    pub resize_factor: f64,
+    pub rotation: f64,
+    /// Area of the original that is visible in the published image.
+    pub crop_box: SRegion,
+    pub original_quality: u8,
+    pub published_quality: u8,
+    pub quality_change: i32,
+    pub photometric_gain: [f64; 3],
+    pub photometric_offset: [f64; 3],
+}
+
+#[derive(Debug, Clone)]
+pub struct ComparisonResult {
+    pub registration: Registration,
+    pub summary: TransformSummary,
+    pub aligned_original: RgbImage,
+    pub difference_map: GrayImage,
+    pub heatmap: RgbImage,
+    pub changed_regions: Vec<SRegion>,
+    pub changed_fraction: f64,
+    pub uncovered_fraction: f64,
+    pub visualization: RgbImage,
+}
+
+pub struct ImageComparator {
+    config: ComparisonConfig,
+    registrar: ImageRegistrar,
+    jpeg_analyzer: JpegAnalyzer,
+    visualizer: Visualizer,
+}
+
+impl ImageComparator {
+    pub fn new() -> Self {
+        Self::with_config(ComparisonConfig::default())
+    }
+
+    pub fn with_config(config: ComparisonConfig) -> Self {
+        Self {
+            registrar: ImageRegistrar::with_config(config.registration.clone()),
+            config,
+            jpeg_analyzer: JpegAnalyzer::new(),
+            visualizer: Visualizer::new(),
+        }
+    }
+
+    /// Aligns `original` onto `published` and reports what differs. Changed regions and
+    /// maps are expressed in the published image's coordinates.
+    pub fn compare(
+        &self,
+        original: &DynamicImage,
+        published: &DynamicImage,
+    ) -> Result<ComparisonResult> {
+        let (width, height) = (published.width(), published.height());
+
+        if width < self.config.block_size * 2 || height < self.config.block_size * 2 {
+            return Err(ForensicsError::ImageTooSmall(self.config.block_size * 2));
+        }
+
+        let registration = self.registrar.register(original, published)?;
+        let registration = self.registrar.refine(original, published, registration);
+
+        let original_rgb = original.to_rgb8();
+        let published_rgb = published.to_rgb8();
+
+        let warped = (0..3)
+            .map(|c| {
+                registration.warp(
+                    &Self::channel(&original_rgb, c),
+                    width as usize,
+                    height as usize,
+                )
+            })
+            .collect::<Vec<_>>();
+        let target = (0..3)
+            .map(|c| Self::channel(&published_rgb, c))
+            .collect::<Vec<_>>();
+
+        let mut gains = [1.0; 3];
+        let mut offsets = [0.0; 3];
+        let mut normalized = Vec::with_capacity(3);
+
+        for c in 0..3 {
+            let (gain, offset) = self.photometric_fit(&warped[c], &target[c]);
+            gains[c] = gain;
+            offsets[c] = offset;
+            normalized.push(warped[c].mapv(|v| gain * v + offset));
+        }
+
+        let aligned_original = Self::compose_rgb(&normalized);
+        let (difference, valid) = self.difference(&normalized, &target);
+
+        let difference_map = GrayImage::from_fn(width, height, |x, y| {
+            let d = difference[[y as usize, x as usize]] * self.config.amplification;
+            Luma([d.clamp(0.0, 255.0) as u8])
+        });
+
+        let (changed_regions, changed_pixels) = self.find_changed_regions(&difference, &valid);
+
+        let total = (width * height) as f64;
+        let uncovered = valid.iter().filter(|&&v| !v).count() as f64;
+
+        let summary = self.summarize(original, published, &registration, gains, offsets)?;
+
+        let heatmap = self.visualizer.create_heatmap(&difference_map);
+        let mut marked = self.visualizer.overlay_heatmap(&published_rgb, &heatmap);
+        for region in &changed_regions {
+            draw_rectangle(&mut marked, region, Rgb([255, 0, 0]));
+        }
+
+        let visualization = self.visualizer.create_comparison(&[
+            ("Original (aligned)", &aligned_original),
+            ("Published", &published_rgb),
+            ("Differences", &marked),
+        ]);
+
+        Ok(ComparisonResult {
+            registration,
+            summary,
+            aligned_original,
+            difference_map,
+            heatmap,
+            changed_regions,
+            changed_fraction: changed_pixels as f64 / total,
+            uncovered_fraction: uncovered / total,
+            visualization,
+        })
+    }
+
+    fn summarize(
+        &self,
+        original: &DynamicImage,
+        published: &DynamicImage,
+        registration: &Registration,
+        photometric_gain: [f64; 3],
+        photometric_offset: [f64; 3],
+    ) -> Result<TransformSummary> {
+        let original_quality = self.jpeg_analyzer.estimate_quality(original)?;
+        let published_quality = self.jpeg_analyzer.estimate_quality(published)?;
+
+        let (min_x, min_y, max_x, max_y) =
+            registration.footprint(published.width(), published.height());
+        let x = min_x.clamp(0.0, original.width() as f64);
+        let y = min_y.clamp(0.0, original.height() as f64);
+
+        Ok(TransformSummary {
+            resize_factor: 1.0 / registration.scale,
+            rotation: registration.rotation,
+            crop_box: SRegion {
+                x: x as u32,
+                y: y as u32,
+                width: (max_x.clamp(0.0, original.width() as f64) - x).round() as u32,
+                height: (max_y.clamp(0.0, original.height() as f64) - y).round() as u32,
+            },
+            original_quality,
+            published_quality,
+            quality_change: published_quality as i32 - original_quality as i32,
+            photometric_gain,
+            photometric_offset,
+        })
+    }
+
+    fn photometric_fit(&self, source: &Array2<f64>, target: &Array2<f64>) -> (f64, f64) {
+        let samples = source
+            .iter()
+            .zip(target.iter())
+            .filter(|(s, _)| !s.is_nan())
+            .map(|(&s, &t)| (s, t))
+            .collect::<Vec<_>>();
+
+        if samples.len() < 2 {
+            return (1.0, 0.0);
+        }
+
+        let n = samples.len() as f64;
+        let mean_s = samples.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_t = samples.iter().map(|p| p.1).sum::<f64>() / n;
+
+        let (mut cov, mut var) = (0.0, 0.0);
+        for &(s, t) in &samples {
+            cov += (s - mean_s) * (t - mean_t);
+            var += (s - mean_s).powi(2);
+        }
+
+        if var < 1e-9 {
+            return (1.0, mean_t - mean_s);
+        }
+
+        let gain = cov / var;
+        (gain, mean_t - gain * mean_s)
+    }
+
+    fn difference(
+        &self,
+        normalized: &[Array2<f64>],
+        target: &[Array2<f64>],
+    ) -> (Array2<f64>, Array2<bool>) {
+        let (height, width) = target[0].dim();
+        let smoothed = normalized
+            .iter()
+            .zip(target)
+            .map(|(n, t)| (Self::smooth(n), Self::smooth(t)))
+            .collect::<Vec<_>>();
+
+        let valid = Array2::from_shape_fn((height, width), |idx| !normalized[0][idx].is_nan());
+
+        let difference = Array2::from_shape_fn((height, width), |idx| {
+            if !valid[idx] {
+                return 0.0;
+            }
+
+            smoothed
+                .iter()
+                .map(|(n, t)| (n[idx] - t[idx]).abs())
+                .filter(|d| !d.is_nan())
+                .fold(0.0, f64::max)
+        });
+
+        (difference, valid)
+    }
+
+    /// Merged regions of changed blocks, and the number of valid pixels inside those blocks.
+    fn find_changed_regions(
+        &self,
+        difference: &Array2<f64>,
+        valid: &Array2<bool>,
+    ) -> (Vec<SRegion>, usize) {
+        let (height, width) = difference.dim();
+        let block_size = self.config.block_size as usize;
+        let mut regions = Vec::new();
+        let mut changed_pixels = 0;
+
+        for by in (0..height).step_by(block_size) {
+            for bx in (0..width).step_by(block_size) {
+                let block_h = block_size.min(height - by);
+                let block_w = block_size.min(width - bx);
+
+                let mut sum = 0.0;
+                let mut count = 0usize;
+
+                for y in by..by + block_h {
+                    for x in bx..bx + block_w {
+                        if valid[[y, x]] {
+                            sum += difference[[y, x]];
+                            count += 1;
+                        }
+                    }
+                }
+
+                if count * 2 < block_h * block_w {
+                    continue;
+                }
+
+                if sum / count as f64 > self.config.change_threshold {
+                    changed_pixels += count;
+                    regions.push(SRegion {
+                        x: bx as u32,
+                        y: by as u32,
+                        width: block_w as u32,
+                        height: block_h as u32,
+                    });
+                }
+            }
+        }
+
+        (merge_regions(regions), changed_pixels)
+    }
+
+    fn channel(image: &RgbImage, channel: usize) -> Array2<f64> {
+        let (width, height) = image.dimensions();
+
+        Array2::from_shape_fn((height as usize, width as usize), |(y, x)| {
+            image.get_pixel(x as u32, y as u32)[channel] as f64
+        })
+    }
+
+    fn compose_rgb(channels: &[Array2<f64>]) -> RgbImage {
+        let (height, width) = channels[0].dim();
+
+        RgbImage::from_fn(width as u32, height as u32, |x, y| {
+            let idx = [y as usize, x as usize];
+            let value = |c: usize| {
+                let v = channels[c][idx];
+                if v.is_nan() {
+                    0
+                } else {
+                    v.clamp(0.0, 255.0) as u8
+                }
+            };
+            Rgb([value(0), value(1), value(2)])
+        })
+    }
+
+    fn smooth(arr: &Array2<f64>) -> Array2<f64> {
+        let (height, width) = arr.dim();
+
+        Array2::from_shape_fn((height, width), |(y, x)| {
+            let mut sum = 0.0;
+            let mut count = 0.0;
+
+            for yy in y.saturating_sub(1)..(y + 2).min(height) {
+                for xx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let v = arr[[yy, xx]];
+                    if !v.is_nan() {
+                        sum += v;
+                        count += 1.0;
+                    }
+                }
+            }
+
+            if count > 0.0 { sum / count } else { f64::NAN }
+        })
+    }
+}
+
+impl Default for ImageComparator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -42,7 +42,7 @@ impl JpegAnalyzer {
         })
     }
 
-    fn estimate_quality(&self, image: &DynamicImage) -> Result<u8> {
+    pub fn estimate_quality(&self, image: &DynamicImage) -> Result<u8> {
         let mut min_diff = f64::MAX;
         let mut best_quality = 75u8;
 
@@ -0,0 +1,614 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+use image::{DynamicImage, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+    image_utils::{bilinear_sample, draw_rectangle, median, rgb_to_luminance, solve_linear_system},
+};
+
+/// Number of spherical-harmonic lighting terms observable along occluding contours.
+const SH_TERMS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct LightingConfig {
+    /// Distance, in pixels, inside the contour at which intensities are sampled.
+    pub inset: f64,
+    pub min_contour_points: usize,
+    /// Tikhonov weight on the second-order harmonics, relative to the sample count.
+    pub regularization: f64,
+    /// Smallest azimuth difference, in radians, that can flag an object.
+    pub angle_tolerance: f64,
+    /// Number of combined standard deviations an azimuth must deviate by to be flagged.
+    pub uncertainty_factor: f64,
+    /// Objects with a larger angular uncertainty, in radians, are reported but not used
+    /// for the scene direction.
+    pub max_uncertainty: f64,
+    /// Area bounds, in pixels, of automatically detected objects.
+    pub min_object_area: u32,
+    pub max_object_fraction: f64,
+    /// Smallest luminance difference from the background for automatic detection.
+    pub min_contrast: f64,
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        Self {
+            inset: 2.0,
+            min_contour_points: 20,
+            regularization: 0.05,
+            angle_tolerance: 20f64.to_radians(),
+            uncertainty_factor: 2.0,
+            max_uncertainty: 30f64.to_radians(),
+            min_object_area: 400,
+            max_object_fraction: 0.25,
+            min_contrast: 15.0,
+        }
+    }
+}
+
+/// A contour sample with its outward surface normal in image coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct ContourPoint {
+    pub x: f64,
+    pub y: f64,
+    pub normal_x: f64,
+    pub normal_y: f64,
+}
+
+/// Points on the occluding boundary of one object. Several contours with the same label
+/// are fitted together.
+#[derive(Debug, Clone)]
+pub struct OccludingContour {
+    pub label: String,
+    pub points: Vec<ContourPoint>,
+}
+
+impl OccludingContour {
+    /// Builds a contour from an ordered polyline. Normals are perpendicular to the line and
+    /// point away from the centroid of the points, so arcs must bulge away from the object.
+    pub fn from_polyline(label: &str, polyline: &[(f64, f64)]) -> Self {
+        let count = polyline.len().max(1) as f64;
+        let cx = polyline.iter().map(|p| p.0).sum::<f64>() / count;
+        let cy = polyline.iter().map(|p| p.1).sum::<f64>() / count;
+
+        let mut points = Vec::new();
+        for segment in polyline.windows(2) {
+            let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+            let length = (x1 - x0).hypot(y1 - y0);
+            if length < 1e-9 {
+                continue;
+            }
+
+            let (mut nx, mut ny) = ((y1 - y0) / length, -(x1 - x0) / length);
+            let (mx, my) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+            if nx * (mx - cx) + ny * (my - cy) < 0.0 {
+                (nx, ny) = (-nx, -ny);
+            }
+
+            for step in 0..length.ceil() as usize {
+                let t = step as f64 / length;
+                points.push(ContourPoint {
+                    x: x0 + t * (x1 - x0),
+                    y: y0 + t * (y1 - y0),
+                    normal_x: nx,
+                    normal_y: ny,
+                });
+            }
+        }
+
+        Self {
+            label: label.to_string(),
+            points,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectLighting {
+    pub label: String,
+    pub bounds: SRegion,
+    /// Unit light direction with x to the right, y up and z toward the viewer. Occluding
+    /// contours carry no information about z, so it is always zero.
+    pub direction: (f64, f64, f64),
+    /// Angle of `direction` counter-clockwise from the x axis, in radians.
+    pub azimuth: f64,
+    /// Standard deviation of `azimuth`, in radians.
+    pub angular_uncertainty: f64,
+    /// Coefficients of `1, nx, ny, nx^2 - ny^2, nx * ny` with `ny` pointing up.
+    pub coefficients: [f64; SH_TERMS],
+    /// RMS intensity residual of the fit.
+    pub residual: f64,
+    pub samples: usize,
+    pub inconsistent: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct LightingResult {
+    pub objects: Vec<ObjectLighting>,
+    /// Uncertainty-weighted mean azimuth of the reliable, consistent objects.
+    pub scene_azimuth: Option<f64>,
+    pub inconsistent_objects: Vec<SRegion>,
+    pub visualization: RgbImage,
+    pub consistency_score: f64,
+    pub manipulation_probability: f64,
+}
+
+pub struct LightingAnalyzer {
+    config: LightingConfig,
+}
+
+impl LightingAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(LightingConfig::default())
+    }
+
+    pub fn with_config(config: LightingConfig) -> Self {
+        Self { config }
+    }
+
+    /// Estimates lighting from automatically detected object contours.
+    pub fn analyze(&self, image: &DynamicImage) -> Result<LightingResult> {
+        let contours = self.detect_contours(image);
+        self.analyze_contours(image, &contours)
+    }
+
+    pub fn analyze_contours(
+        &self,
+        image: &DynamicImage,
+        contours: &[OccludingContour],
+    ) -> Result<LightingResult> {
+        let rgb = image.to_rgb8();
+        let luminance = rgb_to_luminance(&rgb);
+
+        let mut labels: Vec<&str> = Vec::new();
+        for contour in contours {
+            if !labels.contains(&contour.label.as_str()) {
+                labels.push(&contour.label);
+            }
+        }
+
+        let mut objects = labels
+            .into_iter()
+            .filter_map(|label| {
+                let points = contours
+                    .iter()
+                    .filter(|c| c.label == label)
+                    .flat_map(|c| c.points.iter().copied())
+                    .collect::<Vec<_>>();
+                self.fit_object(&luminance, label, &points)
+            })
+            .collect::<Vec<_>>();
+
+        if objects.is_empty() && !contours.is_empty() {
+            return Err(ForensicsError::AnalysisFailed(
+                "No contour had enough usable samples to fit a lighting model".to_string(),
+            ));
+        }
+
+        let deviations = self.flag_inconsistent(&mut objects);
+        let scene_azimuth = self.scene_azimuth(&objects);
+
+        let inconsistent_objects = objects
+            .iter()
+            .filter(|o| o.inconsistent)
+            .map(|o| o.bounds)
+            .collect::<Vec<_>>();
+
+        let consistency_score = if deviations.is_empty() {
+            1.0
+        } else {
+            deviations.iter().map(|d| d.cos().max(0.0)).sum::<f64>() / deviations.len() as f64
+        };
+
+        let manipulation_probability = if objects.len() >= 2 {
+            inconsistent_objects.len() as f64 / objects.len() as f64
+        } else {
+            0.0
+        };
+
+        let visualization = self.create_visualization(&rgb, &objects, scene_azimuth);
+
+        Ok(LightingResult {
+            objects,
+            scene_azimuth,
+            inconsistent_objects,
+            visualization,
+            consistency_score,
+            manipulation_probability,
+        })
+    }
+
+    /// Finds compact objects that stand out from the background, taken as the median
+    /// luminance along the image border, and do not touch the border.
+    pub fn detect_contours(&self, image: &DynamicImage) -> Vec<OccludingContour> {
+        let luminance = rgb_to_luminance(&image.to_rgb8());
+        let (height, width) = luminance.dim();
+        let mut border = luminance
+            .indexed_iter()
+            .filter(|((y, x), _)| *x == 0 || *y == 0 || *x == width - 1 || *y == height - 1)
+            .map(|(_, &v)| v)
+            .collect::<Vec<_>>();
+        let background = median(&mut border);
+        let mut deviations = border
+            .iter()
+            .map(|v| (v - background).abs())
+            .collect::<Vec<_>>();
+        let threshold = (3.0 * 1.4826 * median(&mut deviations)).max(self.config.min_contrast);
+        let foreground = luminance.mapv(|v| (v - background).abs() > threshold);
+        let max_area = (self.config.max_object_fraction * (width * height) as f64) as usize;
+
+        let mut visited = Array2::from_elem((height, width), false);
+        let mut contours = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if visited[[y, x]] {
+                    continue;
+                }
+
+                let class = foreground[[y, x]];
+                let mut component = Vec::new();
+                let mut touches_border = false;
+                let mut queue = VecDeque::from([(x, y)]);
+                visited[[y, x]] = true;
+
+                while let Some((cx, cy)) = queue.pop_front() {
+                    component.push((cx, cy));
+                    touches_border |= cx == 0 || cy == 0 || cx == width - 1 || cy == height - 1;
+
+                    for (nx, ny) in neighbours(cx, cy, width, height) {
+                        if !visited[[ny, nx]] && foreground[[ny, nx]] == class {
+                            visited[[ny, nx]] = true;
+                            queue.push_back((nx, ny));
+                        }
+                    }
+                }
+
+                // Background enclosed by an object is a hole, not an object of its own.
+                if !class
+                    || touches_border
+                    || component.len() < self.config.min_object_area as usize
+                    || component.len() > max_area
+                {
+                    continue;
+                }
+
+                let mut mask = Array2::from_elem((height, width), false);
+                for &(px, py) in &component {
+                    mask[[py, px]] = true;
+                }
+
+                // Only the outer boundary occludes; the rims of holes face into the object.
+                let outside = outside_of(&mask);
+                let points = component
+                    .iter()
+                    .filter(|&&(px, py)| {
+                        neighbours(px, py, width, height).any(|(nx, ny)| outside[[ny, nx]])
+                    })
+                    .filter_map(|&(px, py)| boundary_normal(&mask, px, py))
+                    .collect::<Vec<_>>();
+
+                contours.push(OccludingContour {
+                    label: format!("object {}", contours.len() + 1),
+                    points,
+                });
+            }
+        }
+
+        contours
+    }
+
+    fn fit_object(
+        &self,
+        luminance: &Array2<f64>,
+        label: &str,
+        points: &[ContourPoint],
+    ) -> Option<ObjectLighting> {
+        let mut rows = Vec::new();
+        let mut intensities = Vec::new();
+
+        for p in points {
+            let Some(intensity) = bilinear_sample(
+                luminance,
+                p.x - self.config.inset * p.normal_x,
+                p.y - self.config.inset * p.normal_y,
+            ) else {
+                continue;
+            };
+
+            if !(1.0..=254.0).contains(&intensity) {
+                continue;
+            }
+
+            let (nx, ny) = (p.normal_x, -p.normal_y);
+            rows.push([1.0, nx, ny, nx * nx - ny * ny, nx * ny]);
+            intensities.push(intensity);
+        }
+
+        if rows.len() < self.config.min_contour_points.max(SH_TERMS + 1) {
+            return None;
+        }
+
+        let mut normal = vec![vec![0.0; SH_TERMS]; SH_TERMS];
+        let mut rhs = vec![0.0; SH_TERMS];
+        for (row, intensity) in rows.iter().zip(&intensities) {
+            for i in 0..SH_TERMS {
+                for j in 0..SH_TERMS {
+                    normal[i][j] += row[i] * row[j];
+                }
+                rhs[i] += row[i] * intensity;
+            }
+        }
+
+        let penalty = self.config.regularization * rows.len() as f64;
+        for (i, row) in normal.iter_mut().enumerate().skip(3) {
+            row[i] += penalty;
+        }
+        for (i, row) in normal.iter_mut().enumerate() {
+            row[i] += 1e-9;
+        }
+
+        let solution = solve_linear_system(normal.clone(), rhs)?;
+        let coefficients: [f64; SH_TERMS] = std::array::from_fn(|i| solution[i]);
+
+        let sse = rows
+            .iter()
+            .zip(&intensities)
+            .map(|(row, intensity)| {
+                let predicted = row
+                    .iter()
+                    .zip(&coefficients)
+                    .map(|(a, c)| a * c)
+                    .sum::<f64>();
+                (intensity - predicted).powi(2)
+            })
+            .sum::<f64>();
+        let variance = sse / (rows.len() - SH_TERMS) as f64;
+
+        let (lx, ly) = (coefficients[1], coefficients[2]);
+        let strength_sq = lx * lx + ly * ly;
+        if strength_sq < 1e-12 {
+            return None;
+        }
+
+        // Azimuth variance from the covariance of the first-order terms.
+        let gradient = [-ly / strength_sq, lx / strength_sq];
+        let mut azimuth_variance = 0.0;
+        for (k, g) in gradient.iter().enumerate() {
+            let mut unit = vec![0.0; SH_TERMS];
+            unit[k + 1] = 1.0;
+            let column = solve_linear_system(normal.clone(), unit)?;
+            azimuth_variance += g * (gradient[0] * column[1] + gradient[1] * column[2]);
+        }
+        let angular_uncertainty = (variance * azimuth_variance.max(0.0)).sqrt().min(PI);
+
+        let strength = strength_sq.sqrt();
+        let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+            (lo.min(p.x), hi.max(p.x))
+        });
+        let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+            (lo.min(p.y), hi.max(p.y))
+        });
+
+        Some(ObjectLighting {
+            label: label.to_string(),
+            bounds: SRegion {
+                x: min_x.max(0.0) as u32,
+                y: min_y.max(0.0) as u32,
+                width: (max_x - min_x).ceil() as u32 + 1,
+                height: (max_y - min_y).ceil() as u32 + 1,
+            },
+            direction: (lx / strength, ly / strength, 0.0),
+            azimuth: ly.atan2(lx),
+            angular_uncertainty,
+            coefficients,
+            residual: (sse / rows.len() as f64).sqrt(),
+            samples: rows.len(),
+            inconsistent: false,
+        })
+    }
+
+    /// Compares every object with the weighted circular median azimuth of all other
+    /// reliable objects and returns the azimuth deviations.
+    fn flag_inconsistent(&self, objects: &mut [ObjectLighting]) -> Vec<f64> {
+        let mut deviations = Vec::new();
+
+        for i in 0..objects.len() {
+            let others = objects
+                .iter()
+                .enumerate()
+                .filter(|&(j, o)| j != i && o.angular_uncertainty <= self.config.max_uncertainty)
+                .map(|(_, o)| (o.azimuth, 1.0 / o.angular_uncertainty.max(1e-3).powi(2)))
+                .collect::<Vec<_>>();
+
+            let Some(reference) = others
+                .iter()
+                .map(|&(candidate, _)| {
+                    let cost = others
+                        .iter()
+                        .map(|&(azimuth, weight)| weight * angle_difference(candidate, azimuth))
+                        .sum::<f64>();
+                    (candidate, cost)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(candidate, _)| candidate)
+            else {
+                continue;
+            };
+            let reference_uncertainty = others.iter().map(|o| o.1).sum::<f64>().recip().sqrt();
+
+            let object = &mut objects[i];
+            let deviation = angle_difference(object.azimuth, reference);
+            let allowed = self.config.angle_tolerance.max(
+                self.config.uncertainty_factor
+                    * object.angular_uncertainty.hypot(reference_uncertainty),
+            );
+
+            object.inconsistent = deviation > allowed;
+            deviations.push(deviation);
+        }
+
+        deviations
+    }
+
+    /// Weighted mean azimuth of the reliable objects that were not flagged.
+    fn scene_azimuth(&self, objects: &[ObjectLighting]) -> Option<f64> {
+        let (mut sin_sum, mut cos_sum, mut count) = (0.0, 0.0, 0);
+
+        for object in objects
+            .iter()
+            .filter(|o| !o.inconsistent && o.angular_uncertainty <= self.config.max_uncertainty)
+        {
+            let weight = 1.0 / object.angular_uncertainty.max(1e-3).powi(2);
+            sin_sum += weight * object.azimuth.sin();
+            cos_sum += weight * object.azimuth.cos();
+            count += 1;
+        }
+
+        (count > 0).then(|| sin_sum.atan2(cos_sum))
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        objects: &[ObjectLighting],
+        scene_azimuth: Option<f64>,
+    ) -> RgbImage {
+        let mut vis = original.clone();
+
+        for object in objects {
+            let color = if object.inconsistent {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 255, 0])
+            };
+
+            let b = &object.bounds;
+            draw_rectangle(&mut vis, b, color);
+
+            let center = (
+                b.x as f64 + b.width as f64 / 2.0,
+                b.y as f64 + b.height as f64 / 2.0,
+            );
+            let length = (b.width.min(b.height) as f64 / 2.0).max(10.0);
+            let tip = (
+                center.0 + length * object.azimuth.cos(),
+                center.1 - length * object.azimuth.sin(),
+            );
+            self.draw_line(&mut vis, center, tip, color);
+        }
+
+        if let Some(azimuth) = scene_azimuth {
+            let (width, height) = vis.dimensions();
+            let center = (width as f64 / 2.0, height as f64 / 2.0);
+            let length = width.min(height) as f64 / 4.0;
+            let tip = (
+                center.0 + length * azimuth.cos(),
+                center.1 - length * azimuth.sin(),
+            );
+            self.draw_line(&mut vis, center, tip, Rgb([255, 255, 0]));
+        }
+
+        vis
+    }
+
+    fn draw_line(&self, image: &mut RgbImage, from: (f64, f64), to: (f64, f64), color: Rgb<u8>) {
+        let (width, height) = image.dimensions();
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil() as usize;
+
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f64 / steps as f64
+            };
+            let x = (from.0 + t * (to.0 - from.0)).round();
+            let y = (from.1 + t * (to.1 - from.1)).round();
+
+            if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+                image.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+/// Pixels outside `mask` that connect to the image border without crossing it.
+fn outside_of(mask: &Array2<bool>) -> Array2<bool> {
+    let (height, width) = mask.dim();
+    let mut outside = Array2::from_elem((height, width), false);
+    let mut queue = VecDeque::new();
+
+    for ((y, x), &inside) in mask.indexed_iter() {
+        if !inside && (x == 0 || y == 0 || x == width - 1 || y == height - 1) {
+            outside[[y, x]] = true;
+            queue.push_back((x, y));
+        }
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        for (nx, ny) in neighbours(x, y, width, height) {
+            if !mask[[ny, nx]] && !outside[[ny, nx]] {
+                outside[[ny, nx]] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    outside
+}
+
+fn neighbours(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    [(-1isize, 0isize), (1, 0), (0, -1), (0, 1)]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let nx = x.checked_add_signed(dx)?;
+            let ny = y.checked_add_signed(dy)?;
+            (nx < width && ny < height).then_some((nx, ny))
+        })
+}
+
+/// Outward normal of a mask boundary pixel, opposite to the centroid of the mask pixels in
+/// its 5x5 neighbourhood.
+fn boundary_normal(mask: &Array2<bool>, x: usize, y: usize) -> Option<ContourPoint> {
+    let (height, width) = mask.dim();
+    let (mut sx, mut sy) = (0.0, 0.0);
+
+    for dy in -2isize..=2 {
+        for dx in -2isize..=2 {
+            let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+                continue;
+            };
+            if nx < width && ny < height && mask[[ny, nx]] {
+                sx += dx as f64;
+                sy += dy as f64;
+            }
+        }
+    }
+
+    let length = sx.hypot(sy);
+    (length > 1e-9).then(|| ContourPoint {
+        x: x as f64,
+        y: y as f64,
+        normal_x: -sx / length,
+        normal_y: -sy / length,
+    })
+}
+
+fn angle_difference(a: f64, b: f64) -> f64 {
+    let diff = (a - b).rem_euclid(2.0 * PI);
+    diff.min(2.0 * PI - diff)
+}
+
+impl Default for LightingAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,327 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+};
+
+#[derive(Debug, Clone)]
+pub struct MedianFilterConfig {
+    pub block_size: u32,
+    /// Blocks with a smaller luminance standard deviation are too flat to judge.
+    pub min_block_std: f64,
+    /// Streaking ratio and residual ratio at which the evidence is balanced.
+    pub streaking_midpoint: f64,
+    pub residual_midpoint: f64,
+    /// Logistic scales of the two features.
+    pub streaking_scale: f64,
+    pub residual_scale: f64,
+    /// Blocks above this probability are reported as filtered.
+    pub block_threshold: f64,
+    pub min_region_blocks: usize,
+}
+
+impl Default for MedianFilterConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 64,
+            min_block_std: 4.0,
+            streaking_midpoint: 1.6,
+            residual_midpoint: 0.45,
+            streaking_scale: 0.3,
+            residual_scale: 0.06,
+            block_threshold: 0.5,
+            min_region_blocks: 2,
+        }
+    }
+}
+
+/// Features of one area, summed over the three color channels.
+#[derive(Debug, Clone, Copy)]
+pub struct MedianFilterFeatures {
+    /// Number of zero first-order differences over the number of differences of one; median
+    /// filtering flattens short runs into streaks of equal values.
+    pub streaking_ratio: f64,
+    /// Mean absolute median-filter residual over the mean absolute residual against a 3x3
+    /// mean. A median filtered image is close to a root of the filter, so re-filtering
+    /// barely changes it.
+    pub residual_ratio: f64,
+    pub probability: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MedianFilterResult {
+    pub global: MedianFilterFeatures,
+    pub block_size: u32,
+    /// Probability per block, `None` for blocks too flat to judge.
+    pub block_map: Array2<Option<f64>>,
+    pub filtered_regions: Vec<SRegion>,
+    /// Fraction of judged blocks above the threshold.
+    pub filtered_fraction: f64,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+/// Per-area sums from which the features are formed.
+#[derive(Debug, Clone, Copy, Default)]
+struct FeatureSums {
+    zero_differences: f64,
+    unit_differences: f64,
+    median_residual: f64,
+    mean_residual: f64,
+    luminance: f64,
+    luminance_square: f64,
+    pixels: f64,
+}
+
+impl FeatureSums {
+    fn add(&mut self, other: &FeatureSums) {
+        self.zero_differences += other.zero_differences;
+        self.unit_differences += other.unit_differences;
+        self.median_residual += other.median_residual;
+        self.mean_residual += other.mean_residual;
+        self.luminance += other.luminance;
+        self.luminance_square += other.luminance_square;
+        self.pixels += other.pixels;
+    }
+
+    fn std_dev(&self) -> f64 {
+        if self.pixels == 0.0 {
+            return 0.0;
+        }
+        let mean = self.luminance / self.pixels;
+        (self.luminance_square / self.pixels - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+pub struct MedianFilterAnalyzer {
+    config: MedianFilterConfig,
+}
+
+impl MedianFilterAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(MedianFilterConfig::default())
+    }
+
+    pub fn with_config(config: MedianFilterConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn analyze(&self, image: &DynamicImage) -> Result<MedianFilterResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let block_size = self.config.block_size.max(8);
+        if width < block_size || height < block_size {
+            return Err(ForensicsError::ImageTooSmall(block_size));
+        }
+
+        let rows = (height / block_size) as usize;
+        let columns = (width / block_size) as usize;
+        let block = block_size as usize;
+
+        let mut sums = Array2::<FeatureSums>::from_elem((rows, columns), FeatureSums::default());
+        for channel in 0..3 {
+            let plane = Array2::from_shape_fn((height as usize, width as usize), |(y, x)| {
+                rgb.get_pixel(x as u32, y as u32)[channel] as f64
+            });
+            self.accumulate(&plane, block, &mut sums);
+        }
+
+        let mut global = FeatureSums::default();
+        let mut block_map = Array2::<Option<f64>>::from_elem((rows, columns), None);
+        for ((row, column), block_sums) in sums.indexed_iter() {
+            // Channels were summed, so the block's spread is over all three.
+            if block_sums.std_dev() < self.config.min_block_std {
+                continue;
+            }
+            global.add(block_sums);
+            block_map[[row, column]] = Some(self.features(block_sums).probability);
+        }
+
+        if global.pixels == 0.0 {
+            return Err(ForensicsError::AnalysisFailed(
+                "Image has no textured blocks to test for median filtering".into(),
+            ));
+        }
+        let global = self.features(&global);
+
+        let judged = block_map.iter().flatten().count();
+        let filtered = block_map
+            .iter()
+            .flatten()
+            .filter(|&&p| p > self.config.block_threshold)
+            .count();
+        let filtered_fraction = filtered as f64 / judged.max(1) as f64;
+        let filtered_regions = self.find_regions(&block_map, block_size);
+
+        let local = block_map.iter().flatten().copied().fold(0.0, f64::max);
+        let manipulation_probability = if filtered_regions.is_empty() {
+            global.probability
+        } else {
+            global.probability.max(local)
+        };
+
+        let visualization = self.create_visualization(&rgb, &block_map, block);
+
+        Ok(MedianFilterResult {
+            global,
+            block_size,
+            block_map,
+            filtered_regions,
+            filtered_fraction,
+            visualization,
+            manipulation_probability,
+        })
+    }
+
+    fn accumulate(&self, plane: &Array2<f64>, block: usize, sums: &mut Array2<FeatureSums>) {
+        let (rows, columns) = sums.dim();
+        let (height, width) = plane.dim();
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let mut window = [0.0; 9];
+                let mut mean = 0.0;
+                for (k, value) in window.iter_mut().enumerate() {
+                    *value = plane[[y + k / 3 - 1, x + k % 3 - 1]];
+                    mean += *value / 9.0;
+                }
+                let (row, column) = (y / block, x / block);
+                if row >= rows || column >= columns {
+                    continue;
+                }
+                window.sort_by(|a, b| a.total_cmp(b));
+
+                let s = &mut sums[[row, column]];
+                s.median_residual += (window[4] - plane[[y, x]]).abs();
+                s.mean_residual += (mean - plane[[y, x]]).abs();
+                s.luminance += plane[[y, x]];
+                s.luminance_square += plane[[y, x]] * plane[[y, x]];
+                s.pixels += 1.0;
+
+                for (dy, dx) in [(0, 1), (1, 0)] {
+                    if y + dy >= height || x + dx >= width {
+                        continue;
+                    }
+                    match (plane[[y + dy, x + dx]] - plane[[y, x]]).abs() as u32 {
+                        0 => s.zero_differences += 1.0,
+                        1 => s.unit_differences += 1.0,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Logistic combination of the streaking and residual features.
+    fn features(&self, sums: &FeatureSums) -> MedianFilterFeatures {
+        let streaking_ratio = sums.zero_differences / sums.unit_differences.max(1.0);
+        let residual_ratio = sums.median_residual / sums.mean_residual.max(1e-9);
+
+        let score = (streaking_ratio - self.config.streaking_midpoint)
+            / self.config.streaking_scale
+            + (self.config.residual_midpoint - residual_ratio) / self.config.residual_scale;
+
+        MedianFilterFeatures {
+            streaking_ratio,
+            residual_ratio,
+            probability: 1.0 / (1.0 + (-score).exp()),
+        }
+    }
+
+    /// Connected groups of filtered blocks; a fully filtered image yields no regions.
+    fn find_regions(&self, block_map: &Array2<Option<f64>>, block_size: u32) -> Vec<SRegion> {
+        let (rows, columns) = block_map.dim();
+        let filtered = block_map.map(|p| p.is_some_and(|p| p > self.config.block_threshold));
+        let judged = block_map.iter().flatten().count();
+        let total = filtered.iter().filter(|&&f| f).count();
+        if total == judged {
+            return Vec::new();
+        }
+
+        let mut visited = Array2::from_elem((rows, columns), false);
+        let mut regions = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                if visited[[row, column]] || !filtered[[row, column]] {
+                    continue;
+                }
+                visited[[row, column]] = true;
+                let mut group = vec![(row, column)];
+                let mut next = 0;
+                while next < group.len() {
+                    let (r, c) = group[next];
+                    next += 1;
+                    for (dr, dc) in [(-1isize, 0isize), (1, 0), (0, -1), (0, 1)] {
+                        let (Some(nr), Some(nc)) =
+                            (r.checked_add_signed(dr), c.checked_add_signed(dc))
+                        else {
+                            continue;
+                        };
+                        if nr < rows && nc < columns && filtered[[nr, nc]] && !visited[[nr, nc]] {
+                            visited[[nr, nc]] = true;
+                            group.push((nr, nc));
+                        }
+                    }
+                }
+
+                if group.len() < self.config.min_region_blocks {
+                    continue;
+                }
+                let (r0, r1) = group
+                    .iter()
+                    .fold((usize::MAX, 0), |(lo, hi), &(r, _)| (lo.min(r), hi.max(r)));
+                let (c0, c1) = group
+                    .iter()
+                    .fold((usize::MAX, 0), |(lo, hi), &(_, c)| (lo.min(c), hi.max(c)));
+                regions.push(SRegion {
+                    x: c0 as u32 * block_size,
+                    y: r0 as u32 * block_size,
+                    width: (c1 - c0 + 1) as u32 * block_size,
+                    height: (r1 - r0 + 1) as u32 * block_size,
+                });
+            }
+        }
+
+        regions
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        block_map: &Array2<Option<f64>>,
+        block: usize,
+    ) -> RgbImage {
+        let mut vis = original.clone();
+        let (rows, columns) = block_map.dim();
+
+        for (x, y, pixel) in vis.enumerate_pixels_mut() {
+            let (row, column) = (y as usize / block, x as usize / block);
+            let gray = (pixel[0] as f64 + pixel[1] as f64 + pixel[2] as f64) / 3.0;
+            let tint = if row < rows && column < columns {
+                block_map[[row, column]]
+            } else {
+                None
+            };
+            *pixel = match tint {
+                Some(p) => Rgb([
+                    (0.5 * gray + 127.5 * p) as u8,
+                    (0.5 * gray) as u8,
+                    (0.5 * gray + 127.5 * (1.0 - p)) as u8,
+                ]),
+                None => Rgb([gray as u8, gray as u8, gray as u8]),
+            };
+        }
+
+        vis
+    }
+}
+
+impl Default for MedianFilterAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,31 @@
 pub mod benford_analysis;
+pub mod blur_estimation;
+pub mod camera_identification;
 pub mod cfa_analysis;
 pub mod chromatic_aberration;
+pub mod contrast_enhancement;
 pub mod copy_move;
+pub mod corneal_reflection;
 pub mod dct_analysis;
+pub mod device_clustering;
+pub mod donor_search;
 pub mod ela;
+pub mod fingerprint_store;
+pub mod illuminant_analysis;
+pub mod image_comparison;
 pub mod jpeg_analysis;
+pub mod lighting_estimation;
 pub mod luminance_gradient;
+pub mod median_filtering;
 pub mod noise;
+pub mod noise_level_function;
 pub mod pca_analysis;
+pub mod perspective_analysis;
+pub mod phylogeny;
 pub mod prnu_analysis;
+pub mod prnu_fingerprint;
+pub mod prnu_localization;
+pub mod registration;
 pub mod resampling_detection;
 pub mod shadow_analysis;
+pub mod shadow_geometry;
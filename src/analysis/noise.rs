@@ -1,14 +1,35 @@
 use image::{DynamicImage, GrayImage, Luma};
+use ndarray::Array2;
 
 use crate::{
     NoiseResult, SRegion,
-    error::Result,
-    image_utils::{gaussian_blur_3x3, rgb_to_gray},
+    error::{ForensicsError, Result},
+    image_utils::{
+        float_to_gray, gaussian_blur_3x3_f32, median, rgb_to_luminance, rgb_to_luminance_f32,
+        wavelet::{Wavelet, dwt2d},
+    },
 };
 
+const MAD_TO_SIGMA: f64 = 0.6745;
+const CALIBRATION_SIGMA: f64 = 5.0;
+
+#[derive(Debug, Clone)]
+pub struct WaveletNoiseResult {
+    /// Noise sigma in gray levels per block; NaN where the block was excluded.
+    pub sigma_map: Array2<f64>,
+    pub sigma_image: GrayImage,
+    pub global_sigma: f64,
+    pub calibration_factor: f64,
+    pub excluded_blocks: usize,
+    pub anomalous_regions: Vec<SRegion>,
+    pub inconsistency_score: f64,
+}
+
 pub struct NoiseAnalyzer {
     block_size: u32,
     sensitivity: f64,
+    wavelet: Wavelet,
+    edge_ratio: f64,
 }
 
 impl NoiseAnalyzer {
@@ -16,6 +37,8 @@ impl NoiseAnalyzer {
         Self {
             block_size: 16,
             sensitivity: 2.0,
+            wavelet: Wavelet::Daubechies8,
+            edge_ratio: 2.0,
         }
     }
 
@@ -24,82 +47,270 @@ impl NoiseAnalyzer {
         self
     }
 
+    pub fn with_sensitivity(mut self, sensitivity: f64) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn with_wavelet(mut self, wavelet: Wavelet) -> Self {
+        self.wavelet = wavelet;
+        self
+    }
+
+    pub fn with_edge_ratio(mut self, ratio: f64) -> Self {
+        self.edge_ratio = ratio;
+        self
+    }
+
     pub fn analyze(&self, image: &DynamicImage) -> Result<NoiseResult> {
-        let rgb = image.to_rgb8();
-        let gray = rgb_to_gray(&rgb);
+        let gray = rgb_to_luminance_f32(&image.to_rgb8());
 
-        let noise_map = self.extract_noise(&gray);
-        let local_variance_map = self.calculate_local_variance(&gray);
-        let estimated_noise_level = self.estimate_noise_level(&noise_map);
+        let noise_residual = self.extract_noise(&gray);
+        let local_std_map = self.calculate_local_variance(&gray);
+        let estimated_noise_level = self.estimate_noise_level(&noise_residual);
 
         let (anomalous_regions, inconsistency_score) =
-            self.find_anomlaies(&local_variance_map, estimated_noise_level);
+            self.find_anomlaies(&local_std_map, estimated_noise_level);
 
         Ok(NoiseResult {
-            noise_map,
-            local_variance_map,
+            noise_map: float_to_gray(&noise_residual.mapv(f32::abs)),
+            local_variance_map: float_to_gray(&local_std_map),
+            noise_residual,
+            local_std_map,
             inconsistency_score,
             estimated_noise_level,
             anomalous_regions,
         })
     }
 
-    fn extract_noise(&self, gray: &GrayImage) -> GrayImage {
-        let blurred = gaussian_blur_3x3(gray);
-        let (width, height) = gray.dimensions();
-        let mut noise = GrayImage::new(width, height);
+    /// Estimates sensor noise from the finest diagonal wavelet subband of each block.
+    /// Blocks dominated by edges or clipped intensities are excluded.
+    pub fn analyze_wavelet(&self, image: &DynamicImage) -> Result<WaveletNoiseResult> {
+        let luminance = rgb_to_luminance(&image.to_rgb8());
+        let (height, width) = luminance.dim();
+
+        if (width as u32) < self.block_size * 2 || (height as u32) < self.block_size * 2 {
+            return Err(ForensicsError::ImageTooSmall(self.block_size * 2));
+        }
+
+        let (raw_sigmas, excluded) = self.wavelet_block_sigmas(&luminance);
+
+        let noisy = &luminance + &self.gaussian_noise(height, width, CALIBRATION_SIGMA);
+        let (noisy_sigmas, _) = self.wavelet_block_sigmas(&noisy);
+
+        let mut gains = raw_sigmas
+            .iter()
+            .zip(noisy_sigmas.iter())
+            .zip(excluded.iter())
+            .filter(|(_, excluded)| !**excluded)
+            .map(|((&raw, &noisy), _)| {
+                (noisy * noisy - raw * raw).max(0.0).sqrt() / CALIBRATION_SIGMA
+            })
+            .collect::<Vec<_>>();
+
+        let calibration_factor = match median(&mut gains) {
+            gain if gain > 0.1 => gain,
+            _ => 1.0,
+        };
 
-        for y in 0..height {
-            for x in 0..width {
-                let orig = gray.get_pixel(x, y)[0] as i32;
-                let blur = blurred.get_pixel(x, y)[0] as i32;
-                let diff = (orig - blur).abs() as u8;
-                noise.put_pixel(x, y, Luma([diff]));
+        let sigma_map = Array2::from_shape_fn(raw_sigmas.dim(), |idx| {
+            if excluded[idx] {
+                f64::NAN
+            } else {
+                raw_sigmas[idx] / calibration_factor
+            }
+        });
+
+        let mut valid = sigma_map
+            .iter()
+            .copied()
+            .filter(|s| !s.is_nan())
+            .collect::<Vec<_>>();
+        let global_sigma = median(&mut valid);
+
+        let (anomalous_regions, inconsistency_score) =
+            self.find_sigma_anomalies(&sigma_map, global_sigma, width as u32, height as u32);
+
+        let sigma_image = self.render_sigma_map(&sigma_map, width as u32, height as u32);
+
+        Ok(WaveletNoiseResult {
+            sigma_map,
+            sigma_image,
+            global_sigma,
+            calibration_factor,
+            excluded_blocks: excluded.iter().filter(|&&e| e).count(),
+            anomalous_regions,
+            inconsistency_score,
+        })
+    }
+
+    fn wavelet_block_sigmas(&self, luminance: &Array2<f64>) -> (Array2<f64>, Array2<bool>) {
+        let (height, width) = luminance.dim();
+        let subbands = dwt2d(luminance, self.wavelet);
+        let half_block = (self.block_size / 2) as usize;
+        let block = self.block_size as usize;
+
+        let rows = height / block;
+        let cols = width / block;
+        let mut sigmas = Array2::zeros((rows, cols));
+        let mut excluded = Array2::from_elem((rows, cols), false);
+
+        for by in 0..rows {
+            for bx in 0..cols {
+                let window = ndarray::s![
+                    by * half_block..(by + 1) * half_block,
+                    bx * half_block..(bx + 1) * half_block
+                ];
+
+                let mut diagonal = subbands
+                    .diagonal
+                    .slice(window)
+                    .iter()
+                    .map(|v| v.abs())
+                    .collect::<Vec<_>>();
+                let mut horizontal = subbands
+                    .horizontal
+                    .slice(window)
+                    .iter()
+                    .map(|v| v.abs())
+                    .collect::<Vec<_>>();
+                let mut vertical = subbands
+                    .vertical
+                    .slice(window)
+                    .iter()
+                    .map(|v| v.abs())
+                    .collect::<Vec<_>>();
+
+                let diagonal_mad = median(&mut diagonal);
+                let detail_mad = (median(&mut horizontal) + median(&mut vertical)) / 2.0;
+
+                let mean = luminance
+                    .slice(ndarray::s![
+                        by * block..(by + 1) * block,
+                        bx * block..(bx + 1) * block
+                    ])
+                    .mean()
+                    .unwrap_or(0.0);
+
+                sigmas[[by, bx]] = diagonal_mad / MAD_TO_SIGMA;
+                excluded[[by, bx]] = detail_mad > self.edge_ratio * diagonal_mad.max(0.1)
+                    || !(8.0..=247.0).contains(&mean);
             }
         }
 
-        noise
+        (sigmas, excluded)
     }
 
-    fn calculate_local_variance(&self, gray: &GrayImage) -> GrayImage {
-        let (width, height) = gray.dimensions();
-        let mut variance_map = GrayImage::new(width, height);
-        let half_block = self.block_size / 2;
+    fn find_sigma_anomalies(
+        &self,
+        sigma_map: &Array2<f64>,
+        global_sigma: f64,
+        width: u32,
+        height: u32,
+    ) -> (Vec<SRegion>, f64) {
+        let mut regions = Vec::new();
+        let mut valid_blocks = 0;
 
-        for y in 0..height {
-            for x in 0..width {
-                let mut sum = 0.0;
-                let mut sum_sq = 0.0;
-                let mut count = 0;
+        for ((by, bx), &sigma) in sigma_map.indexed_iter() {
+            if sigma.is_nan() {
+                continue;
+            }
+            valid_blocks += 1;
 
-                for dy in 0..self.block_size {
-                    for dx in 0..self.block_size {
-                        let px = x.saturating_sub(half_block) + dx;
-                        let py = y.saturating_sub(half_block) + dy;
-
-                        if px < width && py < height {
-                            let val = gray.get_pixel(px, py)[0] as f64;
-                            sum += val;
-                            sum_sq += val * val;
-                            count += 1;
-                        }
-                    }
-                }
+            let ratio = sigma.max(1e-3) / global_sigma.max(1e-3);
+            if ratio > self.sensitivity || ratio < 1.0 / self.sensitivity {
+                let x = bx as u32 * self.block_size;
+                let y = by as u32 * self.block_size;
 
-                if count > 0 {
-                    let mean = sum / count as f64;
-                    let variance = (sum_sq / count as f64) - (mean * mean);
-                    let std_dev = variance.sqrt().min(255.0);
-                    variance_map.put_pixel(x, y, Luma([std_dev as u8]));
-                }
+                regions.push(SRegion {
+                    x,
+                    y,
+                    width: self.block_size.min(width - x),
+                    height: self.block_size.min(height - y),
+                });
             }
         }
 
-        variance_map
+        let inconsistency_score = if valid_blocks > 0 {
+            regions.len() as f64 / valid_blocks as f64
+        } else {
+            0.0
+        };
+
+        (regions, inconsistency_score)
+    }
+
+    fn render_sigma_map(&self, sigma_map: &Array2<f64>, width: u32, height: u32) -> GrayImage {
+        let max_sigma = sigma_map
+            .iter()
+            .filter(|s| !s.is_nan())
+            .fold(0.0, |acc: f64, &s| acc.max(s));
+
+        GrayImage::from_fn(width, height, |x, y| {
+            let by = ((y / self.block_size) as usize).min(sigma_map.nrows().saturating_sub(1));
+            let bx = ((x / self.block_size) as usize).min(sigma_map.ncols().saturating_sub(1));
+            let sigma = sigma_map[[by, bx]];
+
+            if sigma.is_nan() || max_sigma <= 0.0 {
+                Luma([0])
+            } else {
+                Luma([(sigma / max_sigma * 255.0) as u8])
+            }
+        })
+    }
+
+    fn gaussian_noise(&self, rows: usize, cols: usize, sigma: f64) -> Array2<f64> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next_uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            ((state >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+        };
+
+        Array2::from_shape_fn((rows, cols), |_| {
+            let u1 = next_uniform();
+            let u2 = next_uniform();
+            sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+        })
+    }
+
+    fn extract_noise(&self, gray: &Array2<f32>) -> Array2<f32> {
+        gray - &gaussian_blur_3x3_f32(gray)
+    }
+
+    fn calculate_local_variance(&self, gray: &Array2<f32>) -> Array2<f32> {
+        let (height, width) = gray.dim();
+        let block_size = self.block_size as usize;
+        let half_block = block_size / 2;
+
+        Array2::from_shape_fn((height, width), |(y, x)| {
+            let mut sum = 0.0;
+            let mut sum_sq = 0.0;
+            let mut count = 0;
+
+            for dy in 0..block_size {
+                for dx in 0..block_size {
+                    let px = x.saturating_sub(half_block) + dx;
+                    let py = y.saturating_sub(half_block) + dy;
+
+                    if px < width && py < height {
+                        let val = gray[[py, px]] as f64;
+                        sum += val;
+                        sum_sq += val * val;
+                        count += 1;
+                    }
+                }
+            }
+
+            let mean = sum / count as f64;
+            let variance = (sum_sq / count as f64) - (mean * mean);
+            variance.max(0.0).sqrt() as f32
+        })
     }
 
-    fn estimate_noise_level(&self, noise_map: &GrayImage) -> f64 {
-        let mut values = noise_map.pixels().map(|p| p[0] as f64).collect::<Vec<_>>();
+    fn estimate_noise_level(&self, noise: &Array2<f32>) -> f64 {
+        let mut values = noise.iter().map(|v| v.abs() as f64).collect::<Vec<_>>();
 
         values.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
@@ -124,8 +335,8 @@ impl NoiseAnalyzer {
         mad * 1.4826
     }
 
-    fn find_anomlaies(&self, variance_map: &GrayImage, global_noise: f64) -> (Vec<SRegion>, f64) {
-        let (width, height) = variance_map.dimensions();
+    fn find_anomlaies(&self, std_map: &Array2<f32>, global_noise: f64) -> (Vec<SRegion>, f64) {
+        let (width, height) = (std_map.ncols() as u32, std_map.nrows() as u32);
         let mut regions = Vec::new();
         let mut anomaly_count = 0;
         let mut total_blocks = 0;
@@ -140,7 +351,7 @@ impl NoiseAnalyzer {
 
                 for y in by..(by + self.block_size).min(height) {
                     for x in bx..(bx + self.block_size).min(width) {
-                        block_sum += variance_map.get_pixel(x, y)[0] as f64;
+                        block_sum += std_map[[y as usize, x as usize]] as f64;
                         count += 1;
                     }
                 }
@@ -0,0 +1,347 @@
+use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    error::{ForensicsError, Result},
+    image_utils::{
+        median, merge_regions,
+        wavelet::{Wavelet, dwt2d},
+    },
+};
+
+const MAD_TO_SIGMA: f64 = 0.6745;
+
+#[derive(Debug, Clone)]
+pub struct NlfConfig {
+    pub patch_size: u32,
+    pub intensity_bins: usize,
+    pub edge_ratio: f64,
+    pub envelope_percentile: f64,
+    pub min_patches_per_bin: usize,
+    pub deviation_threshold: f64,
+    pub clipping_fraction: f64,
+    pub wavelet: Wavelet,
+}
+
+impl Default for NlfConfig {
+    fn default() -> Self {
+        Self {
+            patch_size: 16,
+            intensity_bins: 16,
+            edge_ratio: 1.6,
+            envelope_percentile: 0.3,
+            min_patches_per_bin: 3,
+            deviation_threshold: 1.8,
+            clipping_fraction: 0.05,
+            wavelet: Wavelet::Daubechies8,
+        }
+    }
+}
+
+/// Noise sigma (gray levels) as a function of intensity for one color channel.
+#[derive(Debug, Clone)]
+pub struct NoiseLevelFunction {
+    pub channel: usize,
+    pub intensities: Vec<f64>,
+    pub sigmas: Vec<f64>,
+    pub samples: Vec<usize>,
+    /// Fit of `sigma^2 = poisson_gain * intensity + gaussian_variance`.
+    pub poisson_gain: f64,
+    pub gaussian_variance: f64,
+}
+
+impl NoiseLevelFunction {
+    pub fn sigma_at(&self, intensity: f64) -> f64 {
+        if self.intensities.is_empty() {
+            return 0.0;
+        }
+
+        let last = self.intensities.len() - 1;
+        if intensity <= self.intensities[0] {
+            return self.sigmas[0];
+        }
+        if intensity >= self.intensities[last] {
+            return self.sigmas[last];
+        }
+
+        let upper = self
+            .intensities
+            .iter()
+            .position(|&i| i >= intensity)
+            .unwrap_or(last);
+        let lower = upper.saturating_sub(1);
+        let span = self.intensities[upper] - self.intensities[lower];
+
+        if span <= 0.0 {
+            return self.sigmas[upper];
+        }
+
+        let t = (intensity - self.intensities[lower]) / span;
+        self.sigmas[lower] * (1.0 - t) + self.sigmas[upper] * t
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NlfResult {
+    pub curves: Vec<NoiseLevelFunction>,
+    /// Geometric mean over channels of measured / expected sigma per patch;
+    /// NaN where the patch was not homogeneous enough to measure.
+    pub deviation_ratios: Array2<f64>,
+    pub deviation_map: GrayImage,
+    pub anomalous_regions: Vec<SRegion>,
+    pub inconsistency_score: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PatchMeasurement {
+    mean: f64,
+    sigma: f64,
+    homogeneous: bool,
+}
+
+pub struct NoiseLevelAnalyzer {
+    config: NlfConfig,
+}
+
+impl NoiseLevelAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(NlfConfig::default())
+    }
+
+    pub fn with_config(config: NlfConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn analyze(&self, image: &DynamicImage) -> Result<NlfResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+
+        if width < self.config.patch_size * 2 || height < self.config.patch_size * 2 {
+            return Err(ForensicsError::ImageTooSmall(self.config.patch_size * 2));
+        }
+
+        let measurements = (0..3)
+            .map(|c| self.measure_patches(&rgb, c))
+            .collect::<Vec<_>>();
+
+        let curves = measurements
+            .iter()
+            .enumerate()
+            .map(|(c, patches)| self.fit_curve(c, patches))
+            .collect::<Vec<_>>();
+
+        let deviation_ratios = self.deviation_ratios(&measurements, &curves);
+        let (anomalous_regions, inconsistency_score) =
+            self.find_anomalies(&deviation_ratios, width, height);
+        let deviation_map = self.render_deviation(&deviation_ratios, width, height);
+
+        Ok(NlfResult {
+            curves,
+            deviation_ratios,
+            deviation_map,
+            anomalous_regions,
+            inconsistency_score,
+        })
+    }
+
+    fn measure_patches(&self, rgb: &RgbImage, channel: usize) -> Array2<PatchMeasurement> {
+        let (width, height) = rgb.dimensions();
+        let patch = self.config.patch_size as usize;
+        let half = patch / 2;
+
+        let plane = Array2::from_shape_fn((height as usize, width as usize), |(y, x)| {
+            rgb.get_pixel(x as u32, y as u32)[channel] as f64
+        });
+        let subbands = dwt2d(&plane, self.config.wavelet);
+
+        let rows = height as usize / patch;
+        let cols = width as usize / patch;
+
+        Array2::from_shape_fn((rows, cols), |(py, px)| {
+            let pixels = plane.slice(ndarray::s![
+                py * patch..(py + 1) * patch,
+                px * patch..(px + 1) * patch
+            ]);
+            let mean = pixels.mean().unwrap_or(0.0);
+            let clipped = pixels.iter().filter(|&&v| v <= 0.0 || v >= 255.0).count() as f64
+                / pixels.len() as f64;
+
+            let window = ndarray::s![py * half..(py + 1) * half, px * half..(px + 1) * half];
+            let mut diagonal = subbands
+                .diagonal
+                .slice(window)
+                .iter()
+                .map(|v| v.abs())
+                .collect::<Vec<_>>();
+            let mut detail = subbands
+                .horizontal
+                .slice(window)
+                .iter()
+                .chain(subbands.vertical.slice(window).iter())
+                .map(|v| v.abs())
+                .collect::<Vec<_>>();
+
+            let diagonal_mad = median(&mut diagonal);
+            let detail_mad = median(&mut detail);
+
+            PatchMeasurement {
+                mean,
+                sigma: diagonal_mad / MAD_TO_SIGMA,
+                homogeneous: clipped <= self.config.clipping_fraction
+                    && detail_mad <= self.config.edge_ratio * diagonal_mad.max(0.1),
+            }
+        })
+    }
+
+    /// Lower envelope of homogeneous patch sigmas per intensity bin, so residual texture
+    /// does not inflate the curve.
+    fn fit_curve(&self, channel: usize, patches: &Array2<PatchMeasurement>) -> NoiseLevelFunction {
+        let bins = self.config.intensity_bins;
+        let bin_width = 256.0 / bins as f64;
+        let mut binned = vec![Vec::new(); bins];
+
+        for patch in patches.iter().filter(|p| p.homogeneous) {
+            let bin = ((patch.mean / bin_width) as usize).min(bins - 1);
+            binned[bin].push(patch.sigma);
+        }
+
+        let mut intensities = Vec::new();
+        let mut sigmas = Vec::new();
+        let mut samples = Vec::new();
+
+        for (bin, values) in binned.iter_mut().enumerate() {
+            if values.len() < self.config.min_patches_per_bin {
+                continue;
+            }
+
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let idx =
+                ((values.len() - 1) as f64 * self.config.envelope_percentile).round() as usize;
+
+            intensities.push((bin as f64 + 0.5) * bin_width);
+            sigmas.push(values[idx]);
+            samples.push(values.len());
+        }
+
+        let (poisson_gain, gaussian_variance) = self.fit_poisson_gaussian(&intensities, &sigmas);
+
+        NoiseLevelFunction {
+            channel,
+            intensities,
+            sigmas,
+            samples,
+            poisson_gain,
+            gaussian_variance,
+        }
+    }
+
+    fn fit_poisson_gaussian(&self, intensities: &[f64], sigmas: &[f64]) -> (f64, f64) {
+        let n = intensities.len() as f64;
+        if intensities.len() < 2 {
+            let variance = sigmas.first().map(|s| s * s).unwrap_or(0.0);
+            return (0.0, variance);
+        }
+
+        let variances = sigmas.iter().map(|s| s * s).collect::<Vec<_>>();
+        let mean_i = intensities.iter().sum::<f64>() / n;
+        let mean_v = variances.iter().sum::<f64>() / n;
+
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (&i, &v) in intensities.iter().zip(&variances) {
+            cov += (i - mean_i) * (v - mean_v);
+            var += (i - mean_i).powi(2);
+        }
+
+        let gain = if var > 1e-9 {
+            (cov / var).max(0.0)
+        } else {
+            0.0
+        };
+        (gain, (mean_v - gain * mean_i).max(0.0))
+    }
+
+    fn deviation_ratios(
+        &self,
+        measurements: &[Array2<PatchMeasurement>],
+        curves: &[NoiseLevelFunction],
+    ) -> Array2<f64> {
+        Array2::from_shape_fn(measurements[0].dim(), |idx| {
+            let mut log_sum = 0.0;
+            let mut count = 0;
+
+            for (patches, curve) in measurements.iter().zip(curves) {
+                let patch = patches[idx];
+                let expected = curve.sigma_at(patch.mean);
+
+                if patch.homogeneous && expected > 0.0 {
+                    log_sum += (patch.sigma.max(1e-3) / expected).ln();
+                    count += 1;
+                }
+            }
+
+            if count > 0 {
+                (log_sum / count as f64).exp()
+            } else {
+                f64::NAN
+            }
+        })
+    }
+
+    fn find_anomalies(&self, ratios: &Array2<f64>, width: u32, height: u32) -> (Vec<SRegion>, f64) {
+        let patch = self.config.patch_size;
+        let threshold = self.config.deviation_threshold;
+        let mut regions = Vec::new();
+        let mut measured = 0;
+
+        for ((py, px), &ratio) in ratios.indexed_iter() {
+            if ratio.is_nan() {
+                continue;
+            }
+            measured += 1;
+
+            if ratio > threshold || ratio < 1.0 / threshold {
+                let x = px as u32 * patch;
+                let y = py as u32 * patch;
+
+                regions.push(SRegion {
+                    x,
+                    y,
+                    width: patch.min(width - x),
+                    height: patch.min(height - y),
+                });
+            }
+        }
+
+        let score = if measured > 0 {
+            regions.len() as f64 / measured as f64
+        } else {
+            0.0
+        };
+
+        (merge_regions(regions), score)
+    }
+
+    fn render_deviation(&self, ratios: &Array2<f64>, width: u32, height: u32) -> GrayImage {
+        let patch = self.config.patch_size;
+        let max_log = self.config.deviation_threshold.ln() * 2.0;
+
+        GrayImage::from_fn(width, height, |x, y| {
+            let py = ((y / patch) as usize).min(ratios.nrows().saturating_sub(1));
+            let px = ((x / patch) as usize).min(ratios.ncols().saturating_sub(1));
+            let ratio = ratios[[py, px]];
+
+            if ratio.is_nan() {
+                Luma([0])
+            } else {
+                Luma([(ratio.ln().abs() / max_log * 255.0).min(255.0) as u8])
+            }
+        })
+    }
+}
+
+impl Default for NoiseLevelAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
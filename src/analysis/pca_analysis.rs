@@ -1,6 +1,7 @@
 use image::{DynamicImage, GrayImage, Luma};
+use ndarray::Array2;
 
-use crate::{SRegion, error::Result, image_utils::rgb_to_gray};
+use crate::{SRegion, error::Result, image_utils::rgb_to_luminance_f32};
 
 #[derive(Debug, Clone)]
 pub struct PcaConfig {
@@ -27,6 +28,11 @@ impl Default for PcaConfig {
 
 #[derive(Debug, Clone)]
 pub struct PcaAnalysisResult {
+    /// Per-pixel z-score of the patch reconstruction error; NaN where no patch applies.
+    pub anomaly_scores: Array2<f32>,
+    /// Mean projection on the first three principal components; NaN where no patch applies.
+    pub component_maps: Vec<Array2<f32>>,
+    /// 8-bit renderings of the maps above for display.
     pub anomaly_map: GrayImage,
     pub pc1_map: GrayImage,
     pub pc2_map: GrayImage,
@@ -51,9 +57,8 @@ impl PcaAnalyzer {
     }
 
     pub fn analyze(&self, image: &DynamicImage) -> Result<PcaAnalysisResult> {
-        let rgb = image.to_rgb8();
-        let gray = rgb_to_gray(&rgb);
-        let (width, height) = gray.dimensions();
+        let gray = rgb_to_luminance_f32(&image.to_rgb8());
+        let (width, height) = (gray.ncols() as u32, gray.nrows() as u32);
 
         if width < self.config.block_size * 2 || height < self.config.block_size * 2 {
             return Err(crate::error::ForensicsError::ImageTooSmall(
@@ -85,9 +90,12 @@ impl PcaAnalyzer {
 
         let projections = self.project_patches(&patches, &prinicpal_components, &mean);
 
-        let pc1_map = self.create_component_map(width, height, &patch_positions, &projections, 0);
-        let pc2_map = self.create_component_map(width, height, &patch_positions, &projections, 1);
-        let pc3_map = self.create_component_map(width, height, &patch_positions, &projections, 2);
+        let component_maps = (0..3)
+            .map(|idx| self.component_values(width, height, &patch_positions, &projections, idx))
+            .collect::<Vec<_>>();
+        let pc1_map = self.render_component_map(&component_maps[0]);
+        let pc2_map = self.render_component_map(&component_maps[1]);
+        let pc3_map = self.render_component_map(&component_maps[2]);
 
         let reconstruction_errors = self.compute_reconstruction_errors(
             &patches,
@@ -96,11 +104,11 @@ impl PcaAnalyzer {
             &projections,
         );
 
-        let anomaly_map =
-            self.create_anomaly_map(width, height, &patch_positions, &reconstruction_errors);
+        let anomaly_scores =
+            self.anomaly_scores(width, height, &patch_positions, &reconstruction_errors);
+        let anomaly_map = self.render_anomaly_map(&anomaly_scores);
 
-        let anomalous_regions =
-            self.find_anomalous_regions(&anomaly_map, &reconstruction_errors, &patch_positions);
+        let anomalous_regions = self.find_anomalous_regions(&anomaly_scores);
 
         let overall_anomaly_score = self.calculate_overall_anomaly_score(&reconstruction_errors);
         let manipulation_probability = self.calculate_manipulation_probability(
@@ -111,6 +119,8 @@ impl PcaAnalyzer {
         );
 
         Ok(PcaAnalysisResult {
+            anomaly_scores,
+            component_maps,
             anomaly_map,
             pc1_map,
             pc2_map,
@@ -122,8 +132,8 @@ impl PcaAnalyzer {
         })
     }
 
-    fn extract_patches(&self, gray: &GrayImage) -> (Vec<Vec<f64>>, Vec<(u32, u32)>) {
-        let (width, height) = gray.dimensions();
+    fn extract_patches(&self, gray: &Array2<f32>) -> (Vec<Vec<f64>>, Vec<(u32, u32)>) {
+        let (width, height) = (gray.ncols() as u32, gray.nrows() as u32);
         let patch_size = self.config.patch_size;
         let stride = self.config.patch_stride;
 
@@ -141,13 +151,13 @@ impl PcaAnalyzer {
         (patches, positions)
     }
 
-    fn extract_single_patch(&self, gray: &GrayImage, x: u32, y: u32) -> Vec<f64> {
+    fn extract_single_patch(&self, gray: &Array2<f32>, x: u32, y: u32) -> Vec<f64> {
         let patch_size = self.config.patch_size;
         let mut patch = Vec::with_capacity((patch_size * patch_size) as usize);
 
         for dy in 0..patch_size {
             for dx in 0..patch_size {
-                let pixel = gray.get_pixel(x + dx, y + dy)[0] as f64;
+                let pixel = gray[[(y + dy) as usize, (x + dx) as usize]] as f64;
                 patch.push(pixel);
             }
         }
@@ -341,43 +351,37 @@ impl PcaAnalyzer {
             .collect()
     }
 
-    fn create_component_map(
+    fn component_values(
         &self,
         width: u32,
         height: u32,
         positions: &[(u32, u32)],
         projections: &[Vec<f64>],
         component_idx: usize,
-    ) -> GrayImage {
-        let mut map = GrayImage::new(width, height);
-        let mut value_map = vec![vec![Vec::new(); width as usize]; height as usize];
-        let patch_size = self.config.patch_size;
+    ) -> Array2<f32> {
+        let values = positions
+            .iter()
+            .zip(projections)
+            .map(|(&position, projection)| {
+                (
+                    position,
+                    projection.get(component_idx).copied().unwrap_or(f64::NAN),
+                )
+            })
+            .collect::<Vec<_>>();
 
-        for (i, &(x, y)) in positions.iter().enumerate() {
-            if component_idx < projections[i].len() {
-                let value = projections[i][component_idx];
+        self.average_over_patches(width, height, &values)
+    }
 
-                for dy in 0..patch_size {
-                    for dx in 0..patch_size {
-                        let px = (x + dx) as usize;
-                        let py = (y + dy) as usize;
-                        if px < width as usize && py < height as usize {
-                            value_map[py][px].push(value);
-                        }
-                    }
-                }
-            }
-        }
+    fn render_component_map(&self, values: &Array2<f32>) -> GrayImage {
+        let (height, width) = values.dim();
+        let mut map = GrayImage::new(width as u32, height as u32);
 
-        let mut all_values = Vec::new();
-        for row in &value_map {
-            for cell in row {
-                if !cell.is_empty() {
-                    let avg = cell.iter().sum::<f64>() / cell.len() as f64;
-                    all_values.push(avg);
-                }
-            }
-        }
+        let mut all_values = values
+            .iter()
+            .filter(|v| !v.is_nan())
+            .map(|&v| v as f64)
+            .collect::<Vec<_>>();
 
         if all_values.is_empty() {
             return map;
@@ -388,84 +392,104 @@ impl PcaAnalyzer {
         let max_val = all_values[all_values.len() * 19 / 20];
         let range = (max_val - min_val).max(1e-10);
 
-        for y in 0..height {
-            for x in 0..width {
-                let cell = &value_map[y as usize][x as usize];
-                if !cell.is_empty() {
-                    let avg = cell.iter().sum::<f64>() / cell.len() as f64;
-                    let normalized = ((avg - min_val) / range).clamp(0.0, 1.0);
-                    map.put_pixel(x, y, Luma([(normalized * 255.0) as u8]));
-                }
+        for ((y, x), &value) in values.indexed_iter() {
+            if !value.is_nan() {
+                let normalized = ((value as f64 - min_val) / range).clamp(0.0, 1.0);
+                map.put_pixel(x as u32, y as u32, Luma([(normalized * 255.0) as u8]));
             }
         }
 
         map
     }
 
-    fn create_anomaly_map(
+    fn anomaly_scores(
         &self,
         width: u32,
         height: u32,
         positions: &[(u32, u32)],
         errors: &[f64],
-    ) -> GrayImage {
-        let mut map = GrayImage::new(width, height);
-        let mut error_map = vec![vec![Vec::new(); width as usize]; height as usize];
+    ) -> Array2<f32> {
+        let mean_error = errors.iter().sum::<f64>() / errors.len() as f64;
+        let variance =
+            errors.iter().map(|e| (e - mean_error).powi(2)).sum::<f64>() / errors.len() as f64;
+        let std_dev = variance.sqrt();
+
+        let z_scores = positions
+            .iter()
+            .zip(errors)
+            .map(|(&position, &error)| {
+                let z = if std_dev > 0.0 {
+                    (error - mean_error) / std_dev
+                } else {
+                    0.0
+                };
+                (position, z)
+            })
+            .collect::<Vec<_>>();
+
+        self.average_over_patches(width, height, &z_scores)
+    }
+
+    /// Maps z-scores to `[0, 255]` with 0 at mid-gray; uncovered pixels stay black.
+    fn anomaly_display_value(z_score: f32) -> f64 {
+        if z_score.is_nan() {
+            0.0
+        } else {
+            (z_score as f64 / 5.0 + 0.5).clamp(0.0, 1.0) * 255.0
+        }
+    }
+
+    fn render_anomaly_map(&self, scores: &Array2<f32>) -> GrayImage {
+        let (height, width) = scores.dim();
+
+        GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            Luma([Self::anomaly_display_value(scores[[y as usize, x as usize]]) as u8])
+        })
+    }
+
+    /// Averages per-patch values over every pixel each patch covers.
+    fn average_over_patches(
+        &self,
+        width: u32,
+        height: u32,
+        values: &[((u32, u32), f64)],
+    ) -> Array2<f32> {
+        let shape = (height as usize, width as usize);
+        let mut sum = Array2::<f64>::zeros(shape);
+        let mut count = Array2::<f64>::zeros(shape);
         let patch_size = self.config.patch_size;
 
-        for (i, &(x, y)) in positions.iter().enumerate() {
+        for &((x, y), value) in values {
+            if value.is_nan() {
+                continue;
+            }
+
             for dy in 0..patch_size {
                 for dx in 0..patch_size {
                     let px = (x + dx) as usize;
                     let py = (y + dy) as usize;
                     if px < width as usize && py < height as usize {
-                        error_map[py][px].push(errors[i]);
+                        sum[[py, px]] += value;
+                        count[[py, px]] += 1.0;
                     }
                 }
             }
         }
 
-        let mean_error = errors.iter().sum::<f64>() / errors.len() as f64;
-        let variance =
-            errors.iter().map(|e| (e - mean_error).powi(2)).sum::<f64>() / errors.len() as f64;
-        let std_dev = variance.sqrt();
-
-        for y in 0..height {
-            for x in 0..width {
-                let cell = &error_map[y as usize][x as usize];
-                if !cell.is_empty() {
-                    let avg = cell.iter().sum::<f64>() / cell.len() as f64;
-
-                    let z_score = if std_dev > 0.0 {
-                        (avg - mean_error) / std_dev
-                    } else {
-                        0.0
-                    };
-
-                    let normalized = (z_score / 5.0 + 0.5).clamp(0.0, 1.0);
-                    map.put_pixel(x, y, Luma([(normalized * 255.0) as u8]));
-                }
+        Array2::from_shape_fn(shape, |idx| {
+            if count[idx] > 0.0 {
+                (sum[idx] / count[idx]) as f32
+            } else {
+                f32::NAN
             }
-        }
-
-        map
+        })
     }
 
-    fn find_anomalous_regions(
-        &self,
-        anomaly_map: &GrayImage,
-        errors: &[f64],
-        postions: &[(u32, u32)],
-    ) -> Vec<SRegion> {
-        let (width, height) = anomaly_map.dimensions();
+    /// Blocks whose mean z-score, over the pixels covered by patches, exceeds the threshold.
+    fn find_anomalous_regions(&self, scores: &Array2<f32>) -> Vec<SRegion> {
+        let (width, height) = (scores.ncols() as u32, scores.nrows() as u32);
         let block_size = self.config.block_size;
 
-        let mean_error = errors.iter().sum::<f64>() / errors.len() as f64;
-        let variance =
-            errors.iter().map(|e| (e - mean_error).powi(2)).sum::<f64>() / errors.len() as f64;
-        let std_dev = variance.sqrt();
-        let threshold = mean_error + self.config.anomaly_threshold * std_dev;
-
         let mut regions = Vec::new();
 
         for by in (0..height).step_by(block_size as usize) {
@@ -478,14 +502,20 @@ impl PcaAnalyzer {
 
                 for y in by..(by + block_h) {
                     for x in bx..(bx + block_w) {
-                        block_sum += anomaly_map.get_pixel(x, y)[0] as f64;
-                        count += 1;
+                        let z_score = scores[[y as usize, x as usize]];
+                        if !z_score.is_nan() {
+                            block_sum += z_score as f64;
+                            count += 1;
+                        }
                     }
                 }
 
+                if count == 0 {
+                    continue;
+                }
                 let block_avg = block_sum / count as f64;
 
-                if block_avg > 128.0 + self.config.anomaly_threshold * 30.0 {
+                if block_avg > self.config.anomaly_threshold {
                     regions.push(SRegion {
                         x: bx,
                         y: by,
@@ -0,0 +1,948 @@
+use std::f64::consts::PI;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    error::Result,
+    image_utils::{
+        draw_rectangle, gaussian_blur_3x3_f32, rgb_to_luminance_f32, solve_linear_system,
+    },
+};
+
+/// Number of longest segments whose pairwise intersections are tried as vanishing points.
+const HYPOTHESIS_SEGMENTS: usize = 80;
+
+#[derive(Debug, Clone)]
+pub struct PerspectiveConfig {
+    pub gradient_threshold: f64,
+    /// Largest difference, in radians, between a pixel's level-line angle and its region's.
+    pub alignment_tolerance: f64,
+    /// Smallest fraction of a segment's bounding rectangle covered by aligned pixels.
+    pub min_density: f64,
+    pub min_segment_length: f64,
+    pub max_vanishing_points: usize,
+    pub min_vanishing_support: usize,
+    /// Largest angle, in radians on the Gaussian sphere, between a segment's line and a
+    /// vanishing point it supports.
+    pub inlier_tolerance: f64,
+    /// Largest angle, in radians, between a vanishing direction and the image vertical for
+    /// it to count as the vertical vanishing point.
+    pub vertical_angle: f64,
+    /// Tolerance, in radians, for marked verticals and ground planes.
+    pub marker_tolerance: f64,
+    /// Near-vertical segments deviating from the vertical vanishing point by more than
+    /// `marker_tolerance` but less than this are reported.
+    pub vertical_search_angle: f64,
+    /// Largest relative deviation of an object's implied camera height from the median.
+    pub height_tolerance: f64,
+}
+
+impl Default for PerspectiveConfig {
+    fn default() -> Self {
+        Self {
+            gradient_threshold: 20.0,
+            alignment_tolerance: 22.5f64.to_radians(),
+            min_density: 0.5,
+            min_segment_length: 30.0,
+            max_vanishing_points: 3,
+            min_vanishing_support: 4,
+            inlier_tolerance: 1.0f64.to_radians(),
+            vertical_angle: 30f64.to_radians(),
+            marker_tolerance: 2.0f64.to_radians(),
+            vertical_search_angle: 10f64.to_radians(),
+            height_tolerance: 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LineSegment {
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+    pub length: f64,
+    /// Index into `PerspectiveResult::vanishing_points` of the point the segment supports.
+    pub vanishing_point: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VanishingKind {
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VanishingPoint {
+    /// Image position, `None` when the point is at infinity.
+    pub position: Option<(f64, f64)>,
+    /// Unit image direction toward the point from the image center.
+    pub direction: (f64, f64),
+    pub kind: VanishingKind,
+    pub support: usize,
+    /// Total length of the supporting segments.
+    pub strength: f64,
+}
+
+/// An object standing on the ground plane, marked by its base and top.
+#[derive(Debug, Clone)]
+pub struct ObjectMarker {
+    pub label: String,
+    pub base: (f64, f64),
+    pub top: (f64, f64),
+    /// Real height in any unit shared by all markers, e.g. 1.7 for an adult in metres.
+    pub reference_height: Option<f64>,
+}
+
+/// Image segment given by its two end points.
+pub type MarkedLine = ((f64, f64), (f64, f64));
+
+/// Segments lying on one planar surface whose world directions are parallel in pairs,
+/// e.g. the two pairs of edges of a floor tile.
+#[derive(Debug, Clone)]
+pub struct GroundMarker {
+    pub label: String,
+    pub parallel_pairs: Vec<[MarkedLine; 2]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerspectiveInconsistency {
+    /// A vertical edge does not point at the vertical vanishing point.
+    VerticalMisaligned,
+    /// The object's height relative to the horizon disagrees with the other objects.
+    HeightMismatch,
+    /// A marked plane has a vanishing line other than the horizon.
+    GroundPlaneMismatch,
+}
+
+#[derive(Debug, Clone)]
+pub struct PerspectiveFinding {
+    pub region: SRegion,
+    pub label: String,
+    pub kind: PerspectiveInconsistency,
+    /// Angular deviation in radians, or relative height deviation for height mismatches.
+    pub deviation: f64,
+    pub explanation: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectHeight {
+    pub label: String,
+    /// Object height divided by camera height, from the cross-ratio with the horizon and
+    /// the vertical vanishing point.
+    pub height_ratio: f64,
+    pub implied_camera_height: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PerspectiveResult {
+    pub segments: Vec<LineSegment>,
+    pub vanishing_points: Vec<VanishingPoint>,
+    /// Horizon as `(a, b, c)` with `a * x + b * y + c = 0` and `a^2 + b^2 = 1`.
+    pub horizon: Option<(f64, f64, f64)>,
+    pub object_heights: Vec<ObjectHeight>,
+    pub findings: Vec<PerspectiveFinding>,
+    pub inconsistent_regions: Vec<SRegion>,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+/// Maps image points and lines to the Gaussian sphere of a camera with its principal point
+/// at the image center and a focal length of one image diagonal.
+#[derive(Debug, Clone, Copy)]
+struct Sphere {
+    cx: f64,
+    cy: f64,
+    focal: f64,
+}
+
+impl Sphere {
+    fn point(&self, (x, y): (f64, f64)) -> [f64; 3] {
+        normalize([x - self.cx, y - self.cy, self.focal])
+    }
+
+    fn line(&self, a: (f64, f64), b: (f64, f64)) -> [f64; 3] {
+        normalize(cross(self.point(a), self.point(b)))
+    }
+
+    /// Image position of a direction, `None` at infinity.
+    fn image_point(&self, v: [f64; 3]) -> Option<(f64, f64)> {
+        (v[2].abs() > 1e-9).then(|| {
+            (
+                self.cx + self.focal * v[0] / v[2],
+                self.cy + self.focal * v[1] / v[2],
+            )
+        })
+    }
+
+    /// Image line `(a, b, c)` of a great circle with normal `n`.
+    fn image_line(&self, n: [f64; 3]) -> Option<(f64, f64, f64)> {
+        let norm = n[0].hypot(n[1]);
+        (norm > 1e-9).then(|| {
+            let c = n[2] * self.focal - n[0] * self.cx - n[1] * self.cy;
+            (n[0] / norm, n[1] / norm, c / norm)
+        })
+    }
+}
+
+pub struct PerspectiveAnalyzer {
+    config: PerspectiveConfig,
+}
+
+impl PerspectiveAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(PerspectiveConfig::default())
+    }
+
+    pub fn with_config(config: PerspectiveConfig) -> Self {
+        Self { config }
+    }
+
+    /// Runs the automatic checks only.
+    pub fn analyze(&self, image: &DynamicImage) -> Result<PerspectiveResult> {
+        self.analyze_with_markers(image, &[], &[])
+    }
+
+    pub fn analyze_with_markers(
+        &self,
+        image: &DynamicImage,
+        objects: &[ObjectMarker],
+        grounds: &[GroundMarker],
+    ) -> Result<PerspectiveResult> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let sphere = Sphere {
+            cx: width as f64 / 2.0,
+            cy: height as f64 / 2.0,
+            focal: (width as f64).hypot(height as f64),
+        };
+
+        let mut segments = self.detect_segments(&rgb);
+        let (vanishing_points, directions) = self.find_vanishing_points(&mut segments, &sphere);
+
+        let vertical = vanishing_points
+            .iter()
+            .position(|vp| vp.kind == VanishingKind::Vertical)
+            .map(|i| directions[i]);
+        let horizon_normal = self.horizon_normal(&vanishing_points, &directions);
+        let horizon = horizon_normal.and_then(|n| sphere.image_line(n));
+
+        let mut findings = Vec::new();
+        let mut checks = 0;
+
+        if let Some(vertical) = vertical {
+            let (auto_checks, auto_findings) =
+                self.check_segment_verticals(&segments, &sphere, vertical);
+            checks += auto_checks;
+            findings.extend(auto_findings);
+
+            for object in objects {
+                checks += 1;
+                let deviation = point_line_angle(vertical, sphere.line(object.base, object.top));
+                if deviation > self.config.marker_tolerance {
+                    findings.push(PerspectiveFinding {
+                        region: segment_region(object.base, object.top, 10.0),
+                        label: object.label.clone(),
+                        kind: PerspectiveInconsistency::VerticalMisaligned,
+                        deviation,
+                        explanation: format!(
+                            "Vertical axis of {} misses the vertical vanishing point by {:.1} deg",
+                            object.label,
+                            deviation.to_degrees()
+                        ),
+                    });
+                }
+            }
+        }
+
+        let object_heights = match horizon {
+            Some(horizon) => self.object_heights(objects, horizon, vertical, &sphere),
+            None => Vec::new(),
+        };
+        let height_findings = self.check_heights(objects, &object_heights);
+        checks += object_heights
+            .iter()
+            .filter(|h| h.implied_camera_height.is_some())
+            .count();
+        findings.extend(height_findings);
+
+        if let Some(horizon_normal) = horizon_normal {
+            for ground in grounds {
+                checks += 1;
+                if let Some(finding) = self.check_ground(ground, horizon_normal, &sphere) {
+                    findings.push(finding);
+                }
+            }
+        }
+
+        let inconsistent_regions = findings.iter().map(|f| f.region).collect::<Vec<_>>();
+        let manipulation_probability = if checks > 0 {
+            (findings.len() as f64 / checks as f64).min(1.0)
+        } else {
+            0.0
+        };
+
+        let visualization = self.create_visualization(&rgb, &segments, horizon, objects, &findings);
+
+        Ok(PerspectiveResult {
+            segments,
+            vanishing_points,
+            horizon,
+            object_heights,
+            findings,
+            inconsistent_regions,
+            visualization,
+            manipulation_probability,
+        })
+    }
+
+    /// LSD-style detection: regions of pixels with similar level-line angle grown from the
+    /// strongest gradients, kept when their fitted rectangle is long and dense enough.
+    fn detect_segments(&self, rgb: &RgbImage) -> Vec<LineSegment> {
+        let luminance = gaussian_blur_3x3_f32(&rgb_to_luminance_f32(rgb));
+        let (height, width) = luminance.dim();
+        if width < 3 || height < 3 {
+            return Vec::new();
+        }
+
+        let mut magnitude = Array2::<f64>::zeros((height, width));
+        let mut angle = Array2::<f64>::zeros((height, width));
+        for y in 0..height - 1 {
+            for x in 0..width - 1 {
+                let (a, b) = (luminance[[y, x]] as f64, luminance[[y, x + 1]] as f64);
+                let (c, d) = (
+                    luminance[[y + 1, x]] as f64,
+                    luminance[[y + 1, x + 1]] as f64,
+                );
+                let gx = (b + d - a - c) / 2.0;
+                let gy = (c + d - a - b) / 2.0;
+                magnitude[[y, x]] = gx.hypot(gy);
+                angle[[y, x]] = gx.atan2(-gy);
+            }
+        }
+
+        let threshold = self.config.gradient_threshold;
+        let mut seeds = magnitude
+            .indexed_iter()
+            .filter(|(_, m)| **m > threshold)
+            .map(|((y, x), &m)| (m, x, y))
+            .collect::<Vec<_>>();
+        seeds.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut used = Array2::from_elem((height, width), false);
+        let mut segments = Vec::new();
+
+        for (_, sx, sy) in seeds {
+            if used[[sy, sx]] {
+                continue;
+            }
+
+            // Orientations are compared modulo pi so an edge keeps growing where the
+            // background behind an object flips the contrast polarity.
+            used[[sy, sx]] = true;
+            let mut region = vec![(sx, sy)];
+            let (mut sum_cos, mut sum_sin) =
+                ((2.0 * angle[[sy, sx]]).cos(), (2.0 * angle[[sy, sx]]).sin());
+            let mut region_angle = angle[[sy, sx]];
+            let mut next = 0;
+
+            while next < region.len() {
+                let (x, y) = region[next];
+                next += 1;
+
+                for dy in -1isize..=1 {
+                    for dx in -1isize..=1 {
+                        let (Some(nx), Some(ny)) =
+                            (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                        else {
+                            continue;
+                        };
+                        if nx >= width
+                            || ny >= height
+                            || used[[ny, nx]]
+                            || magnitude[[ny, nx]] <= threshold
+                            || angle_difference(angle[[ny, nx]], region_angle)
+                                > self.config.alignment_tolerance
+                        {
+                            continue;
+                        }
+
+                        used[[ny, nx]] = true;
+                        region.push((nx, ny));
+                        sum_cos += (2.0 * angle[[ny, nx]]).cos();
+                        sum_sin += (2.0 * angle[[ny, nx]]).sin();
+                        region_angle = 0.5 * sum_sin.atan2(sum_cos);
+                    }
+                }
+            }
+
+            // Like LSD, shrink regions that bend into neighbouring edges around their seed
+            // until the rectangle is dense enough. A region down to its seed cannot shrink
+            // any further.
+            let distance =
+                |&(x, y): &(usize, usize)| (x as f64 - sx as f64).hypot(y as f64 - sy as f64);
+            while (region.len() as f64) >= self.config.min_segment_length / 2.0 {
+                if let Some(segment) = self.fit_segment(&region, &magnitude) {
+                    segments.push(segment);
+                    break;
+                }
+                let radius = 0.75 * region.iter().map(distance).fold(0.0, f64::max);
+                let before = region.len();
+                region.retain(|pixel| distance(pixel) <= radius);
+                if region.len() == before {
+                    break;
+                }
+            }
+        }
+
+        segments
+    }
+
+    fn fit_segment(
+        &self,
+        region: &[(usize, usize)],
+        magnitude: &Array2<f64>,
+    ) -> Option<LineSegment> {
+        let total = region.iter().map(|&(x, y)| magnitude[[y, x]]).sum::<f64>();
+        let cx = region
+            .iter()
+            .map(|&(x, y)| x as f64 * magnitude[[y, x]])
+            .sum::<f64>()
+            / total;
+        let cy = region
+            .iter()
+            .map(|&(x, y)| y as f64 * magnitude[[y, x]])
+            .sum::<f64>()
+            / total;
+
+        let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+        for &(x, y) in region {
+            let w = magnitude[[y, x]];
+            let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+            sxx += w * dx * dx;
+            syy += w * dy * dy;
+            sxy += w * dx * dy;
+        }
+
+        let theta = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+        let (dir_x, dir_y) = (theta.cos(), theta.sin());
+
+        let (mut l_min, mut l_max, mut w_min, mut w_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+        for &(x, y) in region {
+            let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+            let along = dx * dir_x + dy * dir_y;
+            let across = -dx * dir_y + dy * dir_x;
+            l_min = l_min.min(along);
+            l_max = l_max.max(along);
+            w_min = w_min.min(across);
+            w_max = w_max.max(across);
+        }
+
+        let length = l_max - l_min + 1.0;
+        let width = w_max - w_min + 1.0;
+        if length < self.config.min_segment_length
+            || (region.len() as f64) < self.config.min_density * length * width
+        {
+            return None;
+        }
+
+        // Gradients are taken between pixels, so the edge sits half a pixel further on.
+        let (ox, oy) = (cx + 0.5, cy + 0.5);
+        Some(LineSegment {
+            start: (ox + l_min * dir_x, oy + l_min * dir_y),
+            end: (ox + l_max * dir_x, oy + l_max * dir_y),
+            length: length - 1.0,
+            vanishing_point: None,
+        })
+    }
+
+    /// Sequentially picks the intersection of two long segments supported by the largest
+    /// total segment length, refines it over its inliers and removes them.
+    fn find_vanishing_points(
+        &self,
+        segments: &mut [LineSegment],
+        sphere: &Sphere,
+    ) -> (Vec<VanishingPoint>, Vec<[f64; 3]>) {
+        let lines = segments
+            .iter()
+            .map(|s| sphere.line(s.start, s.end))
+            .collect::<Vec<_>>();
+        let tolerance = self.config.inlier_tolerance.sin();
+
+        let mut points = Vec::new();
+        let mut directions = Vec::new();
+
+        while points.len() < self.config.max_vanishing_points {
+            let mut candidates = (0..segments.len())
+                .filter(|&i| segments[i].vanishing_point.is_none())
+                .collect::<Vec<_>>();
+            if candidates.len() < self.config.min_vanishing_support {
+                break;
+            }
+            candidates.sort_by(|&a, &b| segments[b].length.total_cmp(&segments[a].length));
+            let hypotheses = &candidates[..candidates.len().min(HYPOTHESIS_SEGMENTS)];
+
+            let support = |v: [f64; 3]| {
+                candidates
+                    .iter()
+                    .filter(|&&k| dot(lines[k], v).abs() < tolerance)
+                    .map(|&k| segments[k].length)
+                    .sum::<f64>()
+            };
+
+            let mut best: Option<([f64; 3], f64)> = None;
+            for (n, &i) in hypotheses.iter().enumerate() {
+                for &j in &hypotheses[n + 1..] {
+                    let v = cross(lines[i], lines[j]);
+                    if norm(v) < 1e-9 {
+                        continue;
+                    }
+                    let v = normalize(v);
+                    let score = support(v);
+                    if best.is_none_or(|(_, s)| score > s) {
+                        best = Some((v, score));
+                    }
+                }
+            }
+
+            let Some((mut v, _)) = best else {
+                break;
+            };
+
+            let mut inliers = Vec::new();
+            for _ in 0..3 {
+                inliers = candidates
+                    .iter()
+                    .copied()
+                    .filter(|&k| dot(lines[k], v).abs() < tolerance)
+                    .collect::<Vec<_>>();
+                v = refine_vanishing_point(&inliers, &lines, segments, v);
+            }
+
+            if inliers.len() < self.config.min_vanishing_support {
+                break;
+            }
+
+            let index = points.len();
+            for &k in &inliers {
+                segments[k].vanishing_point = Some(index);
+            }
+
+            let kind = if v[1].abs() > self.config.vertical_angle.cos() {
+                VanishingKind::Vertical
+            } else {
+                VanishingKind::Horizontal
+            };
+            let direction_norm = v[0].hypot(v[1]).max(1e-12);
+            let sign = if v[2] < 0.0 { -1.0 } else { 1.0 };
+
+            points.push(VanishingPoint {
+                position: sphere.image_point(v),
+                direction: (sign * v[0] / direction_norm, sign * v[1] / direction_norm),
+                kind,
+                support: inliers.len(),
+                strength: inliers.iter().map(|&k| segments[k].length).sum(),
+            });
+            directions.push(v);
+        }
+
+        // Only the strongest vertical candidate keeps that role.
+        if let Some(strongest) = points
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.kind == VanishingKind::Vertical)
+            .max_by(|a, b| a.1.strength.total_cmp(&b.1.strength))
+            .map(|(i, _)| i)
+        {
+            for (i, point) in points.iter_mut().enumerate() {
+                if i != strongest && point.kind == VanishingKind::Vertical {
+                    point.kind = VanishingKind::Horizontal;
+                }
+            }
+        }
+
+        (points, directions)
+    }
+
+    /// Great-circle normal of the horizon: through the two strongest horizontal vanishing
+    /// points, or orthogonal to the vertical one when only that is known.
+    fn horizon_normal(
+        &self,
+        points: &[VanishingPoint],
+        directions: &[[f64; 3]],
+    ) -> Option<[f64; 3]> {
+        let mut horizontal = points
+            .iter()
+            .zip(directions)
+            .filter(|(p, _)| p.kind == VanishingKind::Horizontal)
+            .collect::<Vec<_>>();
+        horizontal.sort_by(|a, b| b.0.strength.total_cmp(&a.0.strength));
+
+        if horizontal.len() >= 2 {
+            let n = cross(*horizontal[0].1, *horizontal[1].1);
+            if norm(n) > 1e-6 {
+                return Some(normalize(n));
+            }
+        }
+
+        points
+            .iter()
+            .zip(directions)
+            .find(|(p, _)| p.kind == VanishingKind::Vertical)
+            .map(|(_, &v)| v)
+    }
+
+    /// Flags long near-vertical segments that do not converge to the vertical vanishing
+    /// point, returning the number of segments checked and the findings.
+    fn check_segment_verticals(
+        &self,
+        segments: &[LineSegment],
+        sphere: &Sphere,
+        vertical: [f64; 3],
+    ) -> (usize, Vec<PerspectiveFinding>) {
+        let mut checked = 0;
+        let mut findings = Vec::new();
+
+        for segment in segments {
+            if segment.length < 2.0 * self.config.min_segment_length {
+                continue;
+            }
+
+            let deviation = point_line_angle(vertical, sphere.line(segment.start, segment.end));
+            if deviation > self.config.vertical_search_angle {
+                continue;
+            }
+
+            checked += 1;
+            if deviation > self.config.marker_tolerance {
+                findings.push(PerspectiveFinding {
+                    region: segment_region(segment.start, segment.end, 5.0),
+                    label: "edge".to_string(),
+                    kind: PerspectiveInconsistency::VerticalMisaligned,
+                    deviation,
+                    explanation: format!(
+                        "Near-vertical edge of {:.0}px misses the vertical vanishing point by {:.1} deg",
+                        segment.length,
+                        deviation.to_degrees()
+                    ),
+                });
+            }
+        }
+
+        (checked, findings)
+    }
+
+    fn object_heights(
+        &self,
+        objects: &[ObjectMarker],
+        horizon: (f64, f64, f64),
+        vertical: Option<[f64; 3]>,
+        sphere: &Sphere,
+    ) -> Vec<ObjectHeight> {
+        let vertical_point = vertical.and_then(|v| sphere.image_point(v));
+
+        objects
+            .iter()
+            .filter_map(|object| {
+                let (bx, by) = object.base;
+                let (tx, ty) = object.top;
+                let length = (tx - bx).hypot(ty - by);
+                if length < 1e-6 {
+                    return None;
+                }
+                let (ux, uy) = ((tx - bx) / length, (ty - by) / length);
+
+                // Intersection of the object axis with the horizon, as a position along it.
+                let (a, b, c) = horizon;
+                let denominator = a * ux + b * uy;
+                if denominator.abs() < 1e-9 {
+                    return None;
+                }
+                let horizon_at = -(a * bx + b * by + c) / denominator;
+                if horizon_at.abs() < 1e-9 {
+                    return None;
+                }
+
+                let mut height_ratio = length / horizon_at;
+                if let Some((vx, vy)) = vertical_point {
+                    let vanishing_at = (vx - bx) * ux + (vy - by) * uy;
+                    let far = vanishing_at - length;
+                    if far.abs() > 1e-9 {
+                        height_ratio *= (vanishing_at - horizon_at) / far;
+                    }
+                }
+
+                Some(ObjectHeight {
+                    label: object.label.clone(),
+                    height_ratio,
+                    implied_camera_height: object
+                        .reference_height
+                        .filter(|_| height_ratio > 1e-9)
+                        .map(|h| h / height_ratio),
+                })
+            })
+            .collect()
+    }
+
+    fn check_heights(
+        &self,
+        objects: &[ObjectMarker],
+        heights: &[ObjectHeight],
+    ) -> Vec<PerspectiveFinding> {
+        let mut implied = heights
+            .iter()
+            .filter_map(|h| h.implied_camera_height)
+            .collect::<Vec<_>>();
+        if implied.len() < 3 {
+            return Vec::new();
+        }
+        implied.sort_by(|a, b| a.total_cmp(b));
+        let median = implied[implied.len() / 2];
+
+        heights
+            .iter()
+            .filter_map(|height| {
+                let camera = height.implied_camera_height?;
+                let deviation = (camera - median).abs() / median;
+                if deviation <= self.config.height_tolerance {
+                    return None;
+                }
+
+                let object = objects.iter().find(|o| o.label == height.label)?;
+                let expected = object.reference_height? / median;
+                Some(PerspectiveFinding {
+                    region: segment_region(object.base, object.top, 10.0),
+                    label: object.label.clone(),
+                    kind: PerspectiveInconsistency::HeightMismatch,
+                    deviation,
+                    explanation: format!(
+                        "{} spans {:.2} camera heights against the horizon but its reference \
+                         height implies {:.2}; the scene's camera height would have to be \
+                         {:.2} instead of {:.2}",
+                        object.label, height.height_ratio, expected, camera, median
+                    ),
+                })
+            })
+            .collect()
+    }
+
+    fn check_ground(
+        &self,
+        ground: &GroundMarker,
+        horizon_normal: [f64; 3],
+        sphere: &Sphere,
+    ) -> Option<PerspectiveFinding> {
+        let mut worst = 0.0f64;
+        let mut points = Vec::new();
+
+        for [(a0, a1), (b0, b1)] in &ground.parallel_pairs {
+            points.extend([*a0, *a1, *b0, *b1]);
+            let v = cross(sphere.line(*a0, *a1), sphere.line(*b0, *b1));
+            if norm(v) < 1e-12 {
+                continue;
+            }
+            worst = worst.max(point_line_angle(normalize(v), horizon_normal));
+        }
+
+        if worst <= self.config.marker_tolerance || points.is_empty() {
+            return None;
+        }
+
+        let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+            (lo.min(p.0), hi.max(p.0))
+        });
+        let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+            (lo.min(p.1), hi.max(p.1))
+        });
+
+        Some(PerspectiveFinding {
+            region: SRegion {
+                x: min_x.max(0.0) as u32,
+                y: min_y.max(0.0) as u32,
+                width: (max_x - min_x).max(1.0) as u32,
+                height: (max_y - min_y).max(1.0) as u32,
+            },
+            label: ground.label.clone(),
+            kind: PerspectiveInconsistency::GroundPlaneMismatch,
+            deviation: worst,
+            explanation: format!(
+                "Vanishing points of {} lie {:.1} deg off the horizon, so it is not parallel \
+                 to the ground plane",
+                ground.label,
+                worst.to_degrees()
+            ),
+        })
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        segments: &[LineSegment],
+        horizon: Option<(f64, f64, f64)>,
+        objects: &[ObjectMarker],
+        findings: &[PerspectiveFinding],
+    ) -> RgbImage {
+        const COLORS: [Rgb<u8>; 3] = [Rgb([255, 0, 0]), Rgb([0, 255, 0]), Rgb([0, 128, 255])];
+        let mut vis = original.clone();
+        let (width, height) = vis.dimensions();
+
+        for segment in segments {
+            let color = segment
+                .vanishing_point
+                .map_or(Rgb([128, 128, 128]), |i| COLORS[i % COLORS.len()]);
+            draw_line(&mut vis, segment.start, segment.end, color);
+        }
+
+        if let Some((a, b, c)) = horizon {
+            let (w, h) = (width as f64, height as f64);
+            if b.abs() > a.abs() {
+                draw_line(
+                    &mut vis,
+                    (0.0, -c / b),
+                    (w, -(c + a * w) / b),
+                    Rgb([255, 255, 0]),
+                );
+            } else {
+                draw_line(
+                    &mut vis,
+                    (-c / a, 0.0),
+                    (-(c + b * h) / a, h),
+                    Rgb([255, 255, 0]),
+                );
+            }
+        }
+
+        for object in objects {
+            draw_line(&mut vis, object.base, object.top, Rgb([255, 0, 255]));
+        }
+
+        for finding in findings {
+            draw_rectangle(&mut vis, &finding.region, Rgb([255, 0, 0]));
+        }
+
+        vis
+    }
+}
+
+/// Least-squares vanishing direction of the inlier lines, weighted by segment length: the
+/// eigenvector of the smallest eigenvalue, found by inverse iteration from `initial`.
+fn refine_vanishing_point(
+    inliers: &[usize],
+    lines: &[[f64; 3]],
+    segments: &[LineSegment],
+    initial: [f64; 3],
+) -> [f64; 3] {
+    if inliers.len() < 2 {
+        return initial;
+    }
+
+    let mut moments = [[0.0; 3]; 3];
+    for &k in inliers {
+        let (l, w) = (lines[k], segments[k].length);
+        for (r, row) in moments.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value += w * l[r] * l[c];
+            }
+        }
+    }
+
+    let shift = 1e-9 * (moments[0][0] + moments[1][1] + moments[2][2]).max(1e-12);
+    let matrix = (0..3)
+        .map(|r| {
+            (0..3)
+                .map(|c| moments[r][c] + if r == c { shift } else { 0.0 })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut v = initial;
+    for _ in 0..8 {
+        let Some(next) = solve_linear_system(matrix.clone(), v.to_vec()) else {
+            return initial;
+        };
+        let next = [next[0], next[1], next[2]];
+        if norm(next) < 1e-300 {
+            return initial;
+        }
+        v = normalize(next);
+    }
+
+    if dot(v, initial) < 0.0 {
+        [-v[0], -v[1], -v[2]]
+    } else {
+        v
+    }
+}
+
+/// Angle between a direction and a great circle.
+fn point_line_angle(point: [f64; 3], line: [f64; 3]) -> f64 {
+    (dot(point, line).abs() / (norm(point) * norm(line)).max(1e-300))
+        .min(1.0)
+        .asin()
+}
+
+fn segment_region(a: (f64, f64), b: (f64, f64), margin: f64) -> SRegion {
+    let x0 = (a.0.min(b.0) - margin).max(0.0);
+    let y0 = (a.1.min(b.1) - margin).max(0.0);
+    SRegion {
+        x: x0 as u32,
+        y: y0 as u32,
+        width: (a.0.max(b.0) + margin - x0).max(1.0) as u32,
+        height: (a.1.max(b.1) + margin - y0).max(1.0) as u32,
+    }
+}
+
+fn draw_line(image: &mut RgbImage, from: (f64, f64), to: (f64, f64), color: Rgb<u8>) {
+    let (width, height) = image.dimensions();
+    let steps = (to.0 - from.0)
+        .abs()
+        .max((to.1 - from.1).abs())
+        .min(4.0 * (width + height) as f64)
+        .ceil() as usize;
+
+    for step in 0..=steps {
+        let t = if steps == 0 {
+            0.0
+        } else {
+            step as f64 / steps as f64
+        };
+        let x = (from.0 + t * (to.0 - from.0)).round();
+        let y = (from.1 + t * (to.1 - from.1)).round();
+
+        if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+            image.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+fn angle_difference(a: f64, b: f64) -> f64 {
+    let diff = (a - b).rem_euclid(PI);
+    diff.min(PI - diff)
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let n = norm(v).max(1e-300);
+    [v[0] / n, v[1] / n, v[2] / n]
+}
+
+impl Default for PerspectiveAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,450 @@
+use std::path::PathBuf;
+
+use image::{DynamicImage, imageops::FilterType};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+use crate::{
+    SRegion,
+    analysis::{
+        jpeg_analysis::JpegAnalyzer,
+        registration::{ImageRegistrar, Registration, RegistrationConfig},
+        resampling_detection::ResamplingDetector,
+    },
+    error::{ForensicsError, Result},
+    image_utils::{gray_to_array, rgb_to_gray},
+};
+
+#[derive(Debug, Clone)]
+pub struct PhylogenyConfig {
+    pub registration: RegistrationConfig,
+    pub max_dimension: u32,
+    /// Side of the centered crop the resampling detector runs on; cropping keeps the
+    /// interpolation traces that downscaling would destroy.
+    pub resampling_crop: u32,
+    pub min_registration_confidence: f64,
+    pub min_overlap: f64,
+    pub quality_weight: f64,
+    pub upscale_weight: f64,
+    pub crop_weight: f64,
+}
+
+impl Default for PhylogenyConfig {
+    fn default() -> Self {
+        Self {
+            registration: RegistrationConfig::default(),
+            max_dimension: 512,
+            resampling_crop: 256,
+            min_registration_confidence: 0.05,
+            min_overlap: 0.25,
+            quality_weight: 1.0,
+            upscale_weight: 1.0,
+            crop_weight: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PhylogenyNode {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// `None` when the quality could not be estimated.
+    pub jpeg_quality: Option<u8>,
+    pub resampling_probability: f64,
+    pub parent: Option<usize>,
+    pub cost: f64,
+    /// Maps this image's pixel coordinates into its parent's.
+    pub transform: Option<Registration>,
+    /// Area of the parent covered by this image.
+    pub crop_box: Option<SRegion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhylogenyEdge {
+    pub parent: usize,
+    pub child: usize,
+    pub dissimilarity: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhylogenyResult {
+    pub nodes: Vec<PhylogenyNode>,
+    pub edges: Vec<PhylogenyEdge>,
+    pub root: usize,
+    /// `dissimilarity[[i, j]]` is the cost of image `j` being derived from image `i`;
+    /// infinite when the pair could not be registered.
+    pub dissimilarity: Array2<f64>,
+}
+
+impl PhylogenyResult {
+    pub fn original(&self) -> &PhylogenyNode {
+        &self.nodes[self.root]
+    }
+
+    pub fn children(&self, index: usize) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter(|e| e.parent == index)
+            .map(|e| e.child)
+            .collect()
+    }
+
+    pub fn unrelated(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|&i| i != self.root && self.nodes[i].parent.is_none())
+            .collect()
+    }
+}
+
+struct ImageProfile {
+    width: u32,
+    height: u32,
+    quality: Option<u8>,
+    resampling: f64,
+    factor: f64,
+    working: Array2<f64>,
+}
+
+struct PairEvidence {
+    dissimilarity: f64,
+    transform: Registration,
+}
+
+pub struct PhylogenyAnalyzer {
+    config: PhylogenyConfig,
+    registrar: ImageRegistrar,
+    jpeg_analyzer: JpegAnalyzer,
+    resampling_detector: ResamplingDetector,
+}
+
+impl PhylogenyAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(PhylogenyConfig::default())
+    }
+
+    pub fn with_config(config: PhylogenyConfig) -> Self {
+        Self {
+            registrar: ImageRegistrar::with_config(config.registration.clone()),
+            config,
+            jpeg_analyzer: JpegAnalyzer::new(),
+            resampling_detector: ResamplingDetector::new(),
+        }
+    }
+
+    pub fn analyze_paths(&self, paths: &[PathBuf]) -> Result<PhylogenyResult> {
+        let images = paths
+            .iter()
+            .map(|path| Ok((path.to_string_lossy().to_string(), image::open(path)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.analyze(&images)
+    }
+
+    pub fn analyze(&self, images: &[(String, DynamicImage)]) -> Result<PhylogenyResult> {
+        if images.len() < 2 {
+            return Err(ForensicsError::InvalidParameter(
+                "Phylogeny needs at least two images".into(),
+            ));
+        }
+
+        let profiles = images
+            .par_iter()
+            .map(|(_, image)| self.profile(image))
+            .collect::<Vec<_>>();
+
+        let count = images.len();
+        let pairs = (0..count)
+            .flat_map(|i| (i + 1..count).map(move |j| (i, j)))
+            .collect::<Vec<_>>();
+
+        let evidence = pairs
+            .par_iter()
+            .map(|&(i, j)| {
+                let registration = self.registrar.register(&images[i].1, &images[j].1).ok();
+
+                let forward = registration.and_then(|r| self.pair_evidence(&profiles, i, j, r));
+                let backward =
+                    registration.and_then(|r| self.pair_evidence(&profiles, j, i, r.inverse()));
+
+                ((i, j, forward), (j, i, backward))
+            })
+            .collect::<Vec<_>>();
+
+        let mut dissimilarity = Array2::from_elem((count, count), f64::INFINITY);
+        let mut transforms = vec![vec![None; count]; count];
+
+        for (a, b) in evidence {
+            for (parent, child, pair) in [a, b] {
+                if let Some(pair) = pair {
+                    dissimilarity[[parent, child]] = pair.dissimilarity;
+                    transforms[parent][child] = Some(pair.transform);
+                }
+            }
+        }
+
+        for i in 0..count {
+            dissimilarity[[i, i]] = 0.0;
+        }
+
+        let parents = self.oriented_kruskal(&dissimilarity);
+
+        let nodes = images
+            .iter()
+            .zip(&profiles)
+            .enumerate()
+            .map(|(child, ((name, _), profile))| {
+                let transform = parents[child].and_then(|p| transforms[p][child]);
+
+                PhylogenyNode {
+                    name: name.clone(),
+                    width: profile.width,
+                    height: profile.height,
+                    jpeg_quality: profile.quality,
+                    resampling_probability: profile.resampling,
+                    parent: parents[child],
+                    cost: parents[child]
+                        .map(|p| dissimilarity[[p, child]])
+                        .unwrap_or(0.0),
+                    transform,
+                    crop_box: parents[child]
+                        .zip(transform)
+                        .map(|(p, t)| self.crop_box(&t, profile, &profiles[p])),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let edges = nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(child, node)| {
+                node.parent.map(|parent| PhylogenyEdge {
+                    parent,
+                    child,
+                    dissimilarity: node.cost,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let root = self.find_root(&parents);
+
+        Ok(PhylogenyResult {
+            nodes,
+            edges,
+            root,
+            dissimilarity,
+        })
+    }
+
+    fn profile(&self, image: &DynamicImage) -> ImageProfile {
+        let (width, height) = (image.width(), image.height());
+        let quality = self.jpeg_analyzer.estimate_quality(image).ok();
+
+        let crop_width = width.min(self.config.resampling_crop);
+        let crop_height = height.min(self.config.resampling_crop);
+        let resampling = self
+            .resampling_detector
+            .detect(&image.crop_imm(
+                (width - crop_width) / 2,
+                (height - crop_height) / 2,
+                crop_width,
+                crop_height,
+            ))
+            .map(|r| r.resampling_probability)
+            .unwrap_or(0.0);
+
+        let factor = (self.config.max_dimension as f64 / width.max(height) as f64).min(1.0);
+        let gray = rgb_to_gray(&image.to_rgb8());
+        let working = if factor < 1.0 {
+            image::imageops::resize(
+                &gray,
+                ((width as f64 * factor).round() as u32).max(1),
+                ((height as f64 * factor).round() as u32).max(1),
+                FilterType::Triangle,
+            )
+        } else {
+            gray
+        };
+
+        ImageProfile {
+            width,
+            height,
+            quality,
+            resampling,
+            factor,
+            working: gray_to_array(&working),
+        }
+    }
+
+    /// Cost of `child` having been produced from `parent`, where `transform` maps child
+    /// pixels into the parent.
+    fn pair_evidence(
+        &self,
+        profiles: &[ImageProfile],
+        parent: usize,
+        child: usize,
+        transform: Registration,
+    ) -> Option<PairEvidence> {
+        if transform.confidence < self.config.min_registration_confidence {
+            return None;
+        }
+
+        let source = &profiles[parent];
+        let target = &profiles[child];
+
+        let working_transform = Registration {
+            scale: transform.scale * source.factor / target.factor,
+            translation_x: transform.translation_x * source.factor,
+            translation_y: transform.translation_y * source.factor,
+            ..transform
+        };
+
+        let (height, width) = target.working.dim();
+        let prediction = working_transform.warp(&source.working, width, height);
+
+        let (residual, coverage) = self.prediction_residual(&prediction, &target.working)?;
+        if coverage < self.config.min_overlap {
+            return None;
+        }
+
+        let crop_penalty = self.config.crop_weight * (1.0 - coverage);
+        let upscale_penalty = self.config.upscale_weight
+            * (1.0 - transform.scale).max(0.0)
+            * (1.0 - target.resampling);
+        let quality_penalty = match (source.quality, target.quality) {
+            (Some(source), Some(target)) => {
+                self.config.quality_weight * (target as f64 - source as f64).max(0.0) / 100.0
+            }
+            _ => 0.0,
+        };
+
+        Some(PairEvidence {
+            dissimilarity: residual + crop_penalty + upscale_penalty + quality_penalty,
+            transform,
+        })
+    }
+
+    /// Fits `target ~= a * prediction + b` over the covered pixels and returns the
+    /// unexplained fraction of the target's deviation along with the coverage.
+    fn prediction_residual(
+        &self,
+        prediction: &Array2<f64>,
+        target: &Array2<f64>,
+    ) -> Option<(f64, f64)> {
+        let samples = prediction
+            .iter()
+            .zip(target.iter())
+            .filter(|(p, _)| !p.is_nan())
+            .map(|(&p, &t)| (p, t))
+            .collect::<Vec<_>>();
+
+        let coverage = samples.len() as f64 / target.len() as f64;
+        if samples.len() < 64 {
+            return None;
+        }
+
+        let n = samples.len() as f64;
+        let mean_p = samples.iter().map(|s| s.0).sum::<f64>() / n;
+        let mean_t = samples.iter().map(|s| s.1).sum::<f64>() / n;
+
+        let (mut cov, mut var_p, mut var_t) = (0.0, 0.0, 0.0);
+        for &(p, t) in &samples {
+            cov += (p - mean_p) * (t - mean_t);
+            var_p += (p - mean_p).powi(2);
+            var_t += (t - mean_t).powi(2);
+        }
+
+        if var_p < 1e-9 || var_t < 1e-9 {
+            return Some((1.0, coverage));
+        }
+
+        let correlation = (cov / (var_p * var_t).sqrt()).max(0.0);
+
+        Some(((1.0 - correlation * correlation).sqrt(), coverage))
+    }
+
+    fn crop_box(
+        &self,
+        transform: &Registration,
+        child: &ImageProfile,
+        parent: &ImageProfile,
+    ) -> SRegion {
+        let (min_x, min_y, max_x, max_y) = transform.footprint(child.width, child.height);
+
+        let x = min_x.clamp(0.0, parent.width as f64);
+        let y = min_y.clamp(0.0, parent.height as f64);
+
+        SRegion {
+            x: x as u32,
+            y: y as u32,
+            width: (max_x.clamp(0.0, parent.width as f64) - x).round() as u32,
+            height: (max_y.clamp(0.0, parent.height as f64) - y).round() as u32,
+        }
+    }
+
+    /// Greedy minimum spanning arborescence: cheapest edges first, each image keeps a
+    /// single parent and no cycles are formed.
+    fn oriented_kruskal(&self, dissimilarity: &Array2<f64>) -> Vec<Option<usize>> {
+        let count = dissimilarity.nrows();
+
+        let mut candidates = dissimilarity
+            .indexed_iter()
+            .filter(|((i, j), d)| i != j && d.is_finite())
+            .map(|((i, j), &d)| (i, j, d))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+        let mut parents = vec![None; count];
+        let mut components = (0..count).collect::<Vec<_>>();
+
+        fn find(components: &mut [usize], node: usize) -> usize {
+            let mut root = node;
+            while components[root] != root {
+                root = components[root];
+            }
+            components[node] = root;
+            root
+        }
+
+        for (parent, child, _) in candidates {
+            if parents[child].is_some() {
+                continue;
+            }
+
+            let parent_root = find(&mut components, parent);
+            let child_root = find(&mut components, child);
+
+            if parent_root != child_root {
+                parents[child] = Some(parent);
+                components[child_root] = parent_root;
+            }
+        }
+
+        parents
+    }
+
+    fn find_root(&self, parents: &[Option<usize>]) -> usize {
+        let tree_size = |root: usize| {
+            (0..parents.len())
+                .filter(|&node| {
+                    let mut current = node;
+                    while let Some(parent) = parents[current] {
+                        current = parent;
+                    }
+                    current == root
+                })
+                .count()
+        };
+
+        (0..parents.len())
+            .filter(|&i| parents[i].is_none())
+            .max_by_key(|&i| tree_size(i))
+            .unwrap_or(0)
+    }
+}
+
+impl Default for PhylogenyAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,11 @@
 use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use ndarray::{Array2, s};
 
-use crate::{SRegion, error::Result, image_utils::rgb_to_gray};
+use crate::{
+    SRegion,
+    error::Result,
+    image_utils::{pattern_to_residual, residual_to_pattern, rgb_to_luminance_f32},
+};
 
 #[derive(Debug, Clone)]
 pub struct PrnuConfig {
@@ -25,6 +30,9 @@ impl Default for PrnuConfig {
 
 #[derive(Debug, Clone)]
 pub struct PrnuAnalysisResult {
+    /// Noise residual in gray levels, signed and unclamped.
+    pub prnu_residual: Array2<f32>,
+    /// `prnu_residual + 128` clamped to 8 bits; prefer `prnu_residual` for comparisons.
     pub prnu_pattern: GrayImage,
     pub correlation_map: GrayImage,
     pub inconsistent_regions: Vec<SRegion>,
@@ -66,11 +74,12 @@ impl PrnuAnalyzer {
             ));
         }
 
-        let prnu_pattern = self.extract_prnu(&rgb)?;
+        let prnu_residual = self.extract_prnu(&rgb);
+        let prnu_pattern = residual_to_pattern(&prnu_residual);
 
-        let prnu_statistics = self.calculate_prnu_statistics(&prnu_pattern);
+        let prnu_statistics = self.calculate_prnu_statistics(&prnu_residual);
 
-        let (correlation_map, block_correlations) = self.analyze_local_consistency(&prnu_pattern);
+        let (correlation_map, block_correlations) = self.analyze_local_consistency(&prnu_residual);
 
         let inconsistent_regions =
             self.find_incosistent_regions(&correlation_map, &block_correlations);
@@ -80,6 +89,7 @@ impl PrnuAnalyzer {
         let manipulation_probability = 1.0 - consistency_score;
 
         Ok(PrnuAnalysisResult {
+            prnu_residual,
             prnu_pattern,
             correlation_map,
             inconsistent_regions,
@@ -90,34 +100,16 @@ impl PrnuAnalyzer {
         })
     }
 
-    fn extract_prnu(&self, rgb: &RgbImage) -> Result<GrayImage> {
-        let (width, height) = rgb.dimensions();
-
-        let gray = rgb_to_gray(rgb);
-
+    fn extract_prnu(&self, rgb: &RgbImage) -> Array2<f32> {
+        let gray = rgb_to_luminance_f32(rgb);
         let denoised = self.denoise_image(&gray);
 
-        let mut prnu = GrayImage::new(width, height);
+        let noise = &gray - &denoised;
 
-        for y in 0..height {
-            for x in 0..width {
-                let original = gray.get_pixel(x, y)[0] as f64;
-                let clean = denoised.get_pixel(x, y)[0] as f64;
-
-                let noise = original - clean;
-
-                let normalized = (noise + 128.0).clamp(0.0, 255.0) as u8;
-                prnu.put_pixel(x, y, Luma([normalized]));
-            }
-        }
-
-        let enhanced = self.weiner_filter(&prnu, &gray);
-
-        Ok(enhanced)
+        self.weiner_filter(&noise, &gray)
     }
 
-    fn denoise_image(&self, gray: &GrayImage) -> GrayImage {
-        let (width, height) = gray.dimensions();
+    fn denoise_image(&self, gray: &Array2<f32>) -> Array2<f32> {
         let mut result = gray.clone();
 
         for _ in 0..self.config.wavelet_levels {
@@ -127,96 +119,83 @@ impl PrnuAnalyzer {
         result
     }
 
-    fn bilateral_filter(&self, image: &GrayImage) -> GrayImage {
-        let (width, height) = image.dimensions();
-        let mut result = GrayImage::new(width, height);
+    fn bilateral_filter(&self, image: &Array2<f32>) -> Array2<f32> {
+        let (height, width) = image.dim();
         let radius = 2i32;
         let sigma_space = 2.0;
         let sigma_color = 30.0;
 
-        for y in 0..height {
-            for x in 0..width {
-                let center = image.get_pixel(x, y)[0] as f64;
-                let mut sum = 0.0;
-                let mut weight_sum = 0.0;
-
-                for dy in -radius..=radius {
-                    for dx in -radius..=radius {
-                        let nx = x as i32 + dx;
-                        let ny = y as i32 + dy;
-
-                        if nx >= 0 && nx < width as i32 && ny >= 0 && ny < height as i32 {
-                            let neighbor = image.get_pixel(nx as u32, ny as u32)[0] as f64;
-
-                            let space_weight = (-(dx * dx + dy * dy) as f64
-                                / (2.0 * sigma_space * sigma_space))
-                                .exp();
-
-                            let color_diff = center - neighbor;
-                            let color_weight = (-color_diff * color_diff
-                                / (2.0 * sigma_color * sigma_color))
-                                .exp();
-
-                            let weight = space_weight * color_weight;
-                            sum += neighbor * weight;
-                            weight_sum += weight;
-                        }
+        Array2::from_shape_fn((height, width), |(y, x)| {
+            let center = image[[y, x]] as f64;
+            let mut sum = 0.0;
+            let mut weight_sum = 0.0;
+
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let nx = x as i32 + dx;
+                    let ny = y as i32 + dy;
+
+                    if nx >= 0 && nx < width as i32 && ny >= 0 && ny < height as i32 {
+                        let neighbor = image[[ny as usize, nx as usize]] as f64;
+
+                        let space_weight =
+                            (-(dx * dx + dy * dy) as f64 / (2.0 * sigma_space * sigma_space)).exp();
+
+                        let color_diff = center - neighbor;
+                        let color_weight =
+                            (-color_diff * color_diff / (2.0 * sigma_color * sigma_color)).exp();
+
+                        let weight = space_weight * color_weight;
+                        sum += neighbor * weight;
+                        weight_sum += weight;
                     }
                 }
-
-                let filtered = if weight_sum > 0.0 {
-                    sum / weight_sum
-                } else {
-                    center
-                };
-                result.put_pixel(x, y, Luma([filtered.clamp(0.0, 250.0) as u8]));
             }
-        }
 
-        result
+            if weight_sum > 0.0 {
+                (sum / weight_sum) as f32
+            } else {
+                center as f32
+            }
+        })
     }
 
-    fn weiner_filter(&self, noise: &GrayImage, original: &GrayImage) -> GrayImage {
-        let (width, height) = noise.dimensions();
-        let mut result = GrayImage::new(width, height);
-
+    fn weiner_filter(&self, noise: &Array2<f32>, original: &Array2<f32>) -> Array2<f32> {
         let block_size = 8;
+        let noise_var = self.config.denoise_sigma * self.config.denoise_sigma;
 
-        for y in 0..height {
-            for x in 0..width {
-                let (local_mean, local_var) =
-                    self.calculate_local_stats(original, x, y, block_size);
+        Array2::from_shape_fn(noise.dim(), |(y, x)| {
+            let (_, local_var) = self.calculate_local_stats(original, x, y, block_size);
 
-                let noise_val = noise.get_pixel(x, y)[0] as f64 - 128.0;
-                let orig_val = original.get_pixel(x, y)[0] as f64;
+            let noise_val = noise[[y, x]] as f64;
+            let orig_val = original[[y, x]] as f64;
 
-                let noise_var = self.config.denoise_sigma * self.config.denoise_sigma;
-                let signal_var = (local_var - noise_var).max(0.0);
+            let signal_var = (local_var - noise_var).max(0.0);
 
-                let weiner_weight = if local_var > 0.0 {
-                    signal_var / local_var
-                } else {
-                    0.0
-                };
-
-                let intensity_weight = if orig_val > 10.0 && orig_val < 245.0 {
-                    1.0
-                } else {
-                    0.5
-                };
+            let weiner_weight = if local_var > 0.0 {
+                signal_var / local_var
+            } else {
+                0.0
+            };
 
-                let filtered = noise_val * weiner_weight * intensity_weight;
-                let normalized = (filtered + 128.0).clamp(0.0, 255.0) as u8;
+            let intensity_weight = if orig_val > 10.0 && orig_val < 245.0 {
+                1.0
+            } else {
+                0.5
+            };
 
-                result.put_pixel(x, y, Luma([normalized]));
-            }
-        }
-
-        result
+            (noise_val * weiner_weight * intensity_weight) as f32
+        })
     }
 
-    fn calculate_local_stats(&self, image: &GrayImage, cx: u32, cy: u32, size: u32) -> (f64, f64) {
-        let (width, height) = image.dimensions();
+    fn calculate_local_stats(
+        &self,
+        image: &Array2<f32>,
+        cx: usize,
+        cy: usize,
+        size: usize,
+    ) -> (f64, f64) {
+        let (height, width) = image.dim();
         let half = size / 2;
 
         let mut sum = 0.0;
@@ -229,7 +208,7 @@ impl PrnuAnalyzer {
                 let y = (cy + dy).saturating_sub(half);
 
                 if x < width && y < height {
-                    let val = image.get_pixel(x, y)[0] as f64;
+                    let val = image[[y, x]] as f64;
                     sum += val;
                     sum_sq += val * val;
                     count += 1;
@@ -247,11 +226,8 @@ impl PrnuAnalyzer {
         (mean, variance.max(0.0))
     }
 
-    fn calculate_prnu_statistics(&self, prnu: &GrayImage) -> PrnuStatistics {
-        let values = prnu
-            .pixels()
-            .map(|p| p[0] as f64 - 128.0)
-            .collect::<Vec<_>>();
+    fn calculate_prnu_statistics(&self, prnu: &Array2<f32>) -> PrnuStatistics {
+        let values = prnu.iter().map(|&v| v as f64).collect::<Vec<_>>();
 
         let n = values.len() as f64;
         let mean = values.iter().sum::<f64>() / n;
@@ -291,20 +267,19 @@ impl PrnuAnalyzer {
         }
     }
 
-    fn analyze_local_consistency(&self, prnu: &GrayImage) -> (GrayImage, Vec<f64>) {
-        let (width, height) = prnu.dimensions();
+    fn analyze_local_consistency(&self, prnu: &Array2<f32>) -> (GrayImage, Vec<f64>) {
+        let (height, width) = (prnu.nrows() as u32, prnu.ncols() as u32);
         let block_size = self.config.block_size;
 
         let mut correlation_map = GrayImage::new(width, height);
         let mut block_correlations = Vec::new();
 
-        let global_mean =
-            prnu.pixels().map(|p| p[0] as f64 - 128.0).sum::<f64>() / (width * height) as f64;
+        let global_mean = prnu.iter().map(|&v| v as f64).sum::<f64>() / (width * height) as f64;
 
         let global_var = prnu
-            .pixels()
-            .map(|p| {
-                let v = p[0] as f64 - 128.0 - global_mean;
+            .iter()
+            .map(|&v| {
+                let v = v as f64 - global_mean;
                 v * v
             })
             .sum::<f64>()
@@ -318,7 +293,7 @@ impl PrnuAnalyzer {
                 let mut block_values = Vec::new();
                 for dy in 0..block_h {
                     for dx in 0..block_w {
-                        let val = prnu.get_pixel(bx + dx, by + dy)[0] as f64 - 128.0;
+                        let val = prnu[[(by + dy) as usize, (bx + dx) as usize]] as f64;
                         block_values.push(val);
                     }
                 }
@@ -493,46 +468,47 @@ impl PrnuAnalyzer {
         (mean * (1.0 - low_ratio * 0.5)).max(0.0).min(1.0)
     }
 
+    /// Correlates two 8-bit patterns (`residual + 128`) over their common area.
     pub fn compare_patterns(&self, pattern1: &GrayImage, pattern2: &GrayImage) -> f64 {
-        let (w1, h1) = pattern1.dimensions();
-        let (w2, h2) = pattern2.dimensions();
-
-        let width = w1.min(w2);
-        let height = h1.min(h2);
+        self.compare_residuals(
+            &pattern_to_residual(pattern1),
+            &pattern_to_residual(pattern2),
+        )
+    }
 
-        let mut sum1 = 0.0;
-        let mut sum2 = 0.0;
-        let n = (width * height) as f64;
+    /// Normalized correlation of two float residuals over their common top-left area.
+    pub fn compare_residuals(&self, residual1: &Array2<f32>, residual2: &Array2<f32>) -> f64 {
+        let height = residual1.nrows().min(residual2.nrows());
+        let width = residual1.ncols().min(residual2.ncols());
 
-        for y in 0..height {
-            for x in 0..height {
-                sum1 += pattern1.get_pixel(x, y)[0] as f64 - 128.0;
-                sum2 += pattern2.get_pixel(x, y)[0] as f64 - 128.0;
-            }
+        if width == 0 || height == 0 {
+            return 0.0;
         }
 
-        let mean1 = sum1 / n;
-        let mean2 = sum2 / n;
+        let a = residual1.slice(s![..height, ..width]);
+        let b = residual2.slice(s![..height, ..width]);
+        let n = (width * height) as f64;
+
+        let mean1 = a.iter().map(|&v| v as f64).sum::<f64>() / n;
+        let mean2 = b.iter().map(|&v| v as f64).sum::<f64>() / n;
 
         let mut numerator = 0.0;
         let mut denom1 = 0.0;
         let mut denom2 = 0.0;
 
-        for y in 0..height {
-            for x in 0..width {
-                let v1 = pattern1.get_pixel(x, y)[0] as f64 - 128.0 - mean1;
-                let v2 = pattern2.get_pixel(x, y)[0] as f64 - 128.0 - mean2;
+        for (&v1, &v2) in a.iter().zip(b.iter()) {
+            let v1 = v1 as f64 - mean1;
+            let v2 = v2 as f64 - mean2;
 
-                numerator += v1 * v2;
-                denom1 += v1 * v1;
-                denom2 += v2 * v2;
-            }
+            numerator += v1 * v2;
+            denom1 += v1 * v1;
+            denom2 += v2 * v2;
         }
 
         let denom = (denom1 * denom2).sqrt();
 
         if denom > 0.0 {
-            (numerator / denom).max(-1.0).min(1.0)
+            (numerator / denom).clamp(-1.0, 1.0)
         } else {
             0.0
         }
@@ -0,0 +1,300 @@
+use std::{
+    fs,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, GrayImage};
+use ndarray::Array2;
+use num_complex::Complex;
+use rayon::prelude::*;
+
+use crate::{
+    error::{ForensicsError, Result},
+    image_utils::{
+        fft_2d, ifft_2d, pattern_to_residual, rgb_to_luminance,
+        wavelet::{Wavelet, denoise, local_wiener},
+    },
+};
+
+const FINGERPRINT_MAGIC: &[u8; 4] = b"IFPR";
+const FINGERPRINT_VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 20;
+
+#[derive(Debug, Clone)]
+pub struct PrnuFingerprintConfig {
+    pub wavelet: Wavelet,
+    pub wavelet_levels: usize,
+    pub denoise_sigma: f64,
+    pub saturation_level: f64,
+    pub min_images: usize,
+}
+
+impl Default for PrnuFingerprintConfig {
+    fn default() -> Self {
+        Self {
+            wavelet: Wavelet::Daubechies8,
+            wavelet_levels: 4,
+            denoise_sigma: 3.0,
+            saturation_level: 250.0,
+            min_images: 2,
+        }
+    }
+}
+
+/// Multiplicative sensor pattern `K` such that an image is `I ~= I0 + I0 * K + noise`.
+#[derive(Debug, Clone)]
+pub struct CameraFingerprint {
+    pub width: u32,
+    pub height: u32,
+    pub image_count: u32,
+    pub data: Array2<f32>,
+}
+
+impl CameraFingerprint {
+    /// Wraps a single residual, e.g. `PrnuAnalysisResult::prnu_residual`.
+    pub fn from_residual(residual: Array2<f32>) -> Self {
+        Self {
+            width: residual.ncols() as u32,
+            height: residual.nrows() as u32,
+            image_count: 1,
+            data: residual,
+        }
+    }
+
+    /// Wraps an 8-bit `prnu_pattern`, where 128 is zero. Clamping loses the strongest
+    /// noise, so `from_residual` is preferred when the float residual is at hand.
+    pub fn from_pattern(pattern: &GrayImage) -> Self {
+        Self::from_residual(pattern_to_residual(pattern))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+
+        writer.write_all(FINGERPRINT_MAGIC)?;
+        writer.write_all(&FINGERPRINT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        writer.write_all(&self.image_count.to_le_bytes())?;
+
+        for value in self.data.iter() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FINGERPRINT_MAGIC {
+            return Err(ForensicsError::UnsupportedFormat(
+                "Not a PRNU fingerprint file".into(),
+            ));
+        }
+
+        let version = Self::read_u32(&mut reader)?;
+        if version != FINGERPRINT_VERSION {
+            return Err(ForensicsError::UnsupportedFormat(format!(
+                "Unsupported fingerprint version {}",
+                version
+            )));
+        }
+
+        let width = Self::read_u32(&mut reader)?;
+        let height = Self::read_u32(&mut reader)?;
+        let image_count = Self::read_u32(&mut reader)?;
+
+        let data_size = width as u64 * height as u64 * 4;
+        if HEADER_SIZE + data_size != file_size {
+            return Err(ForensicsError::UnsupportedFormat(format!(
+                "Fingerprint header of {}x{} does not match the file size",
+                width, height
+            )));
+        }
+
+        let mut bytes = vec![0u8; data_size as usize];
+        reader.read_exact(&mut bytes)?;
+
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>();
+
+        let data = Array2::from_shape_vec((height as usize, width as usize), values)
+            .map_err(|e| ForensicsError::UnsupportedFormat(e.to_string()))?;
+
+        Ok(Self {
+            width,
+            height,
+            image_count,
+            data,
+        })
+    }
+
+    fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+pub struct PrnuFingerprintEstimator {
+    config: PrnuFingerprintConfig,
+}
+
+impl PrnuFingerprintEstimator {
+    pub fn new() -> Self {
+        Self::with_config(PrnuFingerprintConfig::default())
+    }
+
+    pub fn with_config(config: PrnuFingerprintConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn estimate_paths(&self, paths: &[PathBuf]) -> Result<CameraFingerprint> {
+        let images = paths
+            .iter()
+            .map(image::open)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        self.estimate(&images)
+    }
+
+    /// Maximum-likelihood estimate `K = sum(W_i * I_i) / sum(I_i^2)` over the noise
+    /// residuals `W_i`, followed by removal of non-unique artifacts.
+    pub fn estimate(&self, images: &[DynamicImage]) -> Result<CameraFingerprint> {
+        let min_images = self.config.min_images.max(1);
+        if images.len() < min_images {
+            return Err(ForensicsError::InvalidParameter(format!(
+                "At least {} images are needed for a fingerprint",
+                min_images
+            )));
+        }
+
+        let (width, height) = (images[0].width(), images[0].height());
+        if images
+            .iter()
+            .any(|img| img.width() != width || img.height() != height)
+        {
+            return Err(ForensicsError::InvalidParameter(
+                "All fingerprint images must share the same dimensions".into(),
+            ));
+        }
+
+        let shape = (height as usize, width as usize);
+        let (numerator, denominator) = images
+            .par_iter()
+            .map(|image| {
+                let intensity = rgb_to_luminance(&image.to_rgb8());
+                let residual = self.residual_from_luminance(&intensity);
+
+                let mut numerator = Array2::<f64>::zeros(shape);
+                let mut denominator = Array2::<f64>::zeros(shape);
+
+                for ((idx, &i), &w) in intensity.indexed_iter().zip(residual.iter()) {
+                    if i < self.config.saturation_level {
+                        numerator[idx] = w * i;
+                        denominator[idx] = i * i;
+                    }
+                }
+
+                (numerator, denominator)
+            })
+            .reduce(
+                || (Array2::zeros(shape), Array2::zeros(shape)),
+                |(n1, d1), (n2, d2)| (n1 + n2, d1 + d2),
+            );
+
+        let raw = Array2::from_shape_fn(shape, |idx| {
+            if denominator[idx] > 1.0 {
+                numerator[idx] / denominator[idx]
+            } else {
+                0.0
+            }
+        });
+
+        let filtered = self.wiener_in_dft(&Self::zero_mean(&raw));
+
+        Ok(CameraFingerprint {
+            width,
+            height,
+            image_count: images.len() as u32,
+            data: filtered.mapv(|v| v as f32),
+        })
+    }
+
+    /// Noise residual `W = I - F(I)` of an image's luminance, using the wavelet denoiser.
+    pub fn extract_residual(&self, image: &DynamicImage) -> Array2<f32> {
+        let intensity = rgb_to_luminance(&image.to_rgb8());
+        let residual = Self::zero_mean(&self.residual_from_luminance(&intensity));
+
+        residual.mapv(|v| v as f32)
+    }
+
+    fn residual_from_luminance(&self, intensity: &Array2<f64>) -> Array2<f64> {
+        let denoised = denoise(
+            intensity,
+            self.config.wavelet,
+            self.config.wavelet_levels,
+            self.config.denoise_sigma,
+        );
+
+        intensity - &denoised
+    }
+
+    /// Removes row and column means, which carry linear patterns shared by cameras of
+    /// the same model (readout, JPEG grid).
+    pub fn zero_mean(input: &Array2<f64>) -> Array2<f64> {
+        let mut output = input.clone();
+
+        for mut row in output.rows_mut() {
+            let mean = row.mean().unwrap_or(0.0);
+            row.mapv_inplace(|v| v - mean);
+        }
+
+        for mut col in output.columns_mut() {
+            let mean = col.mean().unwrap_or(0.0);
+            col.mapv_inplace(|v| v - mean);
+        }
+
+        output
+    }
+
+    /// Suppresses periodic peaks in the fingerprint spectrum by Wiener filtering the
+    /// DFT magnitude while keeping the phase.
+    pub fn wiener_in_dft(&self, input: &Array2<f64>) -> Array2<f64> {
+        let (rows, cols) = input.dim();
+        let n = (rows * cols) as f64;
+
+        let mean = input.mean().unwrap_or(0.0);
+        let variance = input.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+        let spectrum = fft_2d(&input.mapv(|v| Complex::new(v, 0.0)));
+        let magnitude = spectrum.mapv(|c| c.norm() / n.sqrt());
+
+        let flattened = &magnitude - &local_wiener(&magnitude, variance);
+
+        let filtered = Array2::from_shape_fn((rows, cols), |idx| {
+            let m = magnitude[idx];
+            if m > 0.0 {
+                spectrum[idx] * (flattened[idx] / m)
+            } else {
+                Complex::new(0.0, 0.0)
+            }
+        });
+
+        ifft_2d(&filtered).mapv(|c| c.re)
+    }
+}
+
+impl Default for PrnuFingerprintEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
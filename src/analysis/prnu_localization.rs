@@ -0,0 +1,545 @@
+use image::{DynamicImage, GrayImage, Luma};
+use ndarray::Array2;
+
+use crate::{
+    SRegion,
+    analysis::prnu_fingerprint::{
+        CameraFingerprint, PrnuFingerprintConfig, PrnuFingerprintEstimator,
+    },
+    error::{ForensicsError, Result},
+    image_utils::{local_mean, merge_regions, rgb_to_luminance},
+};
+
+const FEATURE_COUNT: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct PrnuLocalizationConfig {
+    pub fingerprint: PrnuFingerprintConfig,
+    pub window_size: usize,
+    pub step: usize,
+    /// Local standard deviation below which a pixel counts as flat (no texture to hide PRNU).
+    pub flat_threshold: f64,
+    /// Prior probability that a window is tampered.
+    pub tamper_prior: f64,
+    pub probability_threshold: f64,
+    pub fit_iterations: usize,
+}
+
+impl Default for PrnuLocalizationConfig {
+    fn default() -> Self {
+        Self {
+            fingerprint: PrnuFingerprintConfig::default(),
+            window_size: 64,
+            step: 8,
+            flat_threshold: 1.0,
+            tamper_prior: 0.5,
+            probability_threshold: 0.8,
+            fit_iterations: 3,
+        }
+    }
+}
+
+/// Linear model of the correlation expected between the residual and `I * K` in a
+/// window, from intensity, texture and flatness features.
+#[derive(Debug, Clone)]
+pub struct CorrelationPredictor {
+    pub coefficients: [f64; FEATURE_COUNT],
+    /// Standard deviation of the measured correlation around the prediction.
+    pub residual_std: f64,
+}
+
+impl CorrelationPredictor {
+    pub fn predict(&self, features: &[f64; FEATURE_COUNT]) -> f64 {
+        self.coefficients
+            .iter()
+            .zip(features)
+            .map(|(c, f)| c * f)
+            .sum()
+    }
+
+    fn fit(samples: &[([f64; FEATURE_COUNT], f64)]) -> Option<Self> {
+        if samples.len() <= FEATURE_COUNT {
+            return None;
+        }
+
+        let mut normal = [[0.0; FEATURE_COUNT + 1]; FEATURE_COUNT];
+        for (features, target) in samples {
+            for i in 0..FEATURE_COUNT {
+                for j in 0..FEATURE_COUNT {
+                    normal[i][j] += features[i] * features[j];
+                }
+                normal[i][FEATURE_COUNT] += features[i] * target;
+            }
+        }
+
+        for (i, row) in normal.iter_mut().enumerate() {
+            row[i] += 1e-9;
+        }
+
+        let coefficients = Self::solve(normal)?;
+        let mut predictor = Self {
+            coefficients,
+            residual_std: 0.0,
+        };
+
+        let variance = samples
+            .iter()
+            .map(|(f, t)| (t - predictor.predict(f)).powi(2))
+            .sum::<f64>()
+            / (samples.len() - FEATURE_COUNT) as f64;
+        predictor.residual_std = variance.sqrt().max(1e-6);
+
+        Some(predictor)
+    }
+
+    fn solve(mut m: [[f64; FEATURE_COUNT + 1]; FEATURE_COUNT]) -> Option<[f64; FEATURE_COUNT]> {
+        for col in 0..FEATURE_COUNT {
+            let pivot = (col..FEATURE_COUNT)
+                .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+            if m[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(col, pivot);
+
+            let pivot_row = m[col];
+            for (row, values) in m.iter_mut().enumerate() {
+                if row != col {
+                    let factor = values[col] / pivot_row[col];
+                    for (value, pivot_value) in values.iter_mut().zip(&pivot_row).skip(col) {
+                        *value -= factor * pivot_value;
+                    }
+                }
+            }
+        }
+
+        let mut solution = [0.0; FEATURE_COUNT];
+        for (i, value) in solution.iter_mut().enumerate() {
+            *value = m[i][FEATURE_COUNT] / m[i][i];
+        }
+
+        Some(solution)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrnuLocalizationResult {
+    /// Measured residual/fingerprint correlation of the window centered on each pixel.
+    pub correlation_map: Array2<f32>,
+    pub predicted_map: Array2<f32>,
+    /// Per-pixel posterior probability that the camera's PRNU is absent.
+    pub probability_map: Array2<f32>,
+    pub probability_image: GrayImage,
+    pub predictor: CorrelationPredictor,
+    /// Standard deviation of the correlation for windows lacking the fingerprint.
+    pub null_std: f64,
+    pub tampered_regions: Vec<SRegion>,
+    pub tampered_fraction: f64,
+}
+
+struct WindowStats {
+    x: usize,
+    y: usize,
+    correlation: f64,
+    null_correlation: f64,
+    features: [f64; FEATURE_COUNT],
+}
+
+pub struct PrnuLocalizer {
+    config: PrnuLocalizationConfig,
+    estimator: PrnuFingerprintEstimator,
+}
+
+impl PrnuLocalizer {
+    pub fn new() -> Self {
+        Self::with_config(PrnuLocalizationConfig::default())
+    }
+
+    pub fn with_config(config: PrnuLocalizationConfig) -> Self {
+        Self {
+            estimator: PrnuFingerprintEstimator::with_config(config.fingerprint.clone()),
+            config,
+        }
+    }
+
+    /// Localizes regions lacking the claimed camera's PRNU. The correlation predictor is
+    /// fitted on the query itself, discarding poorly correlated windows between passes.
+    pub fn localize(
+        &self,
+        image: &DynamicImage,
+        fingerprint: &CameraFingerprint,
+    ) -> Result<PrnuLocalizationResult> {
+        let windows = self.window_stats(image, fingerprint)?;
+        let predictor = self.fit_predictor(&windows)?;
+
+        Ok(self.build_result(image, &windows, predictor))
+    }
+
+    /// Like [`Self::localize`] with a predictor trained beforehand, e.g. by
+    /// [`Self::train_predictor`] on pristine images from the same camera.
+    pub fn localize_with_predictor(
+        &self,
+        image: &DynamicImage,
+        fingerprint: &CameraFingerprint,
+        predictor: &CorrelationPredictor,
+    ) -> Result<PrnuLocalizationResult> {
+        let windows = self.window_stats(image, fingerprint)?;
+
+        Ok(self.build_result(image, &windows, predictor.clone()))
+    }
+
+    pub fn train_predictor(
+        &self,
+        images: &[DynamicImage],
+        fingerprint: &CameraFingerprint,
+    ) -> Result<CorrelationPredictor> {
+        let mut samples = Vec::new();
+        for image in images {
+            for window in self.window_stats(image, fingerprint)? {
+                samples.push((window.features, window.correlation));
+            }
+        }
+
+        CorrelationPredictor::fit(&samples).ok_or_else(|| {
+            ForensicsError::AnalysisFailed("Not enough windows to fit the predictor".into())
+        })
+    }
+
+    fn fit_predictor(&self, windows: &[WindowStats]) -> Result<CorrelationPredictor> {
+        let mut samples = windows
+            .iter()
+            .map(|w| (w.features, w.correlation))
+            .collect::<Vec<_>>();
+
+        let mut predictor = CorrelationPredictor::fit(&samples).ok_or_else(|| {
+            ForensicsError::AnalysisFailed("Not enough windows to fit the predictor".into())
+        })?;
+
+        for _ in 1..self.config.fit_iterations {
+            let kept = windows
+                .iter()
+                .filter(|w| {
+                    w.correlation > predictor.predict(&w.features) - 2.0 * predictor.residual_std
+                })
+                .map(|w| (w.features, w.correlation))
+                .collect::<Vec<_>>();
+
+            if kept.len() == samples.len() {
+                break;
+            }
+
+            match CorrelationPredictor::fit(&kept) {
+                Some(refit) => {
+                    predictor = refit;
+                    samples = kept;
+                }
+                None => break,
+            }
+        }
+
+        Ok(predictor)
+    }
+
+    fn window_stats(
+        &self,
+        image: &DynamicImage,
+        fingerprint: &CameraFingerprint,
+    ) -> Result<Vec<WindowStats>> {
+        let (width, height) = (image.width(), image.height());
+        let window = self.config.window_size;
+
+        if width != fingerprint.width || height != fingerprint.height {
+            return Err(ForensicsError::InvalidParameter(format!(
+                "Image is {}x{} but the fingerprint is {}x{}",
+                width, height, fingerprint.width, fingerprint.height
+            )));
+        }
+
+        if self.config.step == 0 {
+            return Err(ForensicsError::InvalidParameter(
+                "Window step must be at least one pixel".into(),
+            ));
+        }
+
+        if (width as usize) < window * 2 || (height as usize) < window * 2 {
+            return Err(ForensicsError::ImageTooSmall((window * 2) as u32));
+        }
+
+        let intensity = rgb_to_luminance(&image.to_rgb8());
+        let residual = self
+            .estimator
+            .wiener_in_dft(&self.estimator.extract_residual(image).mapv(|v| v as f64));
+
+        let (rows, cols) = intensity.dim();
+        let signal = Array2::from_shape_fn((rows, cols), |idx| {
+            intensity[idx] * fingerprint.data[idx] as f64
+        });
+        let shifted = Array2::from_shape_fn((rows, cols), |(y, x)| {
+            signal[[(y + rows / 2) % rows, (x + cols / 2) % cols]]
+        });
+
+        let attenuation = intensity.mapv(|i| self.attenuation(i));
+        let texture = self.texture(&intensity);
+        let flat = Self::local_std(&intensity, 3).mapv(|s| {
+            if s < self.config.flat_threshold {
+                1.0
+            } else {
+                0.0
+            }
+        });
+
+        let sums = WindowSums::new(&residual, &signal);
+        let null_sums = WindowSums::new(&residual, &shifted);
+        let attenuation_sum = Self::integral(&attenuation);
+        let texture_sum = Self::integral(&texture);
+        let flat_sum = Self::integral(&flat);
+
+        let area = (window * window) as f64;
+        let mut windows = Vec::new();
+
+        for y in (0..=rows - window).step_by(self.config.step) {
+            for x in (0..=cols - window).step_by(self.config.step) {
+                let a = Self::window_sum(&attenuation_sum, y, x, window) / area;
+                let t = Self::window_sum(&texture_sum, y, x, window) / area;
+                let f = Self::window_sum(&flat_sum, y, x, window) / area;
+
+                windows.push(WindowStats {
+                    x,
+                    y,
+                    correlation: sums.correlation(y, x, window),
+                    null_correlation: null_sums.correlation(y, x, window),
+                    features: [1.0, a, t, f, a * t],
+                });
+            }
+        }
+
+        Ok(windows)
+    }
+
+    fn build_result(
+        &self,
+        image: &DynamicImage,
+        windows: &[WindowStats],
+        predictor: CorrelationPredictor,
+    ) -> PrnuLocalizationResult {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let window = self.config.window_size;
+
+        let null_std = (windows
+            .iter()
+            .map(|w| w.null_correlation.powi(2))
+            .sum::<f64>()
+            / windows.len().max(1) as f64)
+            .sqrt()
+            .max(1e-6);
+
+        let prior = self.config.tamper_prior.clamp(1e-6, 1.0 - 1e-6);
+
+        let grid_rows = (height - window) / self.config.step + 1;
+        let grid_cols = (width - window) / self.config.step + 1;
+
+        let mut correlation_grid = Array2::<f64>::zeros((grid_rows, grid_cols));
+        let mut predicted_grid = Array2::<f64>::zeros((grid_rows, grid_cols));
+        let mut probability_grid = Array2::<f64>::zeros((grid_rows, grid_cols));
+
+        for w in windows {
+            let cell = [w.y / self.config.step, w.x / self.config.step];
+            let predicted = predictor.predict(&w.features).max(0.0);
+
+            let tampered = prior * Self::gaussian(w.correlation, 0.0, null_std);
+            let authentic =
+                (1.0 - prior) * Self::gaussian(w.correlation, predicted, predictor.residual_std);
+
+            correlation_grid[cell] = w.correlation;
+            predicted_grid[cell] = predicted;
+            probability_grid[cell] = if tampered + authentic > 0.0 {
+                tampered / (tampered + authentic)
+            } else if w.correlation < predicted / 2.0 {
+                1.0
+            } else {
+                0.0
+            };
+        }
+
+        // Each window's decision belongs to its center pixel; the border is filled from
+        // the nearest window.
+        let to_pixels = |grid: &Array2<f64>| {
+            Array2::from_shape_fn((height, width), |(y, x)| {
+                let gy = (y.saturating_sub(window / 2) + self.config.step / 2) / self.config.step;
+                let gx = (x.saturating_sub(window / 2) + self.config.step / 2) / self.config.step;
+                grid[[gy.min(grid_rows - 1), gx.min(grid_cols - 1)]] as f32
+            })
+        };
+
+        let correlation_map = to_pixels(&correlation_grid);
+        let predicted_map = to_pixels(&predicted_grid);
+        let probability_map = to_pixels(&Self::dilate(
+            &probability_grid,
+            window / (4 * self.config.step).max(1),
+        ));
+
+        let probability_image = GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            Luma([(probability_map[[y as usize, x as usize]] * 255.0).clamp(0.0, 255.0) as u8])
+        });
+
+        let tampered_regions = self.find_tampered_regions(&probability_map);
+        let tampered_fraction = probability_map
+            .iter()
+            .filter(|&&p| p as f64 > self.config.probability_threshold)
+            .count() as f64
+            / (width * height) as f64;
+
+        PrnuLocalizationResult {
+            correlation_map,
+            predicted_map,
+            probability_map,
+            probability_image,
+            predictor,
+            null_std,
+            tampered_regions,
+            tampered_fraction,
+        }
+    }
+
+    fn find_tampered_regions(&self, probability: &Array2<f32>) -> Vec<SRegion> {
+        let (height, width) = probability.dim();
+        let block = (self.config.window_size / 2).max(1);
+        let mut regions = Vec::new();
+
+        for by in (0..height).step_by(block) {
+            for bx in (0..width).step_by(block) {
+                let block_h = block.min(height - by);
+                let block_w = block.min(width - bx);
+
+                let mean = probability
+                    .slice(ndarray::s![by..by + block_h, bx..bx + block_w])
+                    .iter()
+                    .map(|&p| p as f64)
+                    .sum::<f64>()
+                    / (block_h * block_w) as f64;
+
+                if mean > self.config.probability_threshold {
+                    regions.push(SRegion {
+                        x: bx as u32,
+                        y: by as u32,
+                        width: block_w as u32,
+                        height: block_h as u32,
+                    });
+                }
+            }
+        }
+
+        merge_regions(regions)
+    }
+
+    /// Windows straddling a tampered boundary only partly lose correlation, so each
+    /// decision is spread over a quarter window to recover the region's extent.
+    fn dilate(grid: &Array2<f64>, radius: usize) -> Array2<f64> {
+        let (rows, cols) = grid.dim();
+
+        Array2::from_shape_fn((rows, cols), |(y, x)| {
+            let mut max = 0.0f64;
+            for yy in y.saturating_sub(radius)..(y + radius + 1).min(rows) {
+                for xx in x.saturating_sub(radius)..(x + radius + 1).min(cols) {
+                    max = max.max(grid[[yy, xx]]);
+                }
+            }
+            max
+        })
+    }
+
+    /// Intensity weight that falls to zero near saturation, where PRNU is clipped away.
+    fn attenuation(&self, intensity: f64) -> f64 {
+        let saturation = self.config.fingerprint.saturation_level;
+
+        if intensity < saturation {
+            intensity / saturation
+        } else {
+            (-(intensity - saturation).powi(2) / 6.0).exp()
+        }
+    }
+
+    /// Texture feature in `(0, 1]`: high for smooth content, low where scene detail
+    /// leaks into the residual.
+    fn texture(&self, intensity: &Array2<f64>) -> Array2<f64> {
+        let high_pass = intensity - &local_mean(intensity, 3);
+        let variance = local_mean(&high_pass.mapv(|v| v * v), 5);
+
+        variance.mapv(|v| 1.0 / (1.0 + v))
+    }
+
+    fn local_std(input: &Array2<f64>, size: usize) -> Array2<f64> {
+        let mean = local_mean(input, size);
+        let mean_sq = local_mean(&input.mapv(|v| v * v), size);
+
+        Array2::from_shape_fn(input.dim(), |idx| {
+            (mean_sq[idx] - mean[idx].powi(2)).max(0.0).sqrt()
+        })
+    }
+
+    fn gaussian(x: f64, mean: f64, std: f64) -> f64 {
+        (-(x - mean).powi(2) / (2.0 * std * std)).exp() / std
+    }
+
+    fn integral(input: &Array2<f64>) -> Array2<f64> {
+        let (rows, cols) = input.dim();
+        let mut integral = Array2::<f64>::zeros((rows + 1, cols + 1));
+
+        for y in 0..rows {
+            let mut row_sum = 0.0;
+            for x in 0..cols {
+                row_sum += input[[y, x]];
+                integral[[y + 1, x + 1]] = integral[[y, x + 1]] + row_sum;
+            }
+        }
+
+        integral
+    }
+
+    fn window_sum(integral: &Array2<f64>, y: usize, x: usize, size: usize) -> f64 {
+        integral[[y + size, x + size]] - integral[[y, x + size]] - integral[[y + size, x]]
+            + integral[[y, x]]
+    }
+}
+
+impl Default for PrnuLocalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Integral images for windowed normalized cross-correlation of two arrays.
+struct WindowSums {
+    a: Array2<f64>,
+    b: Array2<f64>,
+    aa: Array2<f64>,
+    bb: Array2<f64>,
+    ab: Array2<f64>,
+}
+
+impl WindowSums {
+    fn new(a: &Array2<f64>, b: &Array2<f64>) -> Self {
+        Self {
+            a: PrnuLocalizer::integral(a),
+            b: PrnuLocalizer::integral(b),
+            aa: PrnuLocalizer::integral(&a.mapv(|v| v * v)),
+            bb: PrnuLocalizer::integral(&b.mapv(|v| v * v)),
+            ab: PrnuLocalizer::integral(&(a * b)),
+        }
+    }
+
+    fn correlation(&self, y: usize, x: usize, size: usize) -> f64 {
+        let n = (size * size) as f64;
+        let sum = |integral: &Array2<f64>| PrnuLocalizer::window_sum(integral, y, x, size);
+
+        let (sa, sb) = (sum(&self.a), sum(&self.b));
+        let covariance = sum(&self.ab) - sa * sb / n;
+        let var_a = sum(&self.aa) - sa * sa / n;
+        let var_b = sum(&self.bb) - sb * sb / n;
+
+        if var_a <= 0.0 || var_b <= 0.0 {
+            return 0.0;
+        }
+
+        covariance / (var_a * var_b).sqrt()
+    }
+}
@@ -0,0 +1,433 @@
+use std::f64::consts::PI;
+
+use image::{DynamicImage, imageops::FilterType};
+use ndarray::Array2;
+use num_complex::Complex;
+
+use crate::{
+    error::{ForensicsError, Result},
+    image_utils::{bilinear_sample, fft_2d, gray_to_array, ifft_2d, rgb_to_gray},
+};
+
+const MIN_RADIUS_DIVISOR: f64 = 64.0;
+const REFINE_ITERATIONS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct RegistrationConfig {
+    pub working_size: u32,
+    pub angle_bins: usize,
+    pub radius_bins: usize,
+    pub min_scale: f64,
+    pub max_scale: f64,
+}
+
+impl Default for RegistrationConfig {
+    fn default() -> Self {
+        Self {
+            working_size: 256,
+            angle_bins: 360,
+            radius_bins: 256,
+            min_scale: 0.2,
+            max_scale: 5.0,
+        }
+    }
+}
+
+/// Similarity transform taking a point of the moving image to the reference image:
+/// `reference = scale * R(rotation) * moving + translation`, rotation in degrees.
+#[derive(Debug, Clone, Copy)]
+pub struct Registration {
+    pub scale: f64,
+    pub rotation: f64,
+    pub translation_x: f64,
+    pub translation_y: f64,
+    pub confidence: f64,
+}
+
+impl Registration {
+    pub fn identity() -> Self {
+        Self {
+            scale: 1.0,
+            rotation: 0.0,
+            translation_x: 0.0,
+            translation_y: 0.0,
+            confidence: 1.0,
+        }
+    }
+
+    pub fn map_point(&self, x: f64, y: f64) -> (f64, f64) {
+        let (sin_r, cos_r) = self.rotation.to_radians().sin_cos();
+
+        (
+            self.scale * (cos_r * x - sin_r * y) + self.translation_x,
+            self.scale * (sin_r * x + cos_r * y) + self.translation_y,
+        )
+    }
+
+    pub fn inverse(&self) -> Registration {
+        let scale = 1.0 / self.scale;
+        let (sin_r, cos_r) = (-self.rotation).to_radians().sin_cos();
+
+        Registration {
+            scale,
+            rotation: -self.rotation,
+            translation_x: -scale * (cos_r * self.translation_x - sin_r * self.translation_y),
+            translation_y: -scale * (sin_r * self.translation_x + cos_r * self.translation_y),
+            confidence: self.confidence,
+        }
+    }
+
+    /// Bounding box `(min_x, min_y, max_x, max_y)` of a `width x height` moving image
+    /// once mapped into the reference frame.
+    pub fn footprint(&self, width: u32, height: u32) -> (f64, f64, f64, f64) {
+        let corners = [
+            self.map_point(0.0, 0.0),
+            self.map_point(width as f64, 0.0),
+            self.map_point(0.0, height as f64),
+            self.map_point(width as f64, height as f64),
+        ];
+
+        corners.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(min_x, min_y, max_x, max_y), &(x, y)| {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            },
+        )
+    }
+
+    /// Samples `reference` at the mapped position of every pixel of a `width x height`
+    /// moving frame. Pixels falling outside the reference are NaN.
+    pub fn warp(&self, reference: &Array2<f64>, width: usize, height: usize) -> Array2<f64> {
+        let source = if self.scale > 1.5 {
+            box_blur(reference, self.scale.round() as usize)
+        } else {
+            reference.clone()
+        };
+
+        Array2::from_shape_fn((height, width), |(y, x)| {
+            let (rx, ry) = self.map_point(x as f64, y as f64);
+            bilinear_sample(&source, rx, ry).unwrap_or(f64::NAN)
+        })
+    }
+}
+
+pub struct ImageRegistrar {
+    config: RegistrationConfig,
+}
+
+impl ImageRegistrar {
+    pub fn new() -> Self {
+        Self::with_config(RegistrationConfig::default())
+    }
+
+    pub fn with_config(config: RegistrationConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn register(
+        &self,
+        reference: &DynamicImage,
+        moving: &DynamicImage,
+    ) -> Result<Registration> {
+        let (ref_w, ref_h) = (reference.width(), reference.height());
+        let (mov_w, mov_h) = (moving.width(), moving.height());
+
+        if ref_w.min(ref_h).min(mov_w).min(mov_h) < 16 {
+            return Err(ForensicsError::ImageTooSmall(16));
+        }
+
+        let working = self.config.working_size as f64;
+        let factor = working / ref_w.max(ref_h).max(mov_w).max(mov_h) as f64;
+        let canvas = (self.config.working_size as usize * 2).next_power_of_two();
+
+        let (ref_canvas, ref_offset) = self.place_on_canvas(reference, factor, canvas);
+        let (mov_canvas, mov_offset) = self.place_on_canvas(moving, factor, canvas);
+
+        let ref_log_polar = self.log_polar_spectrum(&ref_canvas);
+        let mov_log_polar = self.log_polar_spectrum(&mov_canvas);
+
+        let (angle_shift, radius_shift, _) = phase_correlation(&mov_log_polar, &ref_log_polar);
+
+        let log_base = self.log_base();
+        let scale = (radius_shift * log_base)
+            .exp()
+            .clamp(self.config.min_scale, self.config.max_scale);
+        let angle = -angle_shift * PI / self.config.angle_bins as f64;
+
+        let center = canvas as f64 / 2.0;
+        let ref_spectrum = to_spectrum(&ref_canvas);
+
+        let best = [angle, angle + PI]
+            .iter()
+            .map(|&candidate| {
+                let canvas_transform = Registration {
+                    scale,
+                    rotation: candidate.to_degrees(),
+                    translation_x: center - scale * rotated(candidate, center, center).0,
+                    translation_y: center - scale * rotated(candidate, center, center).1,
+                    confidence: 0.0,
+                };
+
+                let aligned = canvas_transform
+                    .inverse()
+                    .warp(&mov_canvas, canvas, canvas)
+                    .mapv(|v| if v.is_nan() { 0.0 } else { v });
+
+                let (dy, dx, peak) = correlate_spectra(&ref_spectrum, &to_spectrum(&aligned));
+
+                (candidate, dx, dy, peak)
+            })
+            .max_by(|a, b| a.3.partial_cmp(&b.3).unwrap())
+            .unwrap();
+
+        let (rotation, dx, dy, peak) = best;
+        let (rot_ox, rot_oy) = rotated(rotation, mov_offset.0 - center, mov_offset.1 - center);
+
+        Ok(Registration {
+            scale,
+            rotation: normalize_degrees(rotation.to_degrees()),
+            translation_x: (scale * rot_ox + center + dx - ref_offset.0) / factor,
+            translation_y: (scale * rot_oy + center + dy - ref_offset.1) / factor,
+            confidence: peak.clamp(0.0, 1.0),
+        })
+    }
+
+    /// Corrects the residual sub-pixel translation of a coarse registration by phase
+    /// correlating a full-resolution window of the moving image.
+    pub fn refine(
+        &self,
+        reference: &DynamicImage,
+        moving: &DynamicImage,
+        registration: Registration,
+    ) -> Registration {
+        let reference = gray_to_array(&rgb_to_gray(&reference.to_rgb8()));
+        let moving = gray_to_array(&rgb_to_gray(&moving.to_rgb8()));
+        let (height, width) = moving.dim();
+
+        let window = (self.config.working_size as usize * 2)
+            .min(width)
+            .min(height);
+        let (off_x, off_y) = ((width - window) / 2, (height - window) / 2);
+        let patch = moving
+            .slice(ndarray::s![off_y..off_y + window, off_x..off_x + window])
+            .to_owned();
+
+        let mut refined = registration;
+
+        for _ in 0..REFINE_ITERATIONS {
+            let (wx, wy) = refined.map_point(off_x as f64, off_y as f64);
+            let windowed = Registration {
+                translation_x: wx,
+                translation_y: wy,
+                ..refined
+            };
+
+            let warped = windowed.warp(&reference, window, window);
+            let valid = warped.iter().filter(|v| !v.is_nan()).count();
+            if valid < window * window / 2 {
+                break;
+            }
+
+            let (dy, dx, _) = phase_correlation(&apodize(&patch), &apodize(&warped));
+            let (sx, sy) = rotated(refined.rotation.to_radians(), dx, dy);
+
+            refined.translation_x -= refined.scale * sx;
+            refined.translation_y -= refined.scale * sy;
+
+            if dx.abs() < 0.05 && dy.abs() < 0.05 {
+                break;
+            }
+        }
+
+        refined
+    }
+
+    fn place_on_canvas(
+        &self,
+        image: &DynamicImage,
+        factor: f64,
+        canvas: usize,
+    ) -> (Array2<f64>, (f64, f64)) {
+        let gray = rgb_to_gray(&image.to_rgb8());
+        let width = ((gray.width() as f64 * factor).round() as u32).max(1);
+        let height = ((gray.height() as f64 * factor).round() as u32).max(1);
+        let small = gray_to_array(&image::imageops::resize(
+            &gray,
+            width,
+            height,
+            FilterType::Triangle,
+        ));
+
+        let mean = small.mean().unwrap_or(0.0);
+        let off_x = (canvas - width as usize) / 2;
+        let off_y = (canvas - height as usize) / 2;
+        let mut placed = Array2::zeros((canvas, canvas));
+
+        for ((y, x), &value) in small.indexed_iter() {
+            let wx = 0.5 - 0.5 * (2.0 * PI * x as f64 / (width as f64 - 1.0).max(1.0)).cos();
+            let wy = 0.5 - 0.5 * (2.0 * PI * y as f64 / (height as f64 - 1.0).max(1.0)).cos();
+            placed[[y + off_y, x + off_x]] = (value - mean) * wx * wy;
+        }
+
+        (placed, (off_x as f64, off_y as f64))
+    }
+
+    fn log_polar_spectrum(&self, canvas: &Array2<f64>) -> Array2<f64> {
+        let n = canvas.nrows();
+        let spectrum = to_spectrum(canvas);
+        let half = n as f64 / 2.0;
+
+        let magnitude = Array2::from_shape_fn((n, n), |(y, x)| {
+            let sy = (y + n / 2) % n;
+            let sx = (x + n / 2) % n;
+            let u = (x as f64 - half) / n as f64;
+            let v = (y as f64 - half) / n as f64;
+            let cos_term = (PI * u).cos() * (PI * v).cos();
+            let high_pass = (1.0 - cos_term) * (2.0 - cos_term);
+            (1.0 + spectrum[[sy, sx]].norm()).ln() * high_pass
+        });
+
+        let log_base = self.log_base();
+        let min_radius = n as f64 / MIN_RADIUS_DIVISOR;
+
+        Array2::from_shape_fn(
+            (self.config.angle_bins, self.config.radius_bins),
+            |(a, r)| {
+                let theta = a as f64 * PI / self.config.angle_bins as f64;
+                let radius = min_radius * (r as f64 * log_base).exp();
+                bilinear_sample(
+                    &magnitude,
+                    half + radius * theta.cos(),
+                    half + radius * theta.sin(),
+                )
+                .unwrap_or(0.0)
+            },
+        )
+    }
+
+    fn log_base(&self) -> f64 {
+        (MIN_RADIUS_DIVISOR / 2.0).ln() / self.config.radius_bins as f64
+    }
+}
+
+/// Returns `(dy, dx, peak)` such that `a(x) ~= b(x - d)`.
+pub fn phase_correlation(a: &Array2<f64>, b: &Array2<f64>) -> (f64, f64, f64) {
+    correlate_spectra(&to_spectrum(a), &to_spectrum(b))
+}
+
+fn correlate_spectra(a: &Array2<Complex<f64>>, b: &Array2<Complex<f64>>) -> (f64, f64, f64) {
+    let (rows, cols) = a.dim();
+
+    let cross = Array2::from_shape_fn((rows, cols), |idx| {
+        let product = a[idx] * b[idx].conj();
+        let norm = product.norm();
+        if norm > 1e-12 {
+            product / norm
+        } else {
+            Complex::new(0.0, 0.0)
+        }
+    });
+
+    let surface = ifft_2d(&cross).mapv(|c| c.re);
+
+    let ((py, px), &peak) = surface
+        .indexed_iter()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+        .unwrap();
+
+    let at = |y: isize, x: isize| {
+        surface[[
+            y.rem_euclid(rows as isize) as usize,
+            x.rem_euclid(cols as isize) as usize,
+        ]]
+    };
+    let refine = |minus: f64, center: f64, plus: f64| {
+        let denom = minus - 2.0 * center + plus;
+        if denom.abs() > 1e-12 {
+            (0.5 * (minus - plus) / denom).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+
+    let (py, px) = (py as isize, px as isize);
+    let sub_y = refine(at(py - 1, px), peak, at(py + 1, px));
+    let sub_x = refine(at(py, px - 1), peak, at(py, px + 1));
+
+    let wrap = |p: isize, n: usize| {
+        if p > n as isize / 2 {
+            (p - n as isize) as f64
+        } else {
+            p as f64
+        }
+    };
+
+    (wrap(py, rows) + sub_y, wrap(px, cols) + sub_x, peak)
+}
+
+fn apodize(arr: &Array2<f64>) -> Array2<f64> {
+    let (height, width) = arr.dim();
+    let valid = arr.iter().filter(|v| !v.is_nan()).collect::<Vec<_>>();
+    let mean = valid.iter().copied().sum::<f64>() / valid.len().max(1) as f64;
+
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        let value = arr[[y, x]];
+        let value = if value.is_nan() { mean } else { value };
+        let wx = 0.5 - 0.5 * (2.0 * PI * x as f64 / (width as f64 - 1.0).max(1.0)).cos();
+        let wy = 0.5 - 0.5 * (2.0 * PI * y as f64 / (height as f64 - 1.0).max(1.0)).cos();
+        (value - mean) * wx * wy
+    })
+}
+
+fn to_spectrum(arr: &Array2<f64>) -> Array2<Complex<f64>> {
+    fft_2d(&arr.mapv(|v| Complex::new(v, 0.0)))
+}
+
+fn rotated(angle: f64, x: f64, y: f64) -> (f64, f64) {
+    let (sin_a, cos_a) = angle.sin_cos();
+    (cos_a * x - sin_a * y, sin_a * x + cos_a * y)
+}
+
+fn normalize_degrees(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(360.0);
+    if wrapped > 180.0 {
+        wrapped - 360.0
+    } else {
+        wrapped
+    }
+}
+
+fn box_blur(arr: &Array2<f64>, size: usize) -> Array2<f64> {
+    let (height, width) = arr.dim();
+    let radius = (size / 2) as isize;
+
+    let horizontal = Array2::from_shape_fn((height, width), |(y, x)| {
+        let (mut sum, mut count) = (0.0, 0.0);
+        for dx in -radius..=radius {
+            let xx = x as isize + dx;
+            if xx >= 0 && (xx as usize) < width {
+                sum += arr[[y, xx as usize]];
+                count += 1.0;
+            }
+        }
+        sum / count
+    });
+
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        let (mut sum, mut count) = (0.0, 0.0);
+        for dy in -radius..=radius {
+            let yy = y as isize + dy;
+            if yy >= 0 && (yy as usize) < height {
+                sum += horizontal[[yy as usize, x]];
+                count += 1.0;
+            }
+        }
+        sum / count
+    })
+}
+
+impl Default for ImageRegistrar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
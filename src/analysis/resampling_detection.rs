@@ -1,26 +1,68 @@
 use std::f64::consts::PI;
 
 use image::{DynamicImage, GrayImage, Luma};
+use ndarray::{Array2, ArrayView2, s};
+use num_complex::Complex;
+use rayon::prelude::*;
 
-use crate::{SRegion, error::Result, image_utils::rgb_to_gray};
+use crate::{
+    SRegion,
+    error::Result,
+    image_utils::{em_linear_predictor, fft_2d, rgb_to_luminance},
+};
+
+/// Spectral bins around DC ignored when searching for peaks.
+const LOW_FREQUENCY_BINS: f64 = 4.0;
+/// Radius of the neighbourhood whose median is the background of a spectral bin.
+const BACKGROUND_RADIUS: isize = 3;
 
 #[derive(Debug, Clone)]
 pub struct ResamplingConfig {
+    /// Step between DFT windows and cell size of the probability map.
     pub block_size: u32,
+    /// Side of the square p-map windows that are Fourier transformed.
     pub window_size: u32,
     pub threshold: f64,
     pub min_factor: f64,
     pub max_factor: f64,
+    /// Radius of the linear predictor neighbourhood used by the EM p-map.
+    pub em_neighborhood: usize,
+    /// Predictor radius for the small per-window p-maps of `detect_local`.
+    pub local_em_neighborhood: usize,
+    pub em_iterations: usize,
+    /// False-alarm rate for declaring a window's spectrum peaked.
+    pub peak_significance: f64,
+    /// Ignore peaks whose frequencies are both multiples of 1/8, which JPEG blocking and
+    /// demosaicing produce without any resampling.
+    pub ignore_grid_peaks: bool,
+    /// Largest log-ratio between scaling factors of neighbouring windows that are grouped
+    /// into one region by `detect_local`.
+    pub factor_tolerance: f64,
+    /// Largest rotation difference, in radians, between grouped windows.
+    pub angle_tolerance: f64,
+    pub min_region_windows: usize,
+    /// Side of the square of neighbouring windows whose spectra are averaged per window
+    /// in `detect_local`.
+    pub pooled_windows: usize,
 }
 
 impl Default for ResamplingConfig {
     fn default() -> Self {
         Self {
-            block_size: 64,
-            window_size: 16,
+            block_size: 32,
+            window_size: 64,
             threshold: 0.3,
             min_factor: 0.5,
             max_factor: 2.0,
+            em_neighborhood: 2,
+            local_em_neighborhood: 1,
+            em_iterations: 20,
+            peak_significance: 0.001,
+            ignore_grid_peaks: true,
+            factor_tolerance: 0.05,
+            angle_tolerance: 3f64.to_radians(),
+            min_region_windows: 3,
+            pooled_windows: 3,
         }
     }
 }
@@ -29,17 +71,58 @@ impl Default for ResamplingConfig {
 pub struct ResamplingResult {
     pub probability_map: GrayImage,
     pub periodic_patterns: Vec<PeriodicPattern>,
+    /// Geometric mean of the estimated x and y scaling factors.
     pub estimated_factor: Option<f64>,
+    pub estimate: Option<ResamplingEstimate>,
     pub resampling_probability: f64,
     pub resampled_regions: Vec<SRegion>,
     pub p_map: GrayImage,
+    /// EM posterior probability of each pixel being a linear combination of its neighbours.
+    pub p_map_values: Array2<f32>,
+    /// Peak strength of each DFT window, in `[0, 1]`.
+    pub window_strengths: Array2<f64>,
 }
 
 #[derive(Debug, Clone)]
 pub struct PeriodicPattern {
     pub period: f64,
     pub strength: f64,
-    pub direction: f64, // 0 = horizontal, PI/2 = vertical
+    /// Angle of the frequency vector in radians; 0 = horizontal, PI/2 = vertical.
+    pub direction: f64,
+    /// Frequency in cycles per pixel along x and y, aliased into `[-0.5, 0.5)`.
+    pub frequency: (f64, f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct ResamplingEstimate {
+    pub scale_x: f64,
+    pub scale_y: f64,
+    /// Rotation in radians, positive for clockwise on screen (y axis pointing down).
+    pub rotation: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResampledRegion {
+    /// Label of the region in `LocalResamplingResult::segment_map`.
+    pub label: u32,
+    pub bounds: SRegion,
+    /// Median estimate of the region's windows.
+    pub estimate: ResamplingEstimate,
+    pub window_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalResamplingResult {
+    /// Estimate of each window, `None` where its spectrum has no usable peak.
+    pub window_estimates: Array2<Option<ResamplingEstimate>>,
+    /// Geometric mean of the x and y factors per pixel, 1.0 where no resampling was found.
+    pub factor_map: Array2<f32>,
+    /// Log-scaled rendering of `factor_map`: mid-gray is 1.0, brighter is upsampled.
+    pub factor_image: GrayImage,
+    /// Region label per pixel, 0 outside all regions.
+    pub segment_map: Array2<u32>,
+    /// Regions ordered by size, largest first.
+    pub regions: Vec<ResampledRegion>,
 }
 
 pub struct ResamplingDetector {
@@ -56,239 +139,577 @@ impl ResamplingDetector {
     }
 
     pub fn detect(&self, image: &DynamicImage) -> Result<ResamplingResult> {
-        let gray = rgb_to_gray(&image.to_rgb8());
-        let (width, height) = gray.dimensions();
+        let luminance = rgb_to_luminance(&image.to_rgb8());
+        let (height, width) = luminance.dim();
+        let window = self.config.window_size.max(self.config.block_size);
 
-        if width < self.config.block_size * 2 || height < self.config.block_size * 2 {
-            return Err(crate::error::ForensicsError::ImageTooSmall(
-                self.config.block_size * 2,
-            ));
+        if (width as u32) < window * 2 || (height as u32) < window * 2 {
+            return Err(crate::error::ForensicsError::ImageTooSmall(window * 2));
         }
 
-        let p_map = self.compute_p_map(&gray);
+        let probabilities = self.compute_p_map(&luminance, self.config.em_neighborhood);
 
-        let periodic_patterns = self.detect_periodic_patterns(&p_map);
+        let spectra = self.window_spectra(&probabilities);
 
-        let estimated_factor = self.estimate_resampling_factor(&periodic_patterns);
+        let periodic_patterns = self.detect_periodic_patterns(&spectra);
 
-        let probability_map = self.create_probability_map(&p_map);
+        let estimate = self.estimate_geometry(&periodic_patterns);
+        let estimated_factor = estimate.as_ref().map(|e| (e.scale_x * e.scale_y).sqrt());
+
+        let window_strengths = spectra.map(|spectrum| {
+            self.spectral_peaks(spectrum, self.window_threshold())
+                .first()
+                .map_or(0.0, |&(_, _, ratio)| {
+                    self.strength(ratio, self.window_threshold())
+                })
+        });
+
+        let probability_map = self.create_probability_map(&window_strengths, width, height);
 
         let resampled_regions = self.find_resampled_regions(&probability_map);
 
         let resampling_probability = self.calculate_resampling_probability(
             &periodic_patterns,
             &resampled_regions,
-            width,
-            height,
+            width as u32,
+            height as u32,
         );
 
+        let p_map = GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            Luma([(probabilities[[y as usize, x as usize]] * 255.0).round() as u8])
+        });
+
         Ok(ResamplingResult {
             probability_map,
             periodic_patterns,
             estimated_factor,
+            estimate,
             resampling_probability,
             resampled_regions,
             p_map,
+            p_map_values: probabilities.mapv(|p| p as f32),
+            window_strengths,
         })
     }
 
-    fn compute_p_map(&self, gray: &GrayImage) -> GrayImage {
-        let (width, height) = gray.dimensions();
-        let mut p_map = GrayImage::new(width, height);
-
-        for y in 2..height - 2 {
-            for x in 2..width - 2 {
-                let d2x = gray.get_pixel(x - 1, y)[0] as f64 - 2.0 * gray.get_pixel(x, y)[0] as f64
-                    + gray.get_pixel(x + 1, y)[0] as f64;
+    /// Sliding-window version of `detect` for pasted objects. Every window gets its own
+    /// EM predictor, since a rescaled object follows other interpolation weights than
+    /// its host, and its own scale and rotation estimate. Neighbouring windows with
+    /// matching estimates are grouped into regions.
+    pub fn detect_local(&self, image: &DynamicImage) -> Result<LocalResamplingResult> {
+        let luminance = rgb_to_luminance(&image.to_rgb8());
+        let (height, width) = luminance.dim();
+        let window = self.config.window_size.max(self.config.block_size);
+
+        if (width as u32) < window * 2 || (height as u32) < window * 2 {
+            return Err(crate::error::ForensicsError::ImageTooSmall(window * 2));
+        }
 
-                let d2y = gray.get_pixel(x, y - 1)[0] as f64 - 2.0 * gray.get_pixel(x, y)[0] as f64
-                    + gray.get_pixel(x, y + 1)[0] as f64;
+        let size = self.config.window_size as usize;
+        let (rows, cols) = self.window_grid(width, height);
+
+        let spectra = (0..rows * cols)
+            .into_par_iter()
+            .map(|index| {
+                let (y0, x0) = self.window_origin(index, cols);
+                let window = luminance.slice(s![y0..y0 + size, x0..x0 + size]).to_owned();
+                self.window_spectrum(
+                    self.compute_p_map(&window, self.config.local_em_neighborhood)
+                        .view(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let pool = self.config.pooled_windows.max(1);
+        let estimates = (0..rows * cols)
+            .into_par_iter()
+            .map(|index| {
+                let (wy, wx) = (index / cols, index % cols);
+                let pooled_rows = wy.saturating_sub(pool / 2)..(wy + pool - pool / 2).min(rows);
+                let pooled_cols = wx.saturating_sub(pool / 2)..(wx + pool - pool / 2).min(cols);
+
+                let mut spectrum = Array2::<f64>::zeros((size, size));
+                for y in pooled_rows.clone() {
+                    for x in pooled_cols.clone() {
+                        spectrum += &spectra[y * cols + x];
+                    }
+                }
+                spectrum /= (pooled_rows.len() * pooled_cols.len()) as f64;
+
+                let threshold = self.averaged_threshold(pooled_rows.len(), pooled_cols.len());
+                let patterns = self.periodic_patterns(&spectrum, threshold);
+                let estimate = patterns
+                    .first()
+                    .is_some_and(|p| p.strength >= self.config.threshold)
+                    .then(|| self.estimate_geometry(&patterns))
+                    .flatten()?;
+
+                // An identity estimate is periodic scene content, not resampling.
+                let identity = estimate.scale_x.ln().abs() <= self.config.factor_tolerance
+                    && estimate.scale_y.ln().abs() <= self.config.factor_tolerance
+                    && estimate.rotation.abs() <= self.config.angle_tolerance;
+                (!identity).then_some(estimate)
+            })
+            .collect::<Vec<_>>();
+        let window_estimates =
+            Array2::from_shape_vec((rows, cols), estimates).expect("one estimate per window");
+
+        let (labels, regions) = self.segment_windows(&window_estimates);
+
+        let factor_map = Array2::from_shape_fn((height, width), |(y, x)| {
+            window_estimates[self.nearest_window(x, y, (rows, cols))]
+                .as_ref()
+                .map_or(1.0, |e| (e.scale_x * e.scale_y).sqrt() as f32)
+        });
+        let segment_map = Array2::from_shape_fn((height, width), |(y, x)| {
+            labels[self.nearest_window(x, y, (rows, cols))]
+        });
+
+        let (low, high) = (self.config.min_factor.ln(), self.config.max_factor.ln());
+        let factor_image = GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            let log = (factor_map[[y as usize, x as usize]] as f64).ln();
+            let value = if log >= 0.0 {
+                127.5 + 127.5 * log / high
+            } else {
+                127.5 - 127.5 * log / low
+            };
+            Luma([value.round().clamp(0.0, 255.0) as u8])
+        });
+
+        Ok(LocalResamplingResult {
+            window_estimates,
+            factor_map,
+            factor_image,
+            segment_map,
+            regions,
+        })
+    }
 
-                let magnitude = (d2x.abs() + d2y.abs()) / 2.0;
-                let value = (magnitude.min(255.0)) as u8;
+    /// Connected groups of windows whose estimates match their neighbours', as a label
+    /// grid and the regions with at least `min_region_windows` windows.
+    fn segment_windows(
+        &self,
+        estimates: &Array2<Option<ResamplingEstimate>>,
+    ) -> (Array2<u32>, Vec<ResampledRegion>) {
+        let (rows, cols) = estimates.dim();
+        let size = self.config.window_size;
+        let step = self.config.block_size;
+        let similar = |a: &ResamplingEstimate, b: &ResamplingEstimate| {
+            (a.scale_x / b.scale_x).ln().abs() <= self.config.factor_tolerance
+                && (a.scale_y / b.scale_y).ln().abs() <= self.config.factor_tolerance
+                && (a.rotation - b.rotation).abs() <= self.config.angle_tolerance
+        };
+
+        let mut labels = Array2::<u32>::zeros((rows, cols));
+        let mut visited = Array2::from_elem((rows, cols), false);
+        let mut regions = Vec::new();
 
-                p_map.put_pixel(x, y, Luma([value]));
+        for start in (0..rows).flat_map(|y| (0..cols).map(move |x| (y, x))) {
+            if visited[start] || estimates[start].is_none() {
+                continue;
             }
-        }
 
-        p_map
-    }
-
-    fn detect_periodic_patterns(&self, p_map: &GrayImage) -> Vec<PeriodicPattern> {
-        let (width, height) = p_map.dimensions();
-        let mut patterns = Vec::new();
-
-        let h_autocorr = self.compute_autocorrelation(p_map, true);
-        if let Some((period, strength)) = self.find_period(&h_autocorr) {
-            if strength > self.config.threshold {
-                patterns.push(PeriodicPattern {
-                    period,
-                    strength,
-                    direction: 0.0,
-                });
+            visited[start] = true;
+            let mut members = vec![start];
+            let mut queue = vec![start];
+
+            while let Some((y, x)) = queue.pop() {
+                let Some(current) = &estimates[[y, x]] else {
+                    continue;
+                };
+                let neighbours = [
+                    (y.wrapping_sub(1), x),
+                    (y + 1, x),
+                    (y, x.wrapping_sub(1)),
+                    (y, x + 1),
+                ];
+
+                for next in neighbours {
+                    if next.0 >= rows || next.1 >= cols || visited[next] {
+                        continue;
+                    }
+                    if estimates[next]
+                        .as_ref()
+                        .is_some_and(|e| similar(current, e))
+                    {
+                        visited[next] = true;
+                        members.push(next);
+                        queue.push(next);
+                    }
+                }
             }
-        }
 
-        let v_autocorr = self.compute_autocorrelation(p_map, false);
-        if let Some((period, strength)) = self.find_period(&v_autocorr) {
-            if strength > self.config.threshold {
-                patterns.push(PeriodicPattern {
-                    period,
-                    strength,
-                    direction: PI / 2.0,
-                });
+            if members.len() < self.config.min_region_windows {
+                continue;
             }
+
+            let median = |values: Vec<f64>| {
+                let mut values = values;
+                values.sort_by(|a, b| a.total_cmp(b));
+                values[values.len() / 2]
+            };
+            let member_estimates = members
+                .iter()
+                .filter_map(|&m| estimates[m].as_ref())
+                .collect::<Vec<_>>();
+            let estimate = ResamplingEstimate {
+                scale_x: median(member_estimates.iter().map(|e| e.scale_x).collect()),
+                scale_y: median(member_estimates.iter().map(|e| e.scale_y).collect()),
+                rotation: median(member_estimates.iter().map(|e| e.rotation).collect()),
+            };
+
+            // Each window speaks for the cell at its center.
+            let offset = (size - step) / 2;
+            let x0 = members.iter().map(|m| m.1).min().unwrap_or(0) as u32 * step + offset;
+            let y0 = members.iter().map(|m| m.0).min().unwrap_or(0) as u32 * step + offset;
+            let x1 = members.iter().map(|m| m.1).max().unwrap_or(0) as u32 * step + offset + step;
+            let y1 = members.iter().map(|m| m.0).max().unwrap_or(0) as u32 * step + offset + step;
+
+            regions.push((
+                members,
+                ResampledRegion {
+                    label: 0,
+                    bounds: SRegion {
+                        x: x0,
+                        y: y0,
+                        width: x1 - x0,
+                        height: y1 - y0,
+                    },
+                    estimate,
+                    window_count: 0,
+                },
+            ));
         }
 
-        patterns
-    }
+        regions.sort_by_key(|(members, _)| std::cmp::Reverse(members.len()));
 
-    fn compute_autocorrelation(&self, p_map: &GrayImage, horizontal: bool) -> Vec<f64> {
-        let (width, height) = p_map.dimensions();
-        let max_lag = self.config.window_size as usize;
-        let mut autocorr = vec![0.0; max_lag];
+        let regions = regions
+            .into_iter()
+            .enumerate()
+            .map(|(index, (members, mut region))| {
+                region.label = index as u32 + 1;
+                region.window_count = members.len();
+                for member in members {
+                    labels[member] = region.label;
+                }
+                region
+            })
+            .collect();
 
-        let step = 4;
-        let mut count = 0;
+        (labels, regions)
+    }
 
-        if horizontal {
-            for y in (0..height).step_by(step) {
-                let line = (0..width)
-                    .map(|x| p_map.get_pixel(x, y)[0] as f64)
-                    .collect::<Vec<_>>();
+    fn compute_p_map(&self, luminance: &Array2<f64>, radius: usize) -> Array2<f64> {
+        em_linear_predictor(luminance, radius, self.config.em_iterations).2
+    }
 
-                let line_autocorr = self.line_autocorrelation(&line, max_lag);
-                for i in 0..max_lag {
-                    autocorr[i] += line_autocorr[i];
-                }
-                count += 1;
-            }
-        } else {
-            for x in (0..width).step_by(step) {
-                let line = (0..height)
-                    .map(|y| p_map.get_pixel(x, y)[0] as f64)
-                    .collect::<Vec<_>>();
-
-                let line_autocorr = self.line_autocorrelation(&line, max_lag);
-                for i in 0..max_lag {
-                    autocorr[i] += line_autocorr[i];
-                }
-                count += 1;
-            }
-        }
+    fn window_spectra(&self, probabilities: &Array2<f64>) -> Array2<Array2<f64>> {
+        let (height, width) = probabilities.dim();
+        let size = self.config.window_size as usize;
+        let (rows, cols) = self.window_grid(width, height);
 
-        if count > 0 {
-            for val in &mut autocorr {
-                *val /= count as f64;
-            }
-        }
+        let spectra = (0..rows * cols)
+            .into_par_iter()
+            .map(|index| {
+                let (y0, x0) = self.window_origin(index, cols);
+                self.window_spectrum(probabilities.slice(s![y0..y0 + size, x0..x0 + size]))
+            })
+            .collect::<Vec<_>>();
 
-        autocorr
+        Array2::from_shape_vec((rows, cols), spectra).expect("one spectrum per window")
     }
 
-    fn line_autocorrelation(&self, line: &[f64], max_lag: usize) -> Vec<f64> {
-        let n = line.len();
-        let mean = line.iter().sum::<f64>() / n as f64;
-        let variance = line.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n as f64;
+    fn window_grid(&self, width: usize, height: usize) -> (usize, usize) {
+        let size = self.config.window_size as usize;
+        let step = self.config.block_size as usize;
+        ((height - size) / step + 1, (width - size) / step + 1)
+    }
 
-        let mut autocorr = vec![0.0; max_lag];
+    fn window_origin(&self, index: usize, cols: usize) -> (usize, usize) {
+        let step = self.config.block_size as usize;
+        ((index / cols) * step, (index % cols) * step)
+    }
 
-        if variance < 1e-10 {
-            return autocorr;
-        }
+    /// Hann-windowed DFT magnitude of a p-map window, divided by the median of the
+    /// surrounding bins so that smooth spectral content from the scene is flattened.
+    fn window_spectrum(&self, window: ArrayView2<f64>) -> Array2<f64> {
+        let size = window.nrows();
+        let hann = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / size as f64).cos())
+            .collect::<Vec<_>>();
+        let mean = window.mean().unwrap_or(0.0);
+
+        let input = Array2::from_shape_fn((size, size), |(y, x)| {
+            Complex::new((window[[y, x]] - mean) * hann[y] * hann[x], 0.0)
+        });
+        let magnitude = fft_2d(&input).mapv(|c| c.norm());
+
+        Self::relative_to_background(&magnitude)
+    }
 
-        for lag in 1..max_lag.min(n) {
-            let mut sum = 0.0;
-            for i in 0..n - lag {
-                sum += (line[i] - mean) * (line[i + lag] - mean);
+    fn relative_to_background(magnitude: &Array2<f64>) -> Array2<f64> {
+        let size = magnitude.nrows() as isize;
+        let mut neighbourhood = Vec::new();
+
+        Array2::from_shape_fn(magnitude.dim(), |(y, x)| {
+            neighbourhood.clear();
+            for dy in -BACKGROUND_RADIUS..=BACKGROUND_RADIUS {
+                for dx in -BACKGROUND_RADIUS..=BACKGROUND_RADIUS {
+                    if (dy, dx) != (0, 0) {
+                        let yy = (y as isize + dy).rem_euclid(size) as usize;
+                        let xx = (x as isize + dx).rem_euclid(size) as usize;
+                        neighbourhood.push(magnitude[[yy, xx]]);
+                    }
+                }
             }
-            autocorr[lag] = sum / ((n - lag) as f64 * variance);
-        }
-
-        autocorr
+            let middle = neighbourhood.len() / 2;
+            let (_, &mut median, _) =
+                neighbourhood.select_nth_unstable_by(middle, |a, b| a.total_cmp(b));
+
+            if median > 0.0 {
+                magnitude[[y, x]] / median
+            } else {
+                0.0
+            }
+        })
     }
 
-    fn find_period(&self, autocorr: &[f64]) -> Option<(f64, f64)> {
-        if autocorr.len() < 3 {
-            return None;
+    /// Peaks of the spectrum averaged over all windows.
+    fn detect_periodic_patterns(&self, spectra: &Array2<Array2<f64>>) -> Vec<PeriodicPattern> {
+        let size = self.config.window_size as usize;
+        let mut average = Array2::<f64>::zeros((size, size));
+        for spectrum in spectra.iter() {
+            average += spectrum;
         }
+        average /= spectra.len().max(1) as f64;
 
-        let mut best_peak = 0.0;
-        let mut best_period = 0.0;
+        let (rows, cols) = spectra.dim();
+        self.periodic_patterns(&average, self.averaged_threshold(rows, cols))
+    }
 
-        for i in 2..autocorr.len() - 1 {
-            if autocorr[i] > autocorr[i - 1] && autocorr[i] > autocorr[i + 1] {
-                if autocorr[i] > best_peak {
-                    best_peak = autocorr[i];
-                    best_period = i as f64;
-                }
-            }
-        }
+    /// Peak threshold for a spectrum averaged over a `rows` x `cols` grid of windows. A
+    /// median-normalized Rayleigh magnitude has mean 1.064 and deviation 0.556, and the
+    /// overlapping windows count as many independent ones as fit in their union.
+    fn averaged_threshold(&self, rows: usize, cols: usize) -> f64 {
+        let size = self.config.window_size as f64;
+        let step = self.config.block_size as f64;
+        let extent = |count: usize| (count.max(1) - 1) as f64 * step + size;
+        let independent = (extent(rows) * extent(cols) / (size * size)).max(1.0);
+        let z = (2.0 * (1.0 / self.per_test_significance()).ln()).sqrt();
+
+        (1.064 + z * 0.556 / independent.sqrt()).min(self.window_threshold())
+    }
 
-        if best_peak > 0.1 {
-            Some((best_period, best_peak))
-        } else {
-            None
-        }
+    fn periodic_patterns(&self, spectrum: &Array2<f64>, threshold: f64) -> Vec<PeriodicPattern> {
+        self.spectral_peaks(spectrum, threshold)
+            .into_iter()
+            .map(|(fx, fy, ratio)| PeriodicPattern {
+                period: 1.0 / fx.hypot(fy),
+                strength: self.strength(ratio, threshold),
+                direction: fy.atan2(fx),
+                frequency: (fx, fy),
+            })
+            .collect()
     }
 
-    fn estimate_resampling_factor(&self, patterns: &[PeriodicPattern]) -> Option<f64> {
-        if patterns.is_empty() {
-            return None;
-        }
+    fn per_test_significance(&self) -> f64 {
+        let tests = (self.config.window_size as f64).powi(2) / 2.0;
+        self.config.peak_significance / tests
+    }
 
-        let best_pattern = patterns
-            .iter()
-            .max_by(|a, b| a.strength.partial_cmp(&b.strength).unwrap())?;
+    /// Under the no-peak hypothesis each median-normalized magnitude is Rayleigh with
+    /// `P(X > t) = 2^(-t^2)`.
+    fn window_threshold(&self) -> f64 {
+        (-self.per_test_significance().log2()).sqrt()
+    }
 
-        if best_pattern.period >= self.config.min_factor
-            && best_pattern.period <= self.config.max_factor
-        {
-            Some(best_pattern.period)
-        } else {
-            None
-        }
+    fn strength(&self, ratio: f64, threshold: f64) -> f64 {
+        (1.0 - threshold / ratio).clamp(0.0, 1.0)
     }
 
-    fn create_probability_map(&self, p_map: &GrayImage) -> GrayImage {
-        let (width, height) = p_map.dimensions();
-        let block_size = self.config.block_size;
-        let mut prob_map = GrayImage::new(width, height);
+    /// Local maxima above `threshold` in one half of the spectrum, strongest first, as
+    /// `(fx, fy, ratio)` with sub-bin frequencies.
+    fn spectral_peaks(&self, spectrum: &Array2<f64>, threshold: f64) -> Vec<(f64, f64, f64)> {
+        let size = spectrum.nrows();
+        let frequency = |k: usize| {
+            if k < size / 2 {
+                k as f64
+            } else {
+                k as f64 - size as f64
+            }
+        };
+        let grid = (size / 8).max(1);
+
+        let mut peaks = Vec::new();
+        for ((ky, kx), &value) in spectrum.indexed_iter() {
+            let (by, bx) = (frequency(ky), frequency(kx));
+            if by < 0.0 || (by == 0.0 && bx < 0.0) || by.hypot(bx) < LOW_FREQUENCY_BINS {
+                continue;
+            }
+            if self.config.ignore_grid_peaks && ky % grid == 0 && kx % grid == 0 {
+                continue;
+            }
+            if value < threshold {
+                continue;
+            }
 
-        for by in (0..height - block_size).step_by(block_size as usize / 2) {
-            for bx in (0..width - block_size).step_by(block_size as usize / 2) {
-                let local_prob = self.analyze_local_periodicity(p_map, bx, by, block_size);
-                let value = (local_prob * 255.0) as u8;
+            let at = |dy: isize, dx: isize| {
+                let y = (ky as isize + dy).rem_euclid(size as isize) as usize;
+                let x = (kx as isize + dx).rem_euclid(size as isize) as usize;
+                spectrum[[y, x]]
+            };
+            let is_maximum = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dy, dx)))
+                .filter(|&offset| offset != (0, 0))
+                .all(|(dy, dx)| at(dy, dx) <= value);
+            if !is_maximum {
+                continue;
+            }
 
-                for y in by..(by + block_size).min(height) {
-                    for x in bx..(bx + block_size).min(width) {
-                        prob_map.put_pixel(x, y, Luma([value]));
-                    }
+            let refine = |before: f64, after: f64| {
+                let curvature = before - 2.0 * value + after;
+                if curvature < 0.0 {
+                    (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+                } else {
+                    0.0
                 }
-            }
+            };
+            let fx = (bx + refine(at(0, -1), at(0, 1))) / size as f64;
+            let fy = (by + refine(at(-1, 0), at(1, 0))) / size as f64;
+
+            peaks.push((fx, fy, value));
         }
 
-        prob_map
+        peaks.sort_by(|a, b| b.2.total_cmp(&a.2));
+        peaks
     }
 
-    fn analyze_local_periodicity(&self, p_map: &GrayImage, bx: u32, by: u32, size: u32) -> f64 {
-        let (width, height) = p_map.dimensions();
+    /// Resampling maps output pixel `(x, y)` to input coordinates `M (x, y)`, and the
+    /// p-map repeats with the fractional part of each, so each row of `M` shows up as a
+    /// spectral peak aliased into `[-0.5, 0.5)`. The strongest peak is paired with each
+    /// other non-harmonic peak (or its sum and difference with the first, to undo
+    /// intermodulation) and unaliased into the rows of a rotated scaling. Aliasing leaves
+    /// several consistent solutions, so the one closest to identity is kept, favouring
+    /// upsampling. An axis without a peak is taken as unscaled.
+    fn estimate_geometry(&self, patterns: &[PeriodicPattern]) -> Option<ResamplingEstimate> {
+        let first = patterns.first()?.frequency;
+        let size = self.config.window_size as f64;
+        let tolerance = 1.5 / size;
+
+        let wrap = |(fx, fy): (f64, f64)| (fx - fx.round(), fy - fy.round());
+        let usable = |(fx, fy): (f64, f64)| {
+            fx.hypot(fy) >= LOW_FREQUENCY_BINS / size
+                && (-4..=4).filter(|&m| m != 0).all(|m| {
+                    let (dx, dy) = wrap((fx - m as f64 * first.0, fy - m as f64 * first.1));
+                    dx.hypot(dy) > tolerance
+                })
+        };
+
+        let mut seconds = vec![None];
+        for pattern in patterns.iter().skip(1).take(6) {
+            let (fx, fy) = pattern.frequency;
+            let candidates = [
+                (fx, fy),
+                wrap((fx - first.0, fy - first.1)),
+                wrap((fx + first.0, fy + first.1)),
+            ];
+            seconds.extend(candidates.into_iter().filter(|&f| usable(f)).map(Some));
+        }
 
-        let mut values = Vec::new();
-        for y in by..(by + size).min(height) {
-            for x in bx..(bx + size).min(width) {
-                values.push(p_map.get_pixel(x, y)[0] as f64);
+        let unalias = |(fx, fy): (f64, f64)| {
+            let mut rows = Vec::new();
+            for sign in [1.0, -1.0] {
+                for nx in -1..=1 {
+                    for ny in -1..=1 {
+                        rows.push((sign * fx + nx as f64, sign * fy + ny as f64));
+                    }
+                }
+            }
+            rows
+        };
+
+        let first_rows = unalias(first);
+        let max_angle = PI / 4.0;
+        let in_range = |s: f64| s >= self.config.min_factor && s <= self.config.max_factor;
+        let mut best: Option<(f64, ResamplingEstimate)> = None;
+
+        for second in seconds {
+            let (second_rows, penalty) = match second {
+                Some(frequency) => (unalias(frequency), 0.0),
+                None => (vec![(1.0, 0.0), (0.0, 1.0)], 0.05),
+            };
+
+            for &a in &first_rows {
+                for &b in &second_rows {
+                    for (row_x, row_y) in [(a, b), (b, a)] {
+                        let scale_x = 1.0 / row_x.0.hypot(row_x.1);
+                        let scale_y = 1.0 / row_y.0.hypot(row_y.1);
+                        let angle_x = row_x.1.atan2(row_x.0);
+                        let angle_y = (-row_y.0).atan2(row_y.1);
+
+                        if !in_range(scale_x)
+                            || !in_range(scale_y)
+                            || angle_x.abs() > max_angle
+                            || angle_y.abs() > max_angle
+                        {
+                            continue;
+                        }
+
+                        let mismatch = (angle_x - angle_y).abs();
+                        if mismatch > 5f64.to_radians() {
+                            continue;
+                        }
+
+                        let rotation = (angle_x + angle_y) / 2.0;
+                        let downsampled = [scale_x, scale_y].iter().filter(|&&s| s < 0.99).count();
+                        let cost = mismatch
+                            + penalty
+                            + 0.1 * (scale_x.ln().abs() + scale_y.ln().abs())
+                            + 0.1 * rotation.abs()
+                            + 0.1 * downsampled as f64;
+
+                        if best.as_ref().is_none_or(|(c, _)| cost < *c) {
+                            best = Some((
+                                cost,
+                                ResamplingEstimate {
+                                    scale_x,
+                                    scale_y,
+                                    rotation,
+                                },
+                            ));
+                        }
+                    }
+                }
             }
         }
 
-        if values.is_empty() {
-            return 0.0;
-        }
+        best.map(|(_, estimate)| estimate)
+    }
 
-        let mean = values.iter().sum::<f64>() / values.len() as f64;
-        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    /// Each cell takes the strength of the window centered closest to it.
+    fn create_probability_map(
+        &self,
+        window_strengths: &Array2<f64>,
+        width: usize,
+        height: usize,
+    ) -> GrayImage {
+        GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            let window = self.nearest_window(x as usize, y as usize, window_strengths.dim());
+            Luma([(window_strengths[window] * 255.0).round() as u8])
+        })
+    }
 
-        (variance / 1000.0).min(1.0)
+    /// Window whose center is closest to the center of the cell holding pixel `(x, y)`.
+    fn nearest_window(&self, x: usize, y: usize, (rows, cols): (usize, usize)) -> [usize; 2] {
+        let size = self.config.window_size as f64;
+        let step = self.config.block_size as f64;
+        let cell = self.config.block_size as usize;
+        let nearest = |pixel: usize, count: usize| {
+            let center = (pixel / cell) as f64 * step + step / 2.0;
+            (((center - size / 2.0) / step).round().max(0.0) as usize).min(count - 1)
+        };
+
+        [nearest(y, rows), nearest(x, cols)]
     }
 
     fn find_resampled_regions(&self, prob_map: &GrayImage) -> Vec<SRegion> {
@@ -0,0 +1,475 @@
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::{
+    SRegion,
+    analysis::shadow_analysis::ShadowRegion,
+    error::{ForensicsError, Result},
+};
+
+#[derive(Debug, Clone)]
+pub struct ShadowGeometryConfig {
+    /// Half-size of the search area around the image, in image diagonals. Projected lights
+    /// of distant sources such as the sun lie far outside the frame.
+    pub search_extent: f64,
+    /// Largest number of constraints for which the biggest consistent subset is found by
+    /// exhaustive search; larger sets are pruned greedily.
+    pub max_exhaustive: usize,
+    /// Half-angle, in radians, used when a constraint has no uncertainty of its own.
+    pub min_tolerance: f64,
+}
+
+impl Default for ShadowGeometryConfig {
+    fn default() -> Self {
+        Self {
+            search_extent: 50.0,
+            max_exhaustive: 16,
+            min_tolerance: 2f64.to_radians(),
+        }
+    }
+}
+
+/// A shadow point and the object point casting it. The projected light lies inside the
+/// wedge with apex at the shadow point, opening toward the object point with the given
+/// half-angle, or in the opposite wedge when the light is behind the camera.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowConstraint {
+    pub shadow: (f64, f64),
+    pub object: (f64, f64),
+    /// Half-angle of the wedge, in radians.
+    pub tolerance: f64,
+}
+
+impl ShadowConstraint {
+    pub fn new(shadow: (f64, f64), object: (f64, f64), tolerance: f64) -> Self {
+        Self {
+            shadow,
+            object,
+            tolerance,
+        }
+    }
+
+    /// Builds a constraint between region centers, with a wedge wide enough to cover both
+    /// regions as seen from each other.
+    pub fn from_regions(shadow: &ShadowRegion, object: &SRegion) -> Self {
+        let center = |r: &SRegion| {
+            (
+                r.x as f64 + r.width as f64 / 2.0,
+                r.y as f64 + r.height as f64 / 2.0,
+            )
+        };
+        let extent = |r: &SRegion| r.width.max(r.height) as f64 / 2.0;
+
+        let (s, o) = (center(&shadow.region), center(object));
+        let distance = (o.0 - s.0).hypot(o.1 - s.1).max(1.0);
+        let spread = extent(&shadow.region) + extent(object);
+
+        Self::new(s, o, (spread / distance).atan())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowGeometryResult {
+    /// Whether all constraints admit one common light position.
+    pub feasible: bool,
+    /// Whether the largest consistent subset requires the light behind the camera.
+    pub light_behind_camera: bool,
+    /// Centroid of the feasible region of the consistent subset, in image coordinates.
+    pub light_position: Option<(f64, f64)>,
+    /// Vertices of the feasible region of the consistent subset.
+    pub feasible_region: Vec<(f64, f64)>,
+    /// True when the feasible region reaches the search boundary, as for a very distant
+    /// light.
+    pub unbounded: bool,
+    /// Indices of the largest mutually consistent subset of constraints.
+    pub consistent: Vec<usize>,
+    /// Indices of the constraints that had to be dropped.
+    pub inconsistent: Vec<usize>,
+    /// For every dropped constraint, a minimal set of constraints including it that no
+    /// light position can satisfy.
+    pub conflicts: Vec<Vec<usize>>,
+    pub visualization: RgbImage,
+    pub manipulation_probability: f64,
+}
+
+/// Light hypothesis and feasible polygon of a set of constraints.
+struct Solution {
+    behind: bool,
+    region: Vec<(f64, f64)>,
+}
+
+/// Half-plane `normal . p <= offset`.
+#[derive(Debug, Clone, Copy)]
+struct HalfPlane {
+    normal: (f64, f64),
+    offset: f64,
+}
+
+pub struct ShadowGeometryAnalyzer {
+    config: ShadowGeometryConfig,
+}
+
+impl ShadowGeometryAnalyzer {
+    pub fn new() -> Self {
+        Self::with_config(ShadowGeometryConfig::default())
+    }
+
+    pub fn with_config(config: ShadowGeometryConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn analyze(
+        &self,
+        image: &DynamicImage,
+        constraints: &[ShadowConstraint],
+    ) -> Result<ShadowGeometryResult> {
+        if constraints.len() < 2 {
+            return Err(ForensicsError::InvalidParameter(
+                "At least two shadow constraints are needed".to_string(),
+            ));
+        }
+        if constraints
+            .iter()
+            .any(|c| (c.object.0 - c.shadow.0).hypot(c.object.1 - c.shadow.1) < 1e-6)
+        {
+            return Err(ForensicsError::InvalidParameter(
+                "Shadow and object points of a constraint coincide".to_string(),
+            ));
+        }
+
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let bounds = self.search_bounds(width, height);
+
+        let n = constraints.len();
+        let (consistent, Solution { behind, region }) =
+            self.largest_consistent_subset(constraints, &bounds);
+        let inconsistent = (0..n)
+            .filter(|i| !consistent.contains(i))
+            .collect::<Vec<_>>();
+
+        let conflicts = inconsistent
+            .iter()
+            .map(|&i| self.minimal_conflict(constraints, &consistent, i, &bounds))
+            .collect();
+
+        let unbounded = region.iter().any(|&(x, y)| {
+            let eps = 1e-6 * (bounds.2 - bounds.0);
+            (x - bounds.0).abs() < eps
+                || (x - bounds.2).abs() < eps
+                || (y - bounds.1).abs() < eps
+                || (y - bounds.3).abs() < eps
+        });
+        let light_position = polygon_centroid(&region);
+
+        let visualization =
+            self.create_visualization(&rgb, constraints, &inconsistent, light_position);
+
+        Ok(ShadowGeometryResult {
+            feasible: inconsistent.is_empty(),
+            light_behind_camera: behind,
+            light_position,
+            feasible_region: region,
+            unbounded,
+            manipulation_probability: inconsistent.len() as f64 / n as f64,
+            consistent,
+            inconsistent,
+            conflicts,
+            visualization,
+        })
+    }
+
+    /// Finds the largest subset of constraints with a common light position, returning the
+    /// subset, the light hypothesis and the feasible polygon.
+    fn largest_consistent_subset(
+        &self,
+        constraints: &[ShadowConstraint],
+        bounds: &(f64, f64, f64, f64),
+    ) -> (Vec<usize>, Solution) {
+        let n = constraints.len();
+        let all = (0..n).collect::<Vec<_>>();
+
+        if let Some(solution) = self.solve(constraints, &all, bounds) {
+            return (all, solution);
+        }
+
+        if n <= self.config.max_exhaustive.min(24) {
+            let mut masks = (1u32..(1 << n)).collect::<Vec<_>>();
+            masks.sort_by_key(|m| std::cmp::Reverse(m.count_ones()));
+
+            for mask in masks {
+                let subset = (0..n).filter(|i| mask & (1 << i) != 0).collect::<Vec<_>>();
+                if let Some(solution) = self.solve(constraints, &subset, bounds) {
+                    return (subset, solution);
+                }
+            }
+        }
+
+        // Greedy fallback: drop the constraint in most pairwise conflicts until feasible.
+        let mut subset = all;
+        loop {
+            if let Some(solution) = self.solve(constraints, &subset, bounds) {
+                return (subset, solution);
+            }
+
+            let worst = subset
+                .iter()
+                .copied()
+                .max_by_key(|&i| {
+                    subset
+                        .iter()
+                        .filter(|&&j| j != i && self.solve(constraints, &[i, j], bounds).is_none())
+                        .count()
+                })
+                .unwrap_or(0);
+            subset.retain(|&i| i != worst);
+        }
+    }
+
+    /// Shrinks `consistent` plus `dropped` to an irreducible infeasible set by deletion.
+    fn minimal_conflict(
+        &self,
+        constraints: &[ShadowConstraint],
+        consistent: &[usize],
+        dropped: usize,
+        bounds: &(f64, f64, f64, f64),
+    ) -> Vec<usize> {
+        let mut conflict = consistent.to_vec();
+        conflict.push(dropped);
+
+        for &candidate in consistent {
+            let reduced = conflict
+                .iter()
+                .copied()
+                .filter(|&i| i != candidate)
+                .collect::<Vec<_>>();
+            if self.solve(constraints, &reduced, bounds).is_none() {
+                conflict = reduced;
+            }
+        }
+
+        conflict.sort_unstable();
+        conflict
+    }
+
+    /// Intersects the wedges of a subset for a light in front of and behind the camera,
+    /// preferring the former.
+    fn solve(
+        &self,
+        constraints: &[ShadowConstraint],
+        subset: &[usize],
+        bounds: &(f64, f64, f64, f64),
+    ) -> Option<Solution> {
+        [false, true].into_iter().find_map(|behind| {
+            let mut polygon = vec![
+                (bounds.0, bounds.1),
+                (bounds.2, bounds.1),
+                (bounds.2, bounds.3),
+                (bounds.0, bounds.3),
+            ];
+
+            for &i in subset {
+                for plane in self.wedge(&constraints[i], behind) {
+                    polygon = clip_polygon(&polygon, plane);
+                    if polygon.is_empty() {
+                        return None;
+                    }
+                }
+            }
+
+            Some(Solution {
+                behind,
+                region: polygon,
+            })
+        })
+    }
+
+    fn wedge(&self, constraint: &ShadowConstraint, behind: bool) -> [HalfPlane; 2] {
+        let (sx, sy) = constraint.shadow;
+        let (mut dx, mut dy) = (constraint.object.0 - sx, constraint.object.1 - sy);
+        let length = dx.hypot(dy);
+        (dx, dy) = (dx / length, dy / length);
+        if behind {
+            (dx, dy) = (-dx, -dy);
+        }
+
+        let angle = constraint
+            .tolerance
+            .max(self.config.min_tolerance)
+            .min(89f64.to_radians());
+        let (sin, cos) = angle.sin_cos();
+        let left = (dx * cos - dy * sin, dx * sin + dy * cos);
+        let right = (dx * cos + dy * sin, -dx * sin + dy * cos);
+
+        let plane = |normal: (f64, f64)| HalfPlane {
+            normal,
+            offset: normal.0 * sx + normal.1 * sy,
+        };
+
+        [plane((-left.1, left.0)), plane((right.1, -right.0))]
+    }
+
+    fn search_bounds(&self, width: u32, height: u32) -> (f64, f64, f64, f64) {
+        let extent = self.config.search_extent * (width as f64).hypot(height as f64);
+        (
+            -extent,
+            -extent,
+            width as f64 + extent,
+            height as f64 + extent,
+        )
+    }
+
+    fn create_visualization(
+        &self,
+        original: &RgbImage,
+        constraints: &[ShadowConstraint],
+        inconsistent: &[usize],
+        light: Option<(f64, f64)>,
+    ) -> RgbImage {
+        let mut vis = original.clone();
+        let (width, height) = vis.dimensions();
+        let reach = (width as f64).hypot(height as f64);
+
+        for (i, constraint) in constraints.iter().enumerate() {
+            let color = if inconsistent.contains(&i) {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 255, 0])
+            };
+
+            let (sx, sy) = constraint.shadow;
+            let (dx, dy) = (constraint.object.0 - sx, constraint.object.1 - sy);
+            let length = dx.hypot(dy);
+            let end = (sx + dx / length * reach, sy + dy / length * reach);
+            self.draw_line(&mut vis, constraint.shadow, end, color);
+            self.draw_line(
+                &mut vis,
+                constraint.shadow,
+                constraint.object,
+                Rgb([255, 255, 0]),
+            );
+        }
+
+        if let Some((lx, ly)) = light {
+            self.draw_line(
+                &mut vis,
+                (lx - 8.0, ly),
+                (lx + 8.0, ly),
+                Rgb([255, 255, 255]),
+            );
+            self.draw_line(
+                &mut vis,
+                (lx, ly - 8.0),
+                (lx, ly + 8.0),
+                Rgb([255, 255, 255]),
+            );
+        }
+
+        vis
+    }
+
+    fn draw_line(&self, image: &mut RgbImage, from: (f64, f64), to: (f64, f64), color: Rgb<u8>) {
+        let (width, height) = image.dimensions();
+        let t_end = clip_segment(from, to, width, height);
+        let to = (
+            from.0 + t_end * (to.0 - from.0),
+            from.1 + t_end * (to.1 - from.1),
+        );
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil() as usize;
+
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f64 / steps as f64
+            };
+            let x = (from.0 + t * (to.0 - from.0)).round();
+            let y = (from.1 + t * (to.1 - from.1)).round();
+
+            if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+                image.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+/// Largest fraction of the segment from `from` to `to` that stays within the image.
+fn clip_segment(from: (f64, f64), to: (f64, f64), width: u32, height: u32) -> f64 {
+    let mut t_max: f64 = 1.0;
+
+    for (start, delta, limit) in [
+        (from.0, to.0 - from.0, width as f64),
+        (from.1, to.1 - from.1, height as f64),
+    ] {
+        if delta > 0.0 {
+            t_max = t_max.min(((limit - start) / delta).max(0.0));
+        } else if delta < 0.0 {
+            t_max = t_max.min(((-1.0 - start) / delta).max(0.0));
+        }
+    }
+
+    t_max
+}
+
+/// Sutherland-Hodgman clipping of a convex polygon by a half-plane.
+fn clip_polygon(polygon: &[(f64, f64)], plane: HalfPlane) -> Vec<(f64, f64)> {
+    let side = |p: (f64, f64)| plane.normal.0 * p.0 + plane.normal.1 * p.1 - plane.offset;
+    let scale = plane.normal.0.hypot(plane.normal.1)
+        * polygon
+            .iter()
+            .map(|p| p.0.abs().max(p.1.abs()))
+            .fold(1.0, f64::max);
+    let eps = 1e-12 * scale;
+
+    let mut output = Vec::new();
+    for (k, &current) in polygon.iter().enumerate() {
+        let previous = polygon[(k + polygon.len() - 1) % polygon.len()];
+        let (d_current, d_previous) = (side(current), side(previous));
+
+        if d_current <= eps {
+            if d_previous > eps {
+                output.push(interpolate(previous, current, d_previous, d_current));
+            }
+            output.push(current);
+        } else if d_previous <= eps {
+            output.push(interpolate(previous, current, d_previous, d_current));
+        }
+    }
+
+    output
+}
+
+fn interpolate(a: (f64, f64), b: (f64, f64), da: f64, db: f64) -> (f64, f64) {
+    let t = da / (da - db);
+    (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1))
+}
+
+fn polygon_centroid(polygon: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if polygon.is_empty() {
+        return None;
+    }
+
+    let (mut area, mut cx, mut cy) = (0.0, 0.0, 0.0);
+    for (k, &(x0, y0)) in polygon.iter().enumerate() {
+        let (x1, y1) = polygon[(k + 1) % polygon.len()];
+        let cross = x0 * y1 - x1 * y0;
+        area += cross;
+        cx += (x0 + x1) * cross;
+        cy += (y0 + y1) * cross;
+    }
+
+    if area.abs() < 1e-9 {
+        let count = polygon.len() as f64;
+        return Some((
+            polygon.iter().map(|p| p.0).sum::<f64>() / count,
+            polygon.iter().map(|p| p.1).sum::<f64>() / count,
+        ));
+    }
+
+    Some((cx / (3.0 * area), cy / (3.0 * area)))
+}
+
+impl Default for ShadowGeometryAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -47,6 +47,8 @@ pub enum ManipulationType {
     Rotation,
     ColorManipulation,
     AIGenerated,
+    /// Processing that hides the traces other detectors rely on, such as median filtering.
+    AntiForensics,
     Unknown,
 }
 
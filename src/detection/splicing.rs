@@ -1,10 +1,14 @@
-use image::{GrayImage, Luma, Rgb, RgbImage};
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
 
 use crate::{
     SRegion,
-    analysis::{ela::ElaAnalyzer, noise::NoiseAnalyzer},
+    analysis::{
+        ela::ElaAnalyzer,
+        illuminant_analysis::{IlluminantAnalyzer, IlluminantConfig},
+        noise::NoiseAnalyzer,
+    },
     detection::{ConfidenceLevel, DetectedManipulation, DetectionResult, Detector},
-    error::Result,
+    error::{ForensicsError, Result},
     image_utils::rgb_to_gray,
 };
 
@@ -46,97 +50,48 @@ impl SplicingDetector {
         Self { config }
     }
 
-    fn analyze_color_consistency(&self, image: &RgbImage) -> (GrayImage, Vec<SRegion>) {
-        let (width, height) = image.dimensions();
-        let block_size = self.config.block_size;
+    /// Compares the illuminant color estimated around each superpixel with the scene's, so
+    /// that differently colored content under the same light is not flagged. Superpixels
+    /// follow `block_size`, and a higher `color_sensitivity` lowers the disagreement needed.
+    fn analyze_color_consistency(&self, image: &DynamicImage) -> Result<(GrayImage, Vec<SRegion>)> {
+        let (width, height) = (image.width(), image.height());
         let mut inconsistency_map = GrayImage::new(width, height);
-        let mut suspicious_regions = Vec::new();
-
-        let global_histogram = self.calculate_color_histogram(image, 0, 0, width, height);
-
-        for by in (0..height).step_by(block_size as usize) {
-            for bx in (0..width).step_by(block_size as usize) {
-                let block_w = block_size.min(width - bx);
-                let block_h = block_size.min(height - by);
-
-                let block_histogram =
-                    self.calculate_color_histogram(image, bx, by, block_w, block_h);
-
-                let diff = self.histogram_difference(&global_histogram, &block_histogram);
-                let inconsistency = (diff * 255.0 * self.config.color_sensitivity) as u8;
-
-                for y in by..(by + block_h) {
-                    for x in bx..(bx + block_w) {
-                        inconsistency_map.put_pixel(x, y, Luma([inconsistency]));
-                    }
-                }
-
-                if diff > 0.3 * self.config.color_sensitivity {
-                    suspicious_regions.push(SRegion {
-                        x: bx,
-                        y: by,
-                        width: block_w,
-                        height: block_h,
-                    });
-                }
-            }
-        }
-
-        (inconsistency_map, suspicious_regions)
-    }
-
-    fn calculate_color_histogram(
-        &self,
-        image: &RgbImage,
-        x: u32,
-        y: u32,
-        w: u32,
-        h: u32,
-    ) -> [[[u32; 8]; 8]; 8] {
-        let mut histogram = [[[0u32; 8]; 8]; 8];
-
-        for py in y..(y + h).min(image.height()) {
-            for px in x..(x + w).min(image.width()) {
-                let pixel = image.get_pixel(px, py);
-                let r_bin = (pixel[0] / 32) as usize;
-                let g_bin = (pixel[1] / 32) as usize;
-                let b_bin = (pixel[2] / 32) as usize;
-                histogram[r_bin][g_bin][b_bin] += 1;
-            }
-        }
 
-        histogram
-    }
-
-    fn histogram_difference(&self, h1: &[[[u32; 8]; 8]; 8], h2: &[[[u32; 8]; 8]; 8]) -> f64 {
-        let mut sum1 = 0u32;
-        let mut sum2 = 0u32;
-        let mut diff_sum = 0.0;
+        let defaults = IlluminantConfig::default();
+        let superpixel_size = self.config.block_size.max(8);
+        let strictness = 0.5 / self.config.color_sensitivity.max(0.01);
+        let config = IlluminantConfig {
+            superpixel_size,
+            // Pool over the same distance in pixels whatever the superpixel size.
+            neighbourhood_radius: defaults.neighbourhood_radius * defaults.superpixel_size as f64
+                / superpixel_size as f64,
+            angle_threshold: defaults.angle_threshold * strictness,
+            robust_factor: defaults.robust_factor * strictness,
+            ..defaults
+        };
 
-        for r in 0..8 {
-            for g in 0..8 {
-                for b in 0..8 {
-                    sum1 += h1[r][g][b];
-                    sum2 += h2[r][g][b];
-                }
+        let result = match IlluminantAnalyzer::with_config(config).analyze(image) {
+            Ok(result) => result,
+            // Too small, or too clipped or dark for any illuminant estimate.
+            Err(ForensicsError::AnalysisFailed(_) | ForensicsError::ImageTooSmall(_)) => {
+                return Ok((inconsistency_map, Vec::new()));
             }
-        }
+            Err(e) => return Err(e),
+        };
 
-        if sum1 == 0 || sum2 == 0 {
-            return 0.0;
+        for ((y, x), &deviation) in result.deviation_map.indexed_iter() {
+            let inconsistency =
+                (deviation / result.threshold * 255.0 * self.config.color_sensitivity).min(255.0);
+            inconsistency_map.put_pixel(x as u32, y as u32, Luma([inconsistency as u8]));
         }
 
-        for r in 0..8 {
-            for g in 0..8 {
-                for b in 0..8 {
-                    let n1 = h1[r][g][b] as f64 / sum1 as f64;
-                    let n2 = h2[r][g][b] as f64 / sum2 as f64;
-                    diff_sum += (n1 - n2).abs();
-                }
-            }
-        }
+        let suspicious_regions = result
+            .inconsistent_regions
+            .iter()
+            .map(|r| r.region)
+            .collect();
 
-        diff_sum / 2.0 // Normalize to 0-1
+        Ok((inconsistency_map, suspicious_regions))
     }
 
     fn detect_edge_inconsistencies(&self, image: &RgbImage) -> (GrayImage, Vec<SRegion>) {
@@ -456,8 +411,8 @@ impl Detector for SplicingDetector {
         let rgb = image.to_rgb8();
         let mut result = DetectionResult::new(&rgb);
 
-        let (_, color_regions) = self.analyze_color_consistency(&rgb);
-        let (_, edge_regions) = self.analyze_color_consistency(&rgb);
+        let (_, color_regions) = self.analyze_color_consistency(image)?;
+        let (_, edge_regions) = self.detect_edge_inconsistencies(&rgb);
 
         let noise_analyzer = NoiseAnalyzer::new();
         let noise_result = noise_analyzer.analyze(image)?;
@@ -477,7 +432,7 @@ impl Detector for SplicingDetector {
                 .iter()
                 .any(|r| self.regions_overlap(r, region))
             {
-                evidence.push("Color histogram incosistency".into());
+                evidence.push("Illuminant color inconsistency".into());
             }
 
             if edge_regions.iter().any(|r| self.regions_overlap(r, region)) {
@@ -518,7 +473,7 @@ impl Detector for SplicingDetector {
     }
 
     fn description(&self) -> &str {
-        "Detects regions that appear to be spliced from another image using color histogram analysis, edge detection, noise analysis, and ELA"
+        "Detects regions that appear to be spliced from another image using illuminant color estimation, edge detection, noise analysis, and ELA"
     }
 }
 
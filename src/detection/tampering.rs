@@ -2,12 +2,20 @@ use image::{DynamicImage, GrayImage, Rgb, RgbImage};
 
 use crate::{
     SRegion,
-    analysis::{copy_move::CopyMoveDetector, jpeg_analysis::JpegAnalyzer},
+    analysis::{
+        blur_estimation::{BlurAnalyzer, BlurConfig, BlurInconsistency},
+        contrast_enhancement::{
+            ContrastAnalyzer, ContrastConfig, ContrastMapping, MappingEstimate,
+        },
+        copy_move::CopyMoveDetector,
+        jpeg_analysis::JpegAnalyzer,
+        median_filtering::MedianFilterAnalyzer,
+    },
     detection::{
         ConfidenceLevel, DetectedManipulation, DetectionResult, Detector, ManipulationType,
         splicing::SplicingDetector,
     },
-    error::Result,
+    error::{ForensicsError, Result},
     image_utils::rgb_to_gray,
 };
 
@@ -16,6 +24,8 @@ pub struct TamperingConfig {
     pub detect_copy_move: bool,
     pub detect_splicing: bool,
     pub detect_retouching: bool,
+    /// Look for median filtering used to hide resampling and JPEG traces.
+    pub detect_anti_forensics: bool,
     pub block_size: u32,
     pub sensitivity: f64,
     pub min_confidence: f64,
@@ -27,6 +37,7 @@ impl Default for TamperingConfig {
             detect_copy_move: true,
             detect_splicing: true,
             detect_retouching: true,
+            detect_anti_forensics: true,
             block_size: 16,
             sensitivity: 0.5,
             min_confidence: 0.3,
@@ -54,7 +65,7 @@ impl TamperingDetector {
         let mut manipulations = Vec::new();
 
         let texture_anomalies = self.analyze_texture_consistency(&rgb);
-        let blur_anomalies = self.analyze_blur_consistency(&rgb);
+        let blur_anomalies = self.analyze_blur_consistency(image)?;
 
         for (region, score) in texture_anomalies {
             if score >= self.config.min_confidence {
@@ -69,7 +80,7 @@ impl TamperingDetector {
             }
         }
 
-        for (region, score) in blur_anomalies {
+        for (region, score, evidence) in blur_anomalies {
             if score >= self.config.min_confidence {
                 manipulations.push(DetectedManipulation {
                     manipulation_type: ManipulationType::Retouching,
@@ -77,11 +88,17 @@ impl TamperingDetector {
                     confidence: score,
                     confidence_level: ConfidenceLevel::from_score(score),
                     description: "Blur inconsistency suggesting retouching".into(),
-                    evidence: vec!["Abnormal blur pattern".into()],
+                    evidence: vec![evidence],
                 });
             }
         }
 
+        manipulations.extend(
+            self.analyze_contrast_consistency(image)?
+                .into_iter()
+                .filter(|m| m.confidence >= self.config.min_confidence),
+        );
+
         Ok(manipulations)
     }
 
@@ -163,92 +180,134 @@ impl TamperingDetector {
         if count > 0 { sum / count as f64 } else { 0.0 }
     }
 
-    fn analyze_blur_consistency(&self, image: &RgbImage) -> Vec<(SRegion, f64)> {
-        let (width, height) = image.dimensions();
-        let block_size = self.config.block_size;
-        let gray = rgb_to_gray(image);
-
-        let mut results = Vec::new();
-        let mut block_sharpness = Vec::new();
-
-        for by in (0..height).step_by(block_size as usize) {
-            for bx in (0..width).step_by(block_size as usize) {
-                let sharpness = self.calculate_laplcaian_variance(&gray, bx, by, block_size);
-                block_sharpness.push((bx, by, sharpness));
+    /// Regions whose estimated defocus blur departs from their surroundings, with the
+    /// measured and surrounding blur as evidence. A lower sensitivity setting flags smaller
+    /// blur ratios.
+    fn analyze_blur_consistency(
+        &self,
+        image: &DynamicImage,
+    ) -> Result<Vec<(SRegion, f64, String)>> {
+        let result = match BlurAnalyzer::with_config(BlurConfig {
+            block_size: self.config.block_size,
+            min_blur_ratio: 1.0 + self.config.sensitivity.max(0.0),
+            ..BlurConfig::default()
+        })
+        .analyze(image)
+        {
+            Ok(result) => result,
+            // Too small, or no edges to measure blur on.
+            Err(ForensicsError::AnalysisFailed(_) | ForensicsError::ImageTooSmall(_)) => {
+                return Ok(Vec::new());
             }
-        }
-
-        if block_sharpness.is_empty() {
-            return results;
-        }
+            Err(e) => return Err(e),
+        };
 
-        let mean_sharpness =
-            block_sharpness.iter().map(|(_, _, s)| s).sum::<f64>() / block_sharpness.len() as f64;
-        let variance = block_sharpness
+        Ok(result
+            .inconsistent_regions
             .iter()
-            .map(|(_, _, s)| (s - mean_sharpness).powi(2))
-            .sum::<f64>()
-            / block_sharpness.len() as f64;
-        let std_dev = variance.sqrt();
-
-        for (bx, by, sharpness) in block_sharpness {
-            let z_score = if std_dev > 0.0 {
-                (sharpness - mean_sharpness).abs() / std_dev
-            } else {
-                0.0
-            };
+            .map(|r| {
+                let kind = match r.kind {
+                    BlurInconsistency::Sharper => "Sharper",
+                    BlurInconsistency::Blurrier => "Blurrier",
+                };
+                (
+                    r.region,
+                    r.confidence,
+                    format!(
+                        "{} than its surroundings: blur of {:.1}px against {:.1}px",
+                        kind, r.blur, r.surrounding_blur
+                    ),
+                )
+            })
+            .collect())
+    }
 
-            if z_score > 2.5 * self.config.sensitivity {
-                let score = (z_score / 5.0).min(1.0);
-                results.push((
-                    SRegion {
-                        x: bx,
-                        y: by,
-                        width: block_size.min(width - bx),
-                        height: block_size.midpoint(height - by),
-                    },
-                    score,
-                ));
+    /// Global contrast enhancement from histogram peaks and gaps, and regions enhanced
+    /// differently from the rest of the image.
+    fn analyze_contrast_consistency(
+        &self,
+        image: &DynamicImage,
+    ) -> Result<Vec<DetectedManipulation>> {
+        let config = ContrastConfig::default();
+        let enhanced_level = config.energy_threshold.ln();
+        let result = match ContrastAnalyzer::with_config(config).analyze(image) {
+            Ok(result) => result,
+            // Too small, or nothing with enough contrast to judge.
+            Err(ForensicsError::AnalysisFailed(_) | ForensicsError::ImageTooSmall(_)) => {
+                return Ok(Vec::new());
             }
-        }
+            Err(e) => return Err(e),
+        };
 
-        results
-    }
-
-    fn calculate_laplcaian_variance(&self, gray: &GrayImage, x: u32, y: u32, size: u32) -> f64 {
-        let (width, height) = gray.dimensions();
-        let mut laplacian_values = Vec::new();
+        let describe = |estimate: &MappingEstimate| match estimate.mapping {
+            ContrastMapping::Gamma(gamma) => {
+                format!("Estimated gamma {:.2} (fit {:.2})", gamma, estimate.fit)
+            }
+            ContrastMapping::Linear { gain, offset } => format!(
+                "Estimated linear stretch with gain {:.2} and offset {:.2} (fit {:.2})",
+                gain, offset, estimate.fit
+            ),
+        };
 
-        for dy in 1..size.saturating_sub(1) {
-            for dx in 1..size.saturating_sub(1) {
-                let px = x + dx;
-                let py = y + dy;
+        let mut manipulations = Vec::new();
 
-                if px > 0 && px + 1 < width && py > 0 && py + 1 < height {
-                    let center = gray.get_pixel(px, py)[0] as f64;
-                    let top = gray.get_pixel(px, py - 1)[0] as f64;
-                    let bottom = gray.get_pixel(px, py + 1)[0] as f64;
-                    let left = gray.get_pixel(px - 1, py)[0] as f64;
-                    let right = gray.get_pixel(px + 1, py)[0] as f64;
+        // Peaks and gaps confined to the enhanced regions are reported with them alone.
+        let enhanced_everywhere =
+            result.enhanced_regions.is_empty() || result.median_log_energy > enhanced_level;
+        if result.global_probability > 0.6 && enhanced_everywhere {
+            let mut evidence: Vec<String> = ["Red", "Green", "Blue"]
+                .iter()
+                .zip(&result.channels)
+                .map(|(name, channel)| {
+                    format!(
+                        "{} histogram high-frequency energy: {:.1}x the noise floor",
+                        name, channel.energy_ratio
+                    )
+                })
+                .collect();
+            evidence.extend(
+                result
+                    .channels
+                    .iter()
+                    .filter_map(|c| c.mapping.as_ref())
+                    .max_by(|a, b| a.fit.total_cmp(&b.fit))
+                    .map(describe),
+            );
 
-                    let laplacian = -4.0 * center + top + bottom + left + right;
-                    laplacian_values.push(laplacian);
-                }
-            }
+            manipulations.push(DetectedManipulation {
+                manipulation_type: ManipulationType::ColorManipulation,
+                region: SRegion {
+                    x: 0,
+                    y: 0,
+                    width: image.width(),
+                    height: image.height(),
+                },
+                confidence: result.global_probability,
+                confidence_level: ConfidenceLevel::from_score(result.global_probability),
+                description: "Histogram peaks and gaps from contrast enhancement".into(),
+                evidence,
+            });
         }
 
-        if laplacian_values.is_empty() {
-            return 0.0;
+        for region in &result.enhanced_regions {
+            let mut evidence = vec![format!(
+                "Histogram energy of {:.1}x the noise floor against {:.1}x for the image",
+                region.log_energy.exp(),
+                region.image_log_energy.exp()
+            )];
+            evidence.extend(region.mapping.as_ref().map(describe));
+
+            manipulations.push(DetectedManipulation {
+                manipulation_type: ManipulationType::Retouching,
+                region: region.region,
+                confidence: region.confidence,
+                confidence_level: ConfidenceLevel::from_score(region.confidence),
+                description: "Contrast enhanced differently from the rest of the image".into(),
+                evidence,
+            });
         }
 
-        let mean = laplacian_values.iter().sum::<f64>() / laplacian_values.len() as f64;
-        let variance = laplacian_values
-            .iter()
-            .map(|v| (v - mean).powi(2))
-            .sum::<f64>()
-            / laplacian_values.len() as f64;
-
-        variance
+        Ok(manipulations)
     }
 
     fn analyze_double_compression(
@@ -283,6 +342,77 @@ impl TamperingDetector {
         }
     }
 
+    /// Median filtering of the whole image, or of the regions where it was applied locally.
+    fn analyze_median_filtering(&self, image: &DynamicImage) -> Result<Vec<DetectedManipulation>> {
+        let mut manipulations = Vec::new();
+        let result = match MedianFilterAnalyzer::new().analyze(image) {
+            Ok(result) => result,
+            // Too small, or nothing textured enough to judge.
+            Err(ForensicsError::AnalysisFailed(_) | ForensicsError::ImageTooSmall(_)) => {
+                return Ok(manipulations);
+            }
+            Err(e) => return Err(e),
+        };
+
+        if result.global.probability > 0.6 {
+            manipulations.push(DetectedManipulation {
+                manipulation_type: ManipulationType::AntiForensics,
+                region: SRegion {
+                    x: 0,
+                    y: 0,
+                    width: image.width(),
+                    height: image.height(),
+                },
+                confidence: result.global.probability,
+                confidence_level: ConfidenceLevel::from_score(result.global.probability),
+                description: "Image shows signs of global median filtering".into(),
+                evidence: vec![
+                    "Anti-forensics indicator: median filtering suppresses JPEG, CFA and \
+                     resampling traces"
+                        .into(),
+                    format!("Streaking ratio: {:.2}", result.global.streaking_ratio),
+                    format!("Median residual ratio: {:.2}", result.global.residual_ratio),
+                    format!("Filtered blocks: {:.1}%", result.filtered_fraction * 100.0),
+                ],
+            });
+            return Ok(manipulations);
+        }
+
+        let block_size = result.block_size;
+        for region in result.filtered_regions {
+            let confidence = result
+                .block_map
+                .indexed_iter()
+                .filter(|&((row, column), _)| {
+                    let (x, y) = (column as u32 * block_size, row as u32 * block_size);
+                    x >= region.x
+                        && x < region.x + region.width
+                        && y >= region.y
+                        && y < region.y + region.height
+                })
+                .filter_map(|(_, p)| *p)
+                .fold(0.0, f64::max);
+
+            if confidence >= self.config.min_confidence {
+                manipulations.push(DetectedManipulation {
+                    manipulation_type: ManipulationType::AntiForensics,
+                    region,
+                    confidence,
+                    confidence_level: ConfidenceLevel::from_score(confidence),
+                    description: "Locally median filtered region".into(),
+                    evidence: vec![
+                        "Anti-forensics indicator: local median filtering can hide \
+                         manipulation traces"
+                            .into(),
+                        format!("Median filtering probability: {:.1}%", confidence * 100.0),
+                    ],
+                });
+            }
+        }
+
+        Ok(manipulations)
+    }
+
     fn create_combined_visualization(
         &self,
         original: &RgbImage,
@@ -413,6 +543,12 @@ impl Detector for TamperingDetector {
             result.add_manipulation(compression);
         }
 
+        if self.config.detect_anti_forensics {
+            for manipulation in self.analyze_median_filtering(image)? {
+                result.add_manipulation(manipulation);
+            }
+        }
+
         result.visualization = self.create_combined_visualization(&rgb, &result.manipulations);
 
         Ok(result)
@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    ForensicsAnalyzer,
+    error::{ForensicsError, Result},
+    hashing::perceptual::{HashKind, PerceptualHashes},
+    image_utils::list_images,
+};
+
+const INDEX_MAGIC: &[u8; 4] = b"IFPH";
+const INDEX_VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 16;
+/// Path length plus the five hashes.
+const MIN_ENTRY_SIZE: u64 = 4 + 5 * 8;
+
+#[derive(Debug, Clone)]
+pub struct HashEntry {
+    pub path: String,
+    pub hashes: PerceptualHashes,
+}
+
+#[derive(Debug, Clone)]
+pub struct HashMatch {
+    pub path: String,
+    pub distance: u32,
+    pub hashes: PerceptualHashes,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryIndexing {
+    pub added: usize,
+    /// Files that could not be opened or decoded.
+    pub skipped: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PerceptualHashIndex {
+    entries: Vec<HashEntry>,
+    /// Position of each path in `entries`.
+    positions: HashMap<String, usize>,
+}
+
+impl PerceptualHashIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        let mut remaining = file.metadata()?.len().saturating_sub(HEADER_SIZE);
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(ForensicsError::UnsupportedFormat(
+                "Not a perceptual hash index".into(),
+            ));
+        }
+
+        let version = Self::read_u32(&mut reader)?;
+        if version != INDEX_VERSION {
+            return Err(ForensicsError::UnsupportedFormat(format!(
+                "Unsupported hash index version {}",
+                version
+            )));
+        }
+
+        let count = Self::read_u64(&mut reader)?;
+        if count > remaining / MIN_ENTRY_SIZE {
+            return Err(ForensicsError::UnsupportedFormat(format!(
+                "Hash index claims {} entries but is too short",
+                count
+            )));
+        }
+        let mut index = Self::new();
+        index.entries.reserve(count as usize);
+
+        for _ in 0..count {
+            let path_len = Self::read_u32(&mut reader)? as u64;
+            if path_len + MIN_ENTRY_SIZE > remaining {
+                return Err(ForensicsError::UnsupportedFormat(
+                    "Truncated entry in hash index".into(),
+                ));
+            }
+            remaining -= path_len + MIN_ENTRY_SIZE;
+
+            let mut path_bytes = vec![0u8; path_len as usize];
+            reader.read_exact(&mut path_bytes)?;
+            let path = String::from_utf8(path_bytes).map_err(|_| {
+                ForensicsError::UnsupportedFormat("Invalid path in hash index".into())
+            })?;
+
+            let hashes = PerceptualHashes {
+                average: Self::read_u64(&mut reader)?,
+                difference: Self::read_u64(&mut reader)?,
+                perceptual: Self::read_u64(&mut reader)?,
+                block_mean: Self::read_u64(&mut reader)?,
+                radial: Self::read_u64(&mut reader)?,
+            };
+
+            index.insert(path, hashes);
+        }
+
+        Ok(index)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+
+        for entry in &self.entries {
+            writer.write_all(&(entry.path.len() as u32).to_le_bytes())?;
+            writer.write_all(entry.path.as_bytes())?;
+
+            for kind in HashKind::ALL {
+                writer.write_all(&entry.hashes.get(kind).to_le_bytes())?;
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[HashEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn insert(&mut self, path: impl Into<String>, hashes: PerceptualHashes) {
+        let path = path.into();
+
+        match self.positions.get(&path) {
+            Some(&position) => self.entries[position].hashes = hashes,
+            None => {
+                self.positions.insert(path.clone(), self.entries.len());
+                self.entries.push(HashEntry { path, hashes });
+            }
+        }
+    }
+
+    pub fn add_analyzer(&mut self, analyzer: &ForensicsAnalyzer) -> Result<()> {
+        let path = analyzer.path().ok_or_else(|| {
+            ForensicsError::InvalidParameter("Analyzer has no file path to index".into())
+        })?;
+
+        self.insert(path, analyzer.perceptual_hashes());
+        Ok(())
+    }
+
+    pub fn add_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let analyzer = ForensicsAnalyzer::new(path)?;
+        self.add_analyzer(&analyzer)
+    }
+
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> Result<DirectoryIndexing> {
+        let outcomes = list_images(directory)?
+            .par_iter()
+            .map(|path| {
+                ForensicsAnalyzer::new(path)
+                    .ok()
+                    .and_then(|analyzer| {
+                        analyzer
+                            .path()
+                            .map(|p| (p.to_string(), analyzer.perceptual_hashes()))
+                    })
+                    .ok_or_else(|| path.clone())
+            })
+            .collect::<Vec<_>>();
+
+        let mut added = 0;
+        let mut skipped = Vec::new();
+
+        for outcome in outcomes {
+            match outcome {
+                Ok((path, hashes)) => {
+                    self.insert(path, hashes);
+                    added += 1;
+                }
+                Err(path) => skipped.push(path),
+            }
+        }
+
+        Ok(DirectoryIndexing { added, skipped })
+    }
+
+    pub fn query(&self, hashes: &PerceptualHashes, kind: HashKind, radius: u32) -> Vec<HashMatch> {
+        let mut matches = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let distance = entry.hashes.distance(hashes, kind);
+                (distance <= radius).then(|| HashMatch {
+                    path: entry.path.clone(),
+                    distance,
+                    hashes: entry.hashes,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        matches.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        matches
+    }
+
+    pub fn query_analyzer(
+        &self,
+        analyzer: &ForensicsAnalyzer,
+        kind: HashKind,
+        radius: u32,
+    ) -> Vec<HashMatch> {
+        self.query(&analyzer.perceptual_hashes(), kind, radius)
+    }
+
+    fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_round_trip_and_query() {
+        let mut index = PerceptualHashIndex::new();
+        let base = PerceptualHashes {
+            average: 0xFFFF_0000_FFFF_0000,
+            difference: 0x0F0F_0F0F_0F0F_0F0F,
+            perceptual: 0x1234_5678_9ABC_DEF0,
+            block_mean: 0,
+            radial: u64::MAX,
+        };
+        let near = PerceptualHashes {
+            perceptual: base.perceptual ^ 0b111,
+            ..base
+        };
+        let far = PerceptualHashes {
+            perceptual: !base.perceptual,
+            ..base
+        };
+
+        index.insert("near.png", near);
+        index.insert("far.png", far);
+        index.insert("exact.png", base);
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("hashes.idx");
+        index.save(&file).unwrap();
+
+        let loaded = PerceptualHashIndex::open(&file).unwrap();
+        assert_eq!(loaded.len(), 3);
+
+        let matches = loaded.query(&base, HashKind::Perceptual, 5);
+        let paths = matches.iter().map(|m| m.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["exact.png", "near.png"]);
+        assert_eq!(matches[1].distance, 3);
+
+        let bytes = fs::read(&file).unwrap();
+        fs::write(&file, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(PerceptualHashIndex::open(&file).is_err());
+    }
+}
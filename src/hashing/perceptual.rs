@@ -0,0 +1,211 @@
+use image::{DynamicImage, GrayImage, imageops::FilterType};
+use ndarray::Array2;
+use num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use crate::image_utils::{dct_2d, gaussian_blur_3x3, gray_to_array, median, rgb_to_gray};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashKind {
+    Average,
+    Difference,
+    Perceptual,
+    BlockMean,
+    Radial,
+}
+
+impl HashKind {
+    pub const ALL: [HashKind; 5] = [
+        HashKind::Average,
+        HashKind::Difference,
+        HashKind::Perceptual,
+        HashKind::BlockMean,
+        HashKind::Radial,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerceptualHashes {
+    pub average: u64,
+    pub difference: u64,
+    pub perceptual: u64,
+    pub block_mean: u64,
+    pub radial: u64,
+}
+
+impl PerceptualHashes {
+    pub fn get(&self, kind: HashKind) -> u64 {
+        match kind {
+            HashKind::Average => self.average,
+            HashKind::Difference => self.difference,
+            HashKind::Perceptual => self.perceptual,
+            HashKind::BlockMean => self.block_mean,
+            HashKind::Radial => self.radial,
+        }
+    }
+
+    pub fn distance(&self, other: &PerceptualHashes, kind: HashKind) -> u32 {
+        hamming_distance(self.get(kind), other.get(kind))
+    }
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+pub struct PerceptualHasher {
+    dct_size: u32,
+    radial_size: u32,
+    radial_angles: usize,
+}
+
+impl PerceptualHasher {
+    pub fn new() -> Self {
+        Self {
+            dct_size: 32,
+            radial_size: 128,
+            radial_angles: 180,
+        }
+    }
+
+    pub fn hash(&self, image: &DynamicImage) -> PerceptualHashes {
+        let gray = rgb_to_gray(&image.to_rgb8());
+
+        PerceptualHashes {
+            average: self.average_hash(&gray),
+            difference: self.difference_hash(&gray),
+            perceptual: self.perceptual_hash(&gray),
+            block_mean: self.block_mean_hash(&gray),
+            radial: self.radial_hash(&gray),
+        }
+    }
+
+    pub fn average_hash(&self, gray: &GrayImage) -> u64 {
+        let small = image::imageops::resize(gray, 8, 8, FilterType::Triangle);
+        let values = small.pixels().map(|p| p[0] as f64).collect::<Vec<_>>();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+
+        Self::bits_from(values.iter().map(|&v| v > mean))
+    }
+
+    pub fn difference_hash(&self, gray: &GrayImage) -> u64 {
+        let small = image::imageops::resize(gray, 9, 8, FilterType::Triangle);
+
+        Self::bits_from(
+            (0..8)
+                .flat_map(|y| (0..8).map(move |x| (x, y)))
+                .map(|(x, y)| small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]),
+        )
+    }
+
+    pub fn perceptual_hash(&self, gray: &GrayImage) -> u64 {
+        let small =
+            image::imageops::resize(gray, self.dct_size, self.dct_size, FilterType::Triangle);
+        let coeffs = dct_2d(&gray_to_array(&small));
+
+        let low = (0..8)
+            .flat_map(|v| (0..8).map(move |u| (v, u)))
+            .map(|(v, u)| coeffs[[v, u]])
+            .collect::<Vec<_>>();
+
+        let median = median(&mut low[1..].to_vec());
+
+        Self::bits_from(low.iter().map(|&c| c > median))
+    }
+
+    pub fn block_mean_hash(&self, gray: &GrayImage) -> u64 {
+        let small = image::imageops::resize(gray, 64, 64, FilterType::Triangle);
+        let mut means = Vec::with_capacity(64);
+
+        for by in 0..8 {
+            for bx in 0..8 {
+                let mut sum = 0.0;
+                for y in by * 8..(by + 1) * 8 {
+                    for x in bx * 8..(bx + 1) * 8 {
+                        sum += small.get_pixel(x, y)[0] as f64;
+                    }
+                }
+                means.push(sum / 64.0);
+            }
+        }
+
+        let median = median(&mut means.clone());
+
+        Self::bits_from(means.iter().map(|&m| m > median))
+    }
+
+    /// Radial variance projections turn a rotation into a cyclic shift, so the hash is
+    /// built from the shift-invariant DFT magnitudes of the projection vector.
+    pub fn radial_hash(&self, gray: &GrayImage) -> u64 {
+        let size = self.radial_size;
+        let small = gaussian_blur_3x3(&image::imageops::resize(
+            gray,
+            size,
+            size,
+            FilterType::Triangle,
+        ));
+        let pixels = gray_to_array(&small);
+
+        let projections = self.radial_projections(&pixels);
+
+        let mut spectrum = projections
+            .iter()
+            .map(|&v| Complex::new(v, 0.0))
+            .collect::<Vec<_>>();
+
+        let mut planner = FftPlanner::new();
+        planner
+            .plan_fft_forward(spectrum.len())
+            .process(&mut spectrum);
+
+        let magnitudes = spectrum
+            .iter()
+            .skip(1)
+            .take(64)
+            .map(|c| c.norm())
+            .collect::<Vec<_>>();
+
+        let median = median(&mut magnitudes.clone());
+
+        Self::bits_from(magnitudes.iter().map(|&m| m > median))
+    }
+
+    fn radial_projections(&self, pixels: &Array2<f64>) -> Vec<f64> {
+        let (height, width) = pixels.dim();
+        let cx = (width as f64 - 1.0) / 2.0;
+        let cy = (height as f64 - 1.0) / 2.0;
+        let radius = cx.min(cy) - 1.0;
+
+        (0..self.radial_angles)
+            .map(|a| {
+                let theta = a as f64 * std::f64::consts::PI / self.radial_angles as f64;
+                let (sin_t, cos_t) = theta.sin_cos();
+
+                let samples = (-(radius as i32)..=radius as i32)
+                    .map(|r| {
+                        let x = (cx + r as f64 * cos_t).round() as usize;
+                        let y = (cy + r as f64 * sin_t).round() as usize;
+                        pixels[[y.min(height - 1), x.min(width - 1)]]
+                    })
+                    .collect::<Vec<_>>();
+
+                let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+                samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / samples.len() as f64
+            })
+            .collect()
+    }
+
+    fn bits_from(bits: impl Iterator<Item = bool>) -> u64 {
+        bits.take(64).enumerate().fold(
+            0u64,
+            |hash, (i, bit)| if bit { hash | (1 << i) } else { hash },
+        )
+    }
+}
+
+impl Default for PerceptualHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
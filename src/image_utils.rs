@@ -1,7 +1,19 @@
-use std::f64;
+use std::{
+    f64, fs,
+    path::{Path, PathBuf},
+};
 
-use image::{GrayImage, Luma, RgbImage};
+use image::{GrayImage, Luma, Rgb, RgbImage};
 use ndarray::Array2;
+use num_complex::Complex;
+use rayon::prelude::*;
+use rustfft::FftPlanner;
+
+use crate::{SRegion, error::Result};
+
+pub mod wavelet;
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp", "gif"];
 
 pub fn rgb_to_gray(image: &RgbImage) -> GrayImage {
     let (width, height) = image.dimensions();
@@ -16,6 +28,18 @@ pub fn rgb_to_gray(image: &RgbImage) -> GrayImage {
     gray
 }
 
+pub fn rgb_to_luminance(image: &RgbImage) -> Array2<f64> {
+    let (width, height) = image.dimensions();
+    let mut arr = Array2::zeros((height as usize, width as usize));
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        arr[[y as usize, x as usize]] =
+            0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+    }
+
+    arr
+}
+
 pub fn gray_to_array(image: &GrayImage) -> Array2<f64> {
     let (width, height) = image.dimensions();
     let mut arr = Array2::zeros((height as usize, width as usize));
@@ -41,6 +65,86 @@ pub fn array_to_gray(arr: &Array2<f64>) -> GrayImage {
     image
 }
 
+pub fn rgb_to_luminance_f32(image: &RgbImage) -> Array2<f32> {
+    let (width, height) = image.dimensions();
+
+    Array2::from_shape_fn((height as usize, width as usize), |(y, x)| {
+        let pixel = image.get_pixel(x as u32, y as u32);
+        0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+    })
+}
+
+/// Rounds and clamps a float image into 8 bits. Only meant for display.
+pub fn float_to_gray(arr: &Array2<f32>) -> GrayImage {
+    let (height, width) = arr.dim();
+
+    GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        Luma([arr[[y as usize, x as usize]].round().clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// Encodes a float residual as an 8-bit pattern where 128 is zero, clamping outliers.
+pub fn residual_to_pattern(residual: &Array2<f32>) -> GrayImage {
+    let (height, width) = residual.dim();
+
+    GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        let v = residual[[y as usize, x as usize]] + 128.0;
+        Luma([v.round().clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// Decodes the `residual + 128` 8-bit pattern encoding back into a float residual.
+pub fn pattern_to_residual(pattern: &GrayImage) -> Array2<f32> {
+    let (width, height) = pattern.dimensions();
+
+    Array2::from_shape_fn((height as usize, width as usize), |(y, x)| {
+        pattern.get_pixel(x as u32, y as u32)[0] as f32 - 128.0
+    })
+}
+
+/// 3x3 Gaussian blur on a float image, replicating the border.
+pub fn gaussian_blur_3x3_f32(arr: &Array2<f32>) -> Array2<f32> {
+    const KERNEL: [f32; 3] = [0.25, 0.5, 0.25];
+    let (height, width) = arr.dim();
+
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        let mut sum = 0.0;
+        for (ky, wy) in KERNEL.iter().enumerate() {
+            let yy = (y + ky).saturating_sub(1).min(height - 1);
+            for (kx, wx) in KERNEL.iter().enumerate() {
+                let xx = (x + kx).saturating_sub(1).min(width - 1);
+                sum += arr[[yy, xx]] * wy * wx;
+            }
+        }
+        sum
+    })
+}
+
+/// Box mean over a `size x size` window, shrinking the window at the borders.
+pub fn local_mean(input: &Array2<f64>, size: usize) -> Array2<f64> {
+    let (rows, cols) = input.dim();
+    let radius = size / 2;
+    let mut integral = Array2::<f64>::zeros((rows + 1, cols + 1));
+
+    for y in 0..rows {
+        let mut row_sum = 0.0;
+        for x in 0..cols {
+            row_sum += input[[y, x]];
+            integral[[y + 1, x + 1]] = integral[[y, x + 1]] + row_sum;
+        }
+    }
+
+    Array2::from_shape_fn((rows, cols), |(y, x)| {
+        let y0 = y.saturating_sub(radius);
+        let x0 = x.saturating_sub(radius);
+        let y1 = (y + radius + 1).min(rows);
+        let x1 = (x + radius + 1).min(cols);
+
+        let sum = integral[[y1, x1]] - integral[[y0, x1]] - integral[[y1, x0]] + integral[[y0, x0]];
+        sum / ((y1 - y0) * (x1 - x0)) as f64
+    })
+}
+
 pub fn gaussian_blur_3x3(image: &GrayImage) -> GrayImage {
     let kernel = [
         [1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0],
@@ -132,3 +236,424 @@ pub fn block_variance(block: &[u8]) -> f64 {
 
     variance
 }
+
+/// Median of `values`, sorting them in place; 0 for an empty slice.
+pub fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+pub fn dct_2d(input: &Array2<f64>) -> Array2<f64> {
+    let (rows, cols) = input.dim();
+    let row_basis = dct_basis(rows);
+    let col_basis = dct_basis(cols);
+
+    let rows_transformed = Array2::from_shape_fn((rows, cols), |(k, x)| {
+        (0..rows)
+            .map(|y| row_basis[[k, y]] * input[[y, x]])
+            .sum::<f64>()
+    });
+
+    Array2::from_shape_fn((rows, cols), |(k, l)| {
+        (0..cols)
+            .map(|x| rows_transformed[[k, x]] * col_basis[[l, x]])
+            .sum::<f64>()
+    })
+}
+
+fn dct_basis(n: usize) -> Array2<f64> {
+    Array2::from_shape_fn((n, n), |(k, i)| {
+        let scale = if k == 0 {
+            (1.0 / n as f64).sqrt()
+        } else {
+            (2.0 / n as f64).sqrt()
+        };
+        scale * (f64::consts::PI * (2.0 * i as f64 + 1.0) * k as f64 / (2.0 * n as f64)).cos()
+    })
+}
+
+pub fn fft_2d(input: &Array2<Complex<f64>>) -> Array2<Complex<f64>> {
+    transform_2d(input, false)
+}
+
+pub fn ifft_2d(input: &Array2<Complex<f64>>) -> Array2<Complex<f64>> {
+    let (rows, cols) = input.dim();
+    let norm = (rows * cols) as f64;
+
+    transform_2d(input, true).mapv(|c| c / norm)
+}
+
+fn transform_2d(input: &Array2<Complex<f64>>, inverse: bool) -> Array2<Complex<f64>> {
+    let (rows, cols) = input.dim();
+    let mut planner = FftPlanner::new();
+    let (row_fft, col_fft) = if inverse {
+        (
+            planner.plan_fft_inverse(cols),
+            planner.plan_fft_inverse(rows),
+        )
+    } else {
+        (
+            planner.plan_fft_forward(cols),
+            planner.plan_fft_forward(rows),
+        )
+    };
+
+    let mut output = input.clone();
+
+    for mut row in output.rows_mut() {
+        let mut buffer = row.to_vec();
+        row_fft.process(&mut buffer);
+        row.iter_mut().zip(buffer).for_each(|(dst, src)| *dst = src);
+    }
+
+    for mut col in output.columns_mut() {
+        let mut buffer = col.to_vec();
+        col_fft.process(&mut buffer);
+        col.iter_mut().zip(buffer).for_each(|(dst, src)| *dst = src);
+    }
+
+    output
+}
+
+pub fn bilinear_sample(arr: &Array2<f64>, x: f64, y: f64) -> Option<f64> {
+    let (height, width) = arr.dim();
+
+    if x < 0.0 || y < 0.0 || x > (width - 1) as f64 || y > (height - 1) as f64 {
+        return None;
+    }
+
+    let x0 = (x.floor() as usize).min(width.saturating_sub(2));
+    let y0 = (y.floor() as usize).min(height.saturating_sub(2));
+    let x1 = (x0 + 1).min(width - 1);
+    let y1 = (y0 + 1).min(height - 1);
+    let fx = x - x0 as f64;
+    let fy = y - y0 as f64;
+
+    let top = arr[[y0, x0]] * (1.0 - fx) + arr[[y0, x1]] * fx;
+    let bottom = arr[[y1, x0]] * (1.0 - fx) + arr[[y1, x1]] * fx;
+
+    Some(top * (1.0 - fy) + bottom * fy)
+}
+
+/// Popescu-Farid expectation-maximization fit of a linear predictor over the
+/// `(2 * radius + 1)^2 - 1` neighbours of each pixel, with outliers drawn from a uniform
+/// density over the 8-bit range. Returns the coefficients (row-major, center skipped), the
+/// inlier residual standard deviation and the posterior probability of each pixel being
+/// predictable. Border pixels keep the 0.5 prior.
+pub fn em_linear_predictor(
+    input: &Array2<f64>,
+    radius: usize,
+    iterations: usize,
+) -> (Vec<f64>, f64, Array2<f64>) {
+    let (height, width) = input.dim();
+    let r = radius.max(1);
+    let offsets = (0..=2 * r)
+        .flat_map(|dy| (0..=2 * r).map(move |dx| (dy, dx)))
+        .filter(|&offset| offset != (r, r))
+        .collect::<Vec<_>>();
+    let k = offsets.len();
+
+    let mut coefficients = vec![1.0 / k as f64; k];
+    let mut probability = Array2::<f64>::from_elem((height, width), 0.5);
+
+    if height <= 2 * r || width <= 2 * r {
+        return (coefficients, 0.0, probability);
+    }
+
+    let outlier_density = 1.0 / 256.0;
+    let gather = |y: usize, x: usize, values: &mut [f64]| {
+        for (value, &(dy, dx)) in values.iter_mut().zip(&offsets) {
+            *value = input[[y + dy - r, x + dx - r]];
+        }
+    };
+    let predict = |values: &[f64], coefficients: &[f64]| {
+        values
+            .iter()
+            .zip(coefficients)
+            .map(|(v, c)| v * c)
+            .sum::<f64>()
+    };
+
+    let mut sigma = {
+        let sum = (r..height - r)
+            .into_par_iter()
+            .map(|y| {
+                let mut values = vec![0.0; k];
+                (r..width - r)
+                    .map(|x| {
+                        gather(y, x, &mut values);
+                        (input[[y, x]] - predict(&values, &coefficients)).powi(2)
+                    })
+                    .sum::<f64>()
+            })
+            .sum::<f64>();
+        let count = ((height - 2 * r) * (width - 2 * r)) as f64;
+        (sum / count).sqrt().max(1.0)
+    };
+
+    for _ in 0..iterations {
+        let rows = (r..height - r)
+            .into_par_iter()
+            .map(|y| {
+                let mut values = vec![0.0; k];
+                let mut normal = vec![vec![0.0; k]; k];
+                let mut rhs = vec![0.0; k];
+                let mut weighted_error = 0.0;
+                let mut weight_sum = 0.0;
+                let mut row = Vec::with_capacity(width - 2 * r);
+
+                for x in r..width - r {
+                    gather(y, x, &mut values);
+                    let target = input[[y, x]];
+                    let residual = target - predict(&values, &coefficients);
+
+                    let inlier = (-residual * residual / (2.0 * sigma * sigma)).exp()
+                        / (sigma * (2.0 * f64::consts::PI).sqrt());
+                    let w = inlier / (inlier + outlier_density);
+                    row.push(w);
+
+                    weighted_error += w * residual * residual;
+                    weight_sum += w;
+
+                    for (i, (normal_row, b)) in normal.iter_mut().zip(rhs.iter_mut()).enumerate() {
+                        *b += w * values[i] * target;
+                        for (j, value) in normal_row.iter_mut().enumerate().skip(i) {
+                            *value += w * values[i] * values[j];
+                        }
+                    }
+                }
+
+                (row, normal, rhs, weighted_error, weight_sum)
+            })
+            .collect::<Vec<_>>();
+
+        let mut normal = vec![vec![0.0; k]; k];
+        let mut rhs = vec![0.0; k];
+        let mut weighted_error = 0.0;
+        let mut weight_sum = 0.0;
+
+        for (y, (row, row_normal, row_rhs, error, weight)) in rows.into_iter().enumerate() {
+            for (x, w) in row.into_iter().enumerate() {
+                probability[[y + r, x + r]] = w;
+            }
+            for (total, part) in normal.iter_mut().zip(&row_normal) {
+                for (t, p) in total.iter_mut().zip(part) {
+                    *t += p;
+                }
+            }
+            for (t, p) in rhs.iter_mut().zip(&row_rhs) {
+                *t += p;
+            }
+            weighted_error += error;
+            weight_sum += weight;
+        }
+
+        let symmetric = (0..k)
+            .map(|i| (0..k).map(|j| normal[i.min(j)][i.max(j)]).collect())
+            .collect();
+
+        let Some(updated) = solve_linear_system(symmetric, rhs) else {
+            break;
+        };
+
+        let change = updated
+            .iter()
+            .zip(&coefficients)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+
+        coefficients = updated;
+        sigma = (weighted_error / weight_sum.max(1e-9)).sqrt().max(0.05);
+
+        if change < 1e-4 {
+            break;
+        }
+    }
+
+    (coefficients, sigma, probability)
+}
+
+/// Solves the square system `a * x = b` by Gaussian elimination with partial pivoting.
+/// Returns `None` when the matrix is singular.
+pub fn solve_linear_system(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let (pivot_row, pivot_b) = (a[col].clone(), b[col]);
+        for (row, rhs) in a.iter_mut().zip(b.iter_mut()).skip(col + 1) {
+            let factor = row[col] / pivot_row[col];
+            if factor == 0.0 {
+                continue;
+            }
+            for (value, pivot_value) in row.iter_mut().zip(&pivot_row).skip(col) {
+                *value -= factor * pivot_value;
+            }
+            *rhs -= factor * pivot_b;
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum = (row + 1..n).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Some(x)
+}
+
+/// Pearson correlation of two coefficient vectors, clamped at zero.
+pub fn coefficient_similarity(coeffs1: &[f64], coeffs2: &[f64]) -> f64 {
+    if coeffs1.len() != coeffs2.len() || coeffs1.is_empty() {
+        return 0.0;
+    }
+
+    let mean1 = coeffs1.iter().sum::<f64>() / coeffs1.len() as f64;
+    let mean2 = coeffs2.iter().sum::<f64>() / coeffs2.len() as f64;
+
+    let mut numerator = 0.0;
+    let mut denom1 = 0.0;
+    let mut denom2 = 0.0;
+
+    for (&c1, &c2) in coeffs1.iter().zip(coeffs2.iter()) {
+        let d1 = c1 - mean1;
+        let d2 = c2 - mean2;
+        numerator += d1 * d2;
+        denom1 += d1 * d1;
+        denom2 += d2 * d2;
+    }
+
+    let denom = (denom1 * denom2).sqrt();
+    if denom < 1e-10 {
+        0.0
+    } else {
+        (numerator / denom).max(0.0)
+    }
+}
+
+pub fn regions_overlap(a: &SRegion, b: &SRegion) -> bool {
+    let overlap_x = a.x < b.x + b.width && a.x + a.width > b.x;
+    let overlap_y = a.y < b.y + b.height && a.y + a.height > b.y;
+
+    overlap_x && overlap_y
+}
+
+/// Whether two regions overlap or touch.
+pub fn regions_adjacent(a: &SRegion, b: &SRegion) -> bool {
+    !(a.x + a.width < b.x || b.x + b.width < a.x || a.y + a.height < b.y || b.y + b.height < a.y)
+}
+
+/// Bounding box of two regions.
+pub fn merge_two_regions(a: &SRegion, b: &SRegion) -> SRegion {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let x2 = (a.x + a.width).max(b.x + b.width);
+    let y2 = (a.y + a.height).max(b.y + b.height);
+
+    SRegion {
+        x,
+        y,
+        width: x2 - x,
+        height: y2 - y,
+    }
+}
+
+/// Repeatedly replaces adjacent regions by their bounding box until none touch.
+pub fn merge_regions(regions: Vec<SRegion>) -> Vec<SRegion> {
+    let mut merged = Vec::new();
+    let mut used = vec![false; regions.len()];
+
+    for i in 0..regions.len() {
+        if used[i] {
+            continue;
+        }
+
+        let mut current = regions[i];
+        used[i] = true;
+
+        loop {
+            let mut found = false;
+            for (j, region) in regions.iter().enumerate() {
+                if !used[j] && regions_adjacent(&current, region) {
+                    current = merge_two_regions(&current, region);
+                    used[j] = true;
+                    found = true;
+                }
+            }
+
+            if !found {
+                break;
+            }
+        }
+
+        merged.push(current);
+    }
+
+    merged
+}
+
+/// Draws a one-pixel outline of `region`, clipped to the image.
+pub fn draw_rectangle(image: &mut RgbImage, region: &SRegion, color: Rgb<u8>) {
+    let (width, height) = image.dimensions();
+    if region.width == 0 || region.height == 0 {
+        return;
+    }
+
+    for x in region.x..(region.x + region.width).min(width) {
+        if region.y < height {
+            image.put_pixel(x, region.y, color);
+        }
+        if region.y + region.height - 1 < height {
+            image.put_pixel(x, region.y + region.height - 1, color);
+        }
+    }
+
+    for y in region.y..(region.y + region.height).min(height) {
+        if region.x < width {
+            image.put_pixel(region.x, y, color);
+        }
+        if region.x + region.width - 1 < width {
+            image.put_pixel(region.x + region.width - 1, y, color);
+        }
+    }
+}
+
+pub fn list_images<P: AsRef<Path>>(directory: P) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let is_image = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false);
+
+        if is_image {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
@@ -1,20 +1,26 @@
 use std::path::Path;
 
 use image::{DynamicImage, GrayImage, RgbImage};
+use ndarray::Array2;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     analysis::{
-        copy_move::CopyMoveDetector, ela::ElaAnalyzer, jpeg_analysis::JpegAnalyzer,
+        cfa_analysis::{CfaAnalyzer, CfaMetadataCheck},
+        copy_move::CopyMoveDetector,
+        ela::ElaAnalyzer,
+        jpeg_analysis::JpegAnalyzer,
         noise::NoiseAnalyzer,
     },
     error::{ForensicsError, Result},
+    hashing::perceptual::{PerceptualHasher, PerceptualHashes},
     metadata::exif::ExifExtractor,
 };
 
 pub mod analysis;
 pub mod detection;
 pub mod error;
+pub mod hashing;
 pub mod image_utils;
 pub mod metadata;
 pub mod report;
@@ -71,6 +77,14 @@ impl ForensicsAnalyzer {
         self
     }
 
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn perceptual_hashes(&self) -> PerceptualHashes {
+        PerceptualHasher::new().hash(&self.original)
+    }
+
     pub fn ela(&self, quality: u8) -> Result<ElaResult> {
         let analyzer = ElaAnalyzer::new(quality);
         analyzer.analyze(&self.original)
@@ -105,6 +119,14 @@ impl ForensicsAnalyzer {
         }
     }
 
+    /// Checks the CFA layout and demosaicing traces against the camera named in EXIF.
+    pub fn check_cfa_metadata(&self) -> Result<CfaMetadataCheck> {
+        let metadata = self.extract_metadata()?;
+        let analyzer = CfaAnalyzer::new();
+        let layout = analyzer.analyze_layout(&self.original)?;
+        Ok(analyzer.check_metadata(&layout, &metadata))
+    }
+
     pub fn full_analysis(&self) -> Result<FullAnalysisReport> {
         let ela = self.ela(self.config.ela_quality)?;
         let copy_move = self.detect_cop_move()?;
@@ -202,6 +224,11 @@ pub struct SRegion {
 
 #[derive(Debug, Clone)]
 pub struct NoiseResult {
+    /// Signed high-pass residual in gray levels.
+    pub noise_residual: Array2<f32>,
+    /// Local standard deviation in gray levels.
+    pub local_std_map: Array2<f32>,
+    /// 8-bit renderings of `|noise_residual|` and `local_std_map` for display.
     pub noise_map: GrayImage,
     pub local_variance_map: GrayImage,
     pub inconsistency_score: f64,
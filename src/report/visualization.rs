@@ -320,6 +320,7 @@ impl Visualizer {
             ManipulationType::Rotation => Rgb([0, 255, 0]),
             ManipulationType::ColorManipulation => Rgb([128, 0, 255]),
             ManipulationType::AIGenerated => Rgb([255, 128, 128]),
+            ManipulationType::AntiForensics => Rgb([0, 128, 255]),
             ManipulationType::Unknown => Rgb([128, 128, 128]),
         }
     }